@@ -11,7 +11,7 @@ fn main() -> Result<()> {
     let key = pubkey!("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
     println!("Attempting to parse account {}", key.to_string());
 
-    let IdlDeserializedAccount { program_name, type_name, data } = deser
+    let IdlDeserializedAccount { program_name, type_name, data, .. } = deser
         .fetch_and_deserialize_account(&key, None)?;
     println!("Found program: {}", program_name);
     println!("Found account type: {}", type_name);
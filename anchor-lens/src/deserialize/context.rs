@@ -0,0 +1,240 @@
+use crate::error::{DataLocation, LensError};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Controls how strictly data must match the IDL layout it's decoded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strictness {
+    /// Decode what the IDL describes and ignore the rest. Leftover bytes are
+    /// common in Anchor accounts that were allocated with room to grow,
+    /// and tuples are cut short at the first field that fails.
+    #[default]
+    Lenient,
+    /// Decode like [Strictness::Lenient], but report leftover bytes and
+    /// tuples cut short as [DecodeWarning]s alongside the output.
+    Warn,
+    /// Treat leftover bytes and failing fields as errors.
+    Strict,
+}
+
+impl FromStr for Strictness {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lenient" => Ok(Strictness::Lenient),
+            "warn" => Ok(Strictness::Warn),
+            "strict" => Ok(Strictness::Strict),
+            _ => Err(anyhow!(
+                "Unknown strictness {}, expected one of lenient, warn, strict",
+                s
+            )),
+        }
+    }
+}
+
+/// One step into a decoded value, used to build paths like `Pool.fees[2].numerator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The account, instruction or event being decoded, or a struct field.
+    Field(String),
+    /// An element of a `Vec` or array, or a tuple field.
+    Index(usize),
+    /// The enum variant that was selected.
+    Variant(String),
+}
+
+/// Renders a path to a value the way it would be accessed in Rust.
+pub fn format_path(path: &[PathSegment]) -> String {
+    let mut formatted = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Field(name) | PathSegment::Variant(name) => {
+                if !formatted.is_empty() {
+                    formatted.push('.');
+                }
+                formatted.push_str(name);
+            }
+            PathSegment::Index(i) => formatted.push_str(&format!("[{}]", i)),
+        }
+    }
+    formatted
+}
+
+/// A recoverable decoding problem, reported instead of an error
+/// under [Strictness::Warn].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodeWarning {
+    pub path: String,
+    pub offset: usize,
+    pub message: String,
+}
+
+/// State carried through a single decode: where we are in the value and the data,
+/// and what to do about anything that doesn't line up with the IDL.
+#[derive(Debug, Clone)]
+pub struct DecodeContext {
+    pub strictness: Strictness,
+    /// The program the data belongs to, reported in errors.
+    pub program_id: Option<Pubkey>,
+    /// Length of the whole buffer, so byte offsets can be recovered
+    /// from the length of what's left to read.
+    data_len: usize,
+    type_name: String,
+    path: Vec<PathSegment>,
+    pub warnings: Vec<DecodeWarning>,
+}
+
+impl DecodeContext {
+    /// `data` is the full buffer that offsets are reported relative to, e.g. the
+    /// account data including its discriminator, and `type_name` is the name
+    /// of the account, instruction or event it holds.
+    pub fn new(
+        strictness: Strictness,
+        program_id: Option<Pubkey>,
+        data: &[u8],
+        type_name: &str,
+    ) -> Self {
+        Self {
+            strictness,
+            program_id,
+            data_len: data.len(),
+            type_name: type_name.to_string(),
+            path: vec![PathSegment::Field(type_name.to_string())],
+            warnings: vec![],
+        }
+    }
+
+    /// Byte offset of the cursor `remaining` into the full buffer.
+    pub fn offset(&self, remaining: &[u8]) -> usize {
+        self.data_len.saturating_sub(remaining.len())
+    }
+
+    pub fn path(&self) -> String {
+        format_path(&self.path)
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.path.push(segment);
+    }
+
+    pub fn pop(&mut self) {
+        self.path.pop();
+    }
+
+    /// The current path and cursor, for locating errors.
+    pub fn location(&self, remaining: &[u8]) -> DataLocation {
+        DataLocation {
+            program_id: self.program_id,
+            type_name: self.type_name.clone(),
+            path: self.path(),
+            offset: self.offset(remaining),
+        }
+    }
+
+    /// Reads a single Borsh value at the cursor, reporting where it failed if it does.
+    pub fn read<T: Readable>(&self, raw_data: &mut &[u8]) -> anyhow::Result<T> {
+        let before = *raw_data;
+        if before.len() < T::len_needed(before) {
+            return Err(LensError::BufferUnderflow {
+                location: self.location(before),
+            }
+            .into());
+        }
+        T::deserialize(raw_data).map_err(|err| {
+            let location = self.location(before);
+            match err.kind() {
+                std::io::ErrorKind::UnexpectedEof => LensError::BufferUnderflow { location }.into(),
+                _ => LensError::InvalidData {
+                    reason: err.to_string(),
+                    location,
+                }
+                .into(),
+            }
+        })
+    }
+
+    /// Handles a problem that decoding can carry on past: an error under
+    /// [Strictness::Strict], a warning under [Strictness::Warn], ignored otherwise.
+    pub fn recoverable(&mut self, err: anyhow::Error) -> anyhow::Result<()> {
+        match self.strictness {
+            Strictness::Strict => Err(err),
+            Strictness::Warn => {
+                let located = err
+                    .downcast_ref::<LensError>()
+                    .and_then(|err| Some((err.reason(), err.location()?)));
+                let warning = match located {
+                    Some((message, location)) => DecodeWarning {
+                        path: location.path.clone(),
+                        offset: location.offset,
+                        message,
+                    },
+                    None => DecodeWarning {
+                        path: self.path(),
+                        offset: 0,
+                        message: err.to_string(),
+                    },
+                };
+                self.warnings.push(warning);
+                Ok(())
+            }
+            Strictness::Lenient => Ok(()),
+        }
+    }
+
+    /// Checks that decoding consumed the whole buffer.
+    pub fn finish(&mut self, remaining: &[u8]) -> anyhow::Result<()> {
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        let err = LensError::TrailingBytes {
+            len: remaining.len(),
+            location: self.location(remaining),
+        };
+        self.recoverable(err.into())
+    }
+}
+
+/// A Borsh value [DecodeContext::read] can tell is cut short before reading it,
+/// as Borsh reports running out of input no differently from an invalid value.
+pub trait Readable: borsh::BorshDeserialize {
+    /// How many bytes reading a value from the start of `data` takes.
+    fn len_needed(data: &[u8]) -> usize;
+}
+
+macro_rules! impl_readable_fixed {
+    ($($ty:ty),*) => {
+        $(
+            impl Readable for $ty {
+                fn len_needed(_data: &[u8]) -> usize {
+                    std::mem::size_of::<$ty>()
+                }
+            }
+        )*
+    };
+}
+
+impl_readable_fixed!(bool, u8, i8, u16, i16, u32, i32, f32, u64, i64, f64, u128, i128, Pubkey);
+
+/// Length prefixed values need their 4 byte length, then that many bytes.
+fn prefixed_len_needed(data: &[u8]) -> usize {
+    match data.get(..4) {
+        Some(len) => 4 + u32::from_le_bytes(len.try_into().unwrap()) as usize,
+        None => 4,
+    }
+}
+
+impl Readable for Vec<u8> {
+    fn len_needed(data: &[u8]) -> usize {
+        prefixed_len_needed(data)
+    }
+}
+
+impl Readable for String {
+    fn len_needed(data: &[u8]) -> usize {
+        prefixed_len_needed(data)
+    }
+}
@@ -0,0 +1,44 @@
+//! Decoders for programs whose serialization no IDL describes, registered
+//! with an [crate::AnchorLens] by program ID.
+
+use serde_json::Value;
+
+/// An account an instruction takes, as named by [ProgramDecoder::name_accounts].
+pub use crate::deserialize::native::NativeAccount;
+
+/// Decodes the instructions and accounts of a single program by hand, for programs that
+/// don't publish an IDL, or whose hand-rolled serialization an IDL can't describe.
+///
+/// Registered decoders are consulted before any other means of decoding the program,
+/// including the built-in native decoders, so they can also replace those. Instructions
+/// and accounts a decoder doesn't handle are decoded with the program's IDL instead.
+pub trait ProgramDecoder: Send + Sync {
+    /// The name decoded instructions and accounts are reported under, in place of an IDL name.
+    fn program_name(&self) -> &str;
+
+    /// Decodes instruction data into the instruction's name and its arguments, as a JSON
+    /// object. `num_accounts` is the number of accounts the instruction was passed.
+    ///
+    /// Returns `None` if the decoder doesn't handle the instruction,
+    /// and an error if it does, but its data can't be decoded.
+    fn decode_instruction(
+        &self,
+        data: &[u8],
+        num_accounts: usize,
+    ) -> Option<anyhow::Result<(String, Value)>>;
+
+    /// Names the accounts an instruction takes, in order, so they can be checked against
+    /// the transaction's account metas. Accounts passed beyond those named are left out.
+    fn name_accounts(
+        &self,
+        instruction: &str,
+        data: &Value,
+        num_accounts: usize,
+    ) -> Vec<NativeAccount>;
+
+    /// Decodes the data of an account the program owns into its type name and decoded
+    /// data. Returns `None` if the decoder doesn't handle the account, which is the default.
+    fn decode_account(&self, _data: &[u8]) -> Option<anyhow::Result<(String, Value)>> {
+        None
+    }
+}
@@ -0,0 +1,138 @@
+use crate::deserialize::context::{DecodeContext, DecodeWarning, PathSegment, Strictness};
+use crate::deserialize::field::deserialize_idl_type_in;
+use crate::error::IdlItemKind;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use anchor_syn::idl::IdlEvent;
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// The instruction data prefix Anchor uses for events emitted through `emit_cpi!`,
+/// which self-invokes the program with the serialized event as instruction data.
+/// This is Anchor's `EVENT_IX_TAG` (`0x1d9acb512ea545e4`) in little endian.
+pub const EVENT_IX_TAG_LE: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+/// Raw event data that a program logged with `emit!`, located within
+/// the transaction by the instruction that was executing at the time.
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    /// The program that emitted the event.
+    pub program_id: Pubkey,
+    /// Index of the top-level instruction in the transaction message.
+    pub instruction_index: usize,
+    /// Index into that instruction's inner instructions, if the event was
+    /// emitted by a program invoked through CPI.
+    pub inner_instruction_index: Option<usize>,
+    /// The event discriminator followed by its Borsh-serialized fields.
+    pub data: Vec<u8>,
+}
+
+/// Deserializes an event's discriminator and fields, based on the emitting program's IDL.
+pub fn deser_event_data_from_idl(
+    idl: &IdlWithDiscriminators,
+    event_data: &[u8],
+) -> anyhow::Result<(IdlEvent, Value)> {
+    let (event, value, _) =
+        deser_event_data_from_idl_with_strictness(idl, event_data, Strictness::Lenient)?;
+    Ok((event, value))
+}
+
+/// Like [deser_event_data_from_idl], but checks the event data against the IDL
+/// according to `strictness`, returning any warnings it raised.
+pub fn deser_event_data_from_idl_with_strictness(
+    idl: &IdlWithDiscriminators,
+    event_data: &[u8],
+    strictness: Strictness,
+) -> anyhow::Result<(IdlEvent, Value, Vec<DecodeWarning>)> {
+    let (discriminator, event) = idl
+        .discriminators
+        .events
+        .get(event_data)
+        .ok_or_else(|| IdlItemKind::Event.unknown_discriminator(idl.address, event_data))?;
+    let mut ctx = DecodeContext::new(strictness, idl.address, event_data, &event.name);
+    let mut data = &event_data[discriminator.len()..];
+    let mut map = serde_json::Map::default();
+    for field in &event.fields {
+        ctx.push(PathSegment::Field(field.name.clone()));
+        let value = deserialize_idl_type_in(&field.ty, &idl.types, &mut data, &mut ctx)?;
+        ctx.pop();
+        map.insert(field.name.clone(), value);
+    }
+    ctx.finish(data)?;
+    Ok((event.clone(), Value::Object(map), ctx.warnings))
+}
+
+/// Walks a transaction's log messages, tracking the invocation stack, to find every
+/// `Program data: <base64>` line and the instruction that emitted it.
+///
+/// `program_ids` are the programs of the message's top-level instructions, in order.
+/// Precompiles, such as the Ed25519 program, don't log their invocation, so each
+/// top-level `invoke` is matched to the next instruction of the program it names.
+///
+/// Inner instructions are recorded by the runtime in the same order their
+/// `invoke` log lines appear, regardless of depth, so counting invocations
+/// within each top-level instruction recovers the inner instruction index.
+/// Logs truncated by the runtime will of course be missing their later events.
+pub fn extract_logged_events(log_messages: &[String], program_ids: &[Pubkey]) -> Vec<LoggedEvent> {
+    let mut events = vec![];
+    // Each frame is the invoked program, and its inner instruction index if it's a CPI.
+    let mut stack: Vec<(Pubkey, Option<usize>)> = vec![];
+    let mut instruction_index: Option<usize> = None;
+    // The top-level instructions before this one have been matched to invocations.
+    let mut unmatched = 0;
+    let mut inner_count = 0;
+    for log in log_messages {
+        if let Some(data) = log.strip_prefix("Program data: ") {
+            if let (Some((program_id, inner_instruction_index)), Some(instruction_index)) =
+                (stack.last(), instruction_index)
+            {
+                // `sol_log_data` logs each of its fields in base64, separated by spaces,
+                // which together make up a single event.
+                let fields = data
+                    .split_whitespace()
+                    .map(base64::decode)
+                    .collect::<Result<Vec<_>, _>>();
+                if let Ok(fields) = fields {
+                    events.push(LoggedEvent {
+                        program_id: *program_id,
+                        instruction_index,
+                        inner_instruction_index: *inner_instruction_index,
+                        data: fields.concat(),
+                    });
+                }
+            }
+            continue;
+        }
+        let mut words = log.split_whitespace();
+        let (program_id, action) = match (words.next(), words.next(), words.next()) {
+            (Some("Program"), Some(program_id), Some(action)) => {
+                match Pubkey::from_str(program_id) {
+                    Ok(program_id) => (program_id, action),
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        match action {
+            "invoke" => {
+                if stack.is_empty() {
+                    instruction_index = program_ids[unmatched..]
+                        .iter()
+                        .position(|id| *id == program_id)
+                        .map(|i| unmatched + i);
+                    unmatched = instruction_index.map_or(program_ids.len(), |i| i + 1);
+                    inner_count = 0;
+                    stack.push((program_id, None));
+                } else {
+                    stack.push((program_id, Some(inner_count)));
+                    inner_count += 1;
+                }
+            }
+            "success" | "failed:" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
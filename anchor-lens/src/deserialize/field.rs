@@ -1,6 +1,7 @@
+use crate::deserialize::context::{DecodeContext, PathSegment, Strictness};
 use crate::deserialize::idl_type_deserializer::TypeDefinitionDeserializer;
+use crate::error::LensError;
 use anchor_syn::idl::{Idl, IdlField, IdlType, IdlTypeDefinition};
-use anyhow::anyhow;
 use serde_json::Value;
 use solana_program::pubkey::Pubkey;
 
@@ -18,76 +19,90 @@ use solana_program::pubkey::Pubkey;
 ///  could structure in a circular fashion and cause infinite recursion here. However,
 ///  this would have to be quite deliberate, as any such IDL would never correspond to
 ///  a program that was compilable, as Rust definitions can't be circular.
+///
+/// This decodes leniently, see [deserialize_idl_type_in] to choose the [Strictness].
 pub fn deserialize_idl_type(
     idl_type: &IdlType,
     type_defs: &Vec<IdlTypeDefinition>,
     raw_data: &mut &[u8],
+) -> anyhow::Result<Value> {
+    let mut ctx = DecodeContext::new(Strictness::Lenient, None, raw_data, "");
+    deserialize_idl_type_in(idl_type, type_defs, raw_data, &mut ctx)
+}
+
+/// Like [deserialize_idl_type], but tracks the field path and byte offset in `ctx`,
+/// so errors report where decoding diverged from the IDL.
+pub fn deserialize_idl_type_in(
+    idl_type: &IdlType,
+    type_defs: &Vec<IdlTypeDefinition>,
+    raw_data: &mut &[u8],
+    ctx: &mut DecodeContext,
 ) -> anyhow::Result<Value> {
     match &idl_type {
         IdlType::Bool => {
-            let value: bool = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: bool = ctx.read(raw_data)?;
             return Ok(Value::Bool(value));
         }
         IdlType::U8 => {
-            let value: u8 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: u8 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::I8 => {
-            let value: i8 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: i8 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::U16 => {
-            let value: u16 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: u16 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::I16 => {
-            let value: i16 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: i16 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::U32 => {
-            let value: u32 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: u32 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::I32 => {
-            let value: i32 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: i32 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::F32 => {
-            let value: f32 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: f32 = ctx.read(raw_data)?;
             return Ok(Value::String(value.to_string()));
         }
         IdlType::U64 => {
-            let value: u64 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: u64 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::I64 => {
-            let value: i64 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: i64 = ctx.read(raw_data)?;
             return Ok(Value::Number(value.into()));
         }
         IdlType::F64 => {
-            let value: f64 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: f64 = ctx.read(raw_data)?;
             return Ok(Value::String(value.to_string()));
         }
         IdlType::U128 => {
-            let value: u128 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: u128 = ctx.read(raw_data)?;
             return Ok(Value::String(value.to_string()));
         }
         IdlType::I128 => {
-            let value: i128 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: i128 = ctx.read(raw_data)?;
             return Ok(Value::String(value.to_string()));
         }
         IdlType::Bytes => {
-            let value: Vec<u8> = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: Vec<u8> = ctx.read(raw_data)?;
             return Ok(Value::Array(
                 value.iter().map(|v| Value::Number((*v).into())).collect(),
             ));
         }
         IdlType::String => {
-            let value: String = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: String = ctx.read(raw_data)?;
             return Ok(Value::String(value));
         }
         IdlType::PublicKey => {
-            let value: Pubkey = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let value: Pubkey = ctx.read(raw_data)?;
             return Ok(Value::String(value.to_string()));
         }
         IdlType::Defined(defined_type) => {
@@ -97,32 +112,41 @@ pub fn deserialize_idl_type(
                         idl_type_defs: type_defs.clone(),
                         curr_type: type_def.clone(),
                     }
-                    .deserialize(raw_data)?);
+                    .deserialize_in(raw_data, ctx)?);
                 }
             }
-            return Err(anyhow!("Couldn't find defined type: {}", &defined_type));
+            return Err(LensError::TypeNotDefined {
+                defined: defined_type.clone(),
+                location: ctx.location(raw_data),
+            }
+            .into());
         }
         IdlType::Option(idl_type) => {
-            let is_some: bool = borsh::BorshDeserialize::deserialize(raw_data)?;
-            let value = deserialize_idl_type(idl_type, type_defs, raw_data)?;
+            // Borsh writes a single 0 byte for None, and nothing follows it.
+            let is_some: bool = ctx.read(raw_data)?;
             if is_some {
+                let value = deserialize_idl_type_in(idl_type, type_defs, raw_data, ctx)?;
                 return Ok(Some(value).into());
             } else {
                 return Ok(None::<Value>.into());
             }
         }
         IdlType::Vec(idl_type) => {
-            let arr_len: u32 = borsh::BorshDeserialize::deserialize(raw_data)?;
+            let arr_len: u32 = ctx.read(raw_data)?;
             let mut values = vec![];
-            for _ in 0..arr_len {
-                values.push(deserialize_idl_type(idl_type, type_defs, raw_data)?);
+            for i in 0..arr_len as usize {
+                ctx.push(PathSegment::Index(i));
+                values.push(deserialize_idl_type_in(idl_type, type_defs, raw_data, ctx)?);
+                ctx.pop();
             }
             return Ok(values.into());
         }
         IdlType::Array(idl_type, arr_len) => {
             let mut values = vec![];
-            for _ in 0..*arr_len {
-                values.push(deserialize_idl_type(idl_type, type_defs, raw_data)?);
+            for i in 0..*arr_len {
+                ctx.push(PathSegment::Index(i));
+                values.push(deserialize_idl_type_in(idl_type, type_defs, raw_data, ctx)?);
+                ctx.pop();
             }
             return Ok(values.into());
         }
@@ -131,17 +155,44 @@ pub fn deserialize_idl_type(
 
 /// Mainly for use in an enum tuple-variant, to deserialize
 /// its containing data.
+///
+/// This decodes leniently, so the values are cut short at the first element that
+/// fails to deserialize. See [deserialize_idl_types_in] to report or reject that instead.
 pub fn deserialize_idl_types(
     types: &Vec<IdlType>,
     type_defs: &Vec<IdlTypeDefinition>,
     raw_data: &mut &[u8],
 ) -> anyhow::Result<Vec<Value>> {
-    Ok(types
-        .iter()
-        .map(|idl_type| deserialize_idl_type(idl_type, type_defs, raw_data))
-        .into_iter()
-        .flatten()
-        .collect())
+    let mut ctx = DecodeContext::new(Strictness::Lenient, None, raw_data, "");
+    deserialize_idl_types_in(types, type_defs, raw_data, &mut ctx)
+}
+
+/// Like [deserialize_idl_types]. Decoding stops at the first element that fails,
+/// with the cursor put back to where that element started, since the bytes after
+/// it can't be located. This is reported as a warning under [Strictness::Warn],
+/// and fails the whole decode under [Strictness::Strict].
+pub fn deserialize_idl_types_in(
+    types: &Vec<IdlType>,
+    type_defs: &Vec<IdlTypeDefinition>,
+    raw_data: &mut &[u8],
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<Vec<Value>> {
+    let mut values = vec![];
+    for (i, idl_type) in types.iter().enumerate() {
+        let before = *raw_data;
+        ctx.push(PathSegment::Index(i));
+        let value = deserialize_idl_type_in(idl_type, type_defs, raw_data, ctx);
+        ctx.pop();
+        match value {
+            Ok(value) => values.push(value),
+            Err(err) => {
+                *raw_data = before;
+                ctx.recoverable(err)?;
+                break;
+            }
+        }
+    }
+    Ok(values)
 }
 
 /// Deserializes many named fields, indexing them into a [serde_json::map::Map]
@@ -150,13 +201,24 @@ pub fn deserialize_idl_fields(
     fields: &Vec<IdlField>,
     idl: &Idl,
     data: &mut &[u8],
+) -> anyhow::Result<Value> {
+    let mut ctx = DecodeContext::new(Strictness::Lenient, None, data, "");
+    deserialize_idl_fields_in(fields, &idl.types, data, &mut ctx)
+}
+
+/// Like [deserialize_idl_fields], tracking the field path and byte offset in `ctx`.
+pub fn deserialize_idl_fields_in(
+    fields: &Vec<IdlField>,
+    type_defs: &Vec<IdlTypeDefinition>,
+    data: &mut &[u8],
+    ctx: &mut DecodeContext,
 ) -> anyhow::Result<Value> {
     let mut map = serde_json::Map::default();
     for field in fields {
-        map.insert(
-            field.name.clone(),
-            deserialize_idl_type(&field.ty, &idl.types, data)?,
-        );
+        ctx.push(PathSegment::Field(field.name.clone()));
+        let value = deserialize_idl_type_in(&field.ty, type_defs, data, ctx)?;
+        ctx.pop();
+        map.insert(field.name.clone(), value);
     }
     return Ok(Value::Object(map.into()));
 }
@@ -0,0 +1,213 @@
+//! Anchor programs come with built-in instructions for managing their IDL account,
+//! which aren't listed in the IDL itself. They're tagged with [IDL_IX_TAG] in place
+//! of a discriminator, followed by a borsh-encoded [IdlInstruction].
+
+use crate::deserialize::context::{DecodeWarning, Strictness};
+use crate::deserialize::instruction::{deser_ix_data_from_idl_with_strictness, full_account_keys};
+use crate::deserialize::HistoricalTransaction;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::inflate;
+use crate::fetch_idl::versioned::VersionedIdl;
+use anchor_lang::idl::{IdlInstruction, IDL_IX_TAG};
+use borsh::BorshDeserialize;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Parses an IDL management instruction, returning `None` if the data isn't tagged as one.
+pub fn parse_idl_instruction(data: &[u8]) -> Option<IdlInstruction> {
+    let mut data = data.strip_prefix(&IDL_IX_TAG.to_le_bytes()[..])?;
+    IdlInstruction::deserialize(&mut data).ok()
+}
+
+/// An IDL listing the IDL management instructions, so that they can be decoded like
+/// any other instruction. Each discriminator is [IDL_IX_TAG] followed by the
+/// [IdlInstruction] variant index, in declaration order. Since every Anchor program
+/// shares these instructions, the IDL has no address, and is only built once.
+pub fn idl_management_idl() -> &'static IdlWithDiscriminators {
+    &IDL_MANAGEMENT_IDL
+}
+
+static IDL_MANAGEMENT_IDL: Lazy<IdlWithDiscriminators> = Lazy::new(|| {
+    let discriminator = |variant: u8| {
+        let mut discriminator = IDL_IX_TAG.to_le_bytes().to_vec();
+        discriminator.push(variant);
+        discriminator
+    };
+    let idl_accounts = json!([
+        { "name": "idl", "writable": true },
+        { "name": "authority", "signer": true }
+    ]);
+    let idl = json!({
+        // The spec requires an address, which is cleared below.
+        "address": Pubkey::default().to_string(),
+        "metadata": { "name": "anchor_idl", "version": "0.1.0", "spec": "0.1.0" },
+        "instructions": [
+            {
+                "name": "create",
+                "discriminator": discriminator(0),
+                "accounts": [
+                    { "name": "from", "writable": true, "signer": true },
+                    { "name": "to", "writable": true },
+                    { "name": "base" },
+                    { "name": "system_program" },
+                    { "name": "program" }
+                ],
+                "args": [{ "name": "data_len", "type": "u64" }]
+            },
+            {
+                "name": "create_buffer",
+                "discriminator": discriminator(1),
+                "accounts": [
+                    { "name": "buffer", "writable": true },
+                    { "name": "authority", "signer": true }
+                ],
+                "args": []
+            },
+            {
+                "name": "write",
+                "discriminator": discriminator(2),
+                "accounts": idl_accounts,
+                "args": [{ "name": "data", "type": "bytes" }]
+            },
+            {
+                "name": "set_buffer",
+                "discriminator": discriminator(3),
+                "accounts": [
+                    { "name": "buffer", "writable": true },
+                    { "name": "idl", "writable": true },
+                    { "name": "authority", "signer": true }
+                ],
+                "args": []
+            },
+            {
+                "name": "set_authority",
+                "discriminator": discriminator(4),
+                "accounts": idl_accounts,
+                "args": [{ "name": "new_authority", "type": "pubkey" }]
+            },
+            // Added in Anchor 0.29.
+            {
+                "name": "close",
+                "discriminator": discriminator(5),
+                "accounts": [
+                    { "name": "idl", "writable": true },
+                    { "name": "authority", "signer": true },
+                    { "name": "sol_destination", "writable": true }
+                ],
+                "args": []
+            },
+            {
+                "name": "resize",
+                "discriminator": discriminator(6),
+                "accounts": [
+                    { "name": "idl", "writable": true },
+                    { "name": "authority", "writable": true, "signer": true },
+                    { "name": "system_program" }
+                ],
+                "args": [{ "name": "data_len", "type": "u64" }]
+            }
+        ]
+    });
+    let idl = VersionedIdl::from_value(idl).expect("IDL management instructions are valid");
+    let mut idl =
+        IdlWithDiscriminators::from_versioned(idl).expect("IDL management instructions are valid");
+    idl.address = None;
+    idl
+});
+
+/// Decodes an IDL management instruction, see [deser_ix_data_from_idl_with_strictness].
+/// Returns `None` if the data isn't tagged as one.
+pub fn deser_idl_ix_data_with_strictness(
+    ix_data: &[u8],
+    strictness: Strictness,
+) -> Option<anyhow::Result<(anchor_syn::idl::IdlInstruction, Value, Vec<DecodeWarning>)>> {
+    if !ix_data.starts_with(&IDL_IX_TAG.to_le_bytes()) {
+        return None;
+    }
+    Some(deser_ix_data_from_idl_with_strictness(
+        idl_management_idl(),
+        ix_data.to_vec(),
+        strictness,
+    ))
+}
+
+/// Decompresses IDL data as stored in an IDL account, or written to one,
+/// returning `None` unless it holds a whole IDL.
+pub fn decompress_idl(data: &[u8]) -> Option<Value> {
+    serde_json::from_slice(&inflate(data)?).ok()
+}
+
+/// Where an instruction sits in a transaction: the top-level instruction index,
+/// and the inner instruction index if it was invoked through CPI.
+pub type InstructionLocation = (usize, Option<usize>);
+
+/// An IDL management instruction sent by a transaction.
+pub struct TransactionIdlInstruction {
+    pub location: InstructionLocation,
+    /// The program the instruction was sent to, whose IDL account it manages.
+    pub program_id: Pubkey,
+    pub instruction: IdlInstruction,
+    pub accounts: Vec<Pubkey>,
+}
+
+/// The IDL management instructions a transaction sent, in execution order,
+/// including those invoked through CPI.
+pub fn idl_instructions(tx: &HistoricalTransaction) -> Vec<TransactionIdlInstruction> {
+    let account_keys = full_account_keys(&tx.message, &tx.loaded_addresses);
+    let mut instructions = vec![];
+    for (i, ix) in tx.message.instructions().iter().enumerate() {
+        let inner = u8::try_from(i)
+            .ok()
+            .and_then(|i| tx.inner_instructions.get(&i))
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(j, ix)| (Some(j), ix));
+        for (j, ix) in std::iter::once((None, ix)).chain(inner) {
+            let (program_id, instruction) = match (
+                account_keys.get(ix.program_id_index as usize),
+                parse_idl_instruction(&ix.data),
+            ) {
+                (Some(program_id), Some(instruction)) => (*program_id, instruction),
+                _ => continue,
+            };
+            instructions.push(TransactionIdlInstruction {
+                location: (i, j),
+                program_id,
+                instruction,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .filter_map(|a| account_keys.get(*a as usize).copied())
+                    .collect(),
+            });
+        }
+    }
+    instructions
+}
+
+/// Reassembles the IDLs written to IDL accounts or buffers by a transaction's `Write`
+/// instructions. Chunks written to the same account are concatenated until they form
+/// a whole IDL, which is returned along with the instruction that completed it.
+///
+/// The Anchor CLI usually writes an IDL over several transactions, in which case
+/// none of them holds a whole IDL; see [crate::fetch_idl::history] to replay them all.
+pub fn written_idls(tx: &HistoricalTransaction) -> Vec<(InstructionLocation, Value)> {
+    let mut written = HashMap::<Pubkey, Vec<u8>>::new();
+    let mut idls = vec![];
+    for ix in idl_instructions(tx) {
+        let (chunk, account) = match (ix.instruction, ix.accounts.first()) {
+            (IdlInstruction::Write { data }, Some(account)) => (data, *account),
+            _ => continue,
+        };
+        let data = written.entry(account).or_default();
+        data.extend(chunk);
+        if let Some(idl) = decompress_idl(data) {
+            written.remove(&account);
+            idls.push((ix.location, idl));
+        }
+    }
+    idls
+}
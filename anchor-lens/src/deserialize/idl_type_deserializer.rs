@@ -1,8 +1,7 @@
-use crate::deserialize::field::{deserialize_idl_type, deserialize_idl_types};
-use anchor_syn::idl::{
-    EnumFields, IdlEnumVariant, IdlField, IdlTypeDefinition, IdlTypeDefinitionTy,
-};
-use anyhow::anyhow;
+use crate::deserialize::context::{DecodeContext, PathSegment, Strictness};
+use crate::deserialize::field::{deserialize_idl_fields_in, deserialize_idl_types_in};
+use crate::error::LensError;
+use anchor_syn::idl::{EnumFields, IdlEnumVariant, IdlTypeDefinition, IdlTypeDefinitionTy};
 use serde_json::{json, Value};
 
 /// Performs a top-down, iterated deserialization over the type-tree
@@ -16,55 +15,67 @@ pub struct TypeDefinitionDeserializer {
 impl TypeDefinitionDeserializer {
     /// Deserialize a data according to a custom type definition defined
     /// in the IDL. This includes accounts, instructions, and auxiliary defined types.
+    ///
+    /// This decodes leniently, see [TypeDefinitionDeserializer::deserialize_in]
+    /// to choose the [Strictness].
     pub fn deserialize(self, data: &mut &[u8]) -> anyhow::Result<Value> {
+        let mut ctx = DecodeContext::new(Strictness::Lenient, None, data, &self.curr_type.name);
+        self.deserialize_in(data, &mut ctx)
+    }
+
+    /// Like [TypeDefinitionDeserializer::deserialize], tracking the field path
+    /// and byte offset in `ctx`.
+    pub fn deserialize_in(
+        self,
+        data: &mut &[u8],
+        ctx: &mut DecodeContext,
+    ) -> anyhow::Result<Value> {
         match self.curr_type.ty.clone() {
             IdlTypeDefinitionTy::Struct { fields } => {
-                self.deserialize_idl_fields(&fields.clone(), data)
+                deserialize_idl_fields_in(&fields, &self.idl_type_defs, data, ctx)
             }
             IdlTypeDefinitionTy::Enum { variants } => {
-                for variant in variants {
-                    let IdlEnumVariant { name, fields } = variant;
-                    if let Ok(value) = self.deserialize_enum_field(name, &fields.clone(), data) {
-                        return Ok(value);
-                    }
-                }
-                return Err(anyhow!(
-                    "Couldn't deserialize using any of the available enum variants"
-                ));
+                // Borsh prefixes enums with the index of the variant as a single byte.
+                let before = *data;
+                let variant_index: u8 = ctx.read(data)?;
+                let IdlEnumVariant { name, fields } = variants
+                    .get(variant_index as usize)
+                    .ok_or_else(|| LensError::InvalidEnumVariant {
+                        enum_name: self.curr_type.name.clone(),
+                        variant_index,
+                        location: ctx.location(before),
+                    })?
+                    .clone();
+                ctx.push(PathSegment::Variant(name.clone()));
+                let value = self.deserialize_enum_field(name, &fields, data, ctx);
+                ctx.pop();
+                value
             }
         }
     }
 
-    fn deserialize_idl_fields(
-        &self,
-        fields: &Vec<IdlField>,
-        data: &mut &[u8],
-    ) -> anyhow::Result<Value> {
-        let mut map = serde_json::Map::default();
-        for field in fields {
-            map.insert(
-                field.name.clone(),
-                deserialize_idl_type(&field.ty, &self.idl_type_defs, data)?,
-            );
-        }
-        return Ok(Value::Object(map.into()));
-    }
-
+    /// Deserializes the fields of an enum variant, once the variant has been selected.
+    /// Every variant is output as `{"name": <variant name>, "fields": <fields>}`, where
+    /// the fields are an object for struct variants, an array for tuple variants,
+    /// and null for variants without fields.
     fn deserialize_enum_field(
         &self,
         name: String,
         fields: &Option<EnumFields>,
         data: &mut &[u8],
+        ctx: &mut DecodeContext,
     ) -> anyhow::Result<Value> {
         if let Some(enum_fields) = fields {
             match enum_fields {
                 // A variant with struct fields.
-                EnumFields::Named(idl_fields) => Ok(self.deserialize_idl_fields(idl_fields, data)?),
+                EnumFields::Named(idl_fields) => Ok(json!({
+                    "name": name,
+                    "fields": deserialize_idl_fields_in(idl_fields, &self.idl_type_defs, data, ctx)?
+                })),
                 // A variant with unnamed tuple fields.
                 EnumFields::Tuple(idl_types) => {
-                    let mut data = data.clone();
                     let deserialized =
-                        deserialize_idl_types(idl_types, &self.idl_type_defs, &mut data)?;
+                        deserialize_idl_types_in(idl_types, &self.idl_type_defs, data, ctx)?;
                     Ok(json!({
                         "name": name,
                         "fields": Value::Array(deserialized)
@@ -1,12 +1,14 @@
 // TODO Top level function for deserializing the entire instruction,
 // return metadata like name, accounts, return value
 
-use crate::deserialize::field::deserialize_idl_fields;
+use crate::deserialize::context::{DecodeContext, DecodeWarning, Strictness};
+use crate::deserialize::field::deserialize_idl_fields_in;
+use crate::error::IdlItemKind;
 use crate::fetch_idl::discriminators::IdlWithDiscriminators;
 use anchor_syn::idl::{IdlAccountItem, IdlAccounts, IdlInstruction};
-use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use solana_program::message::v0::LoadedAddresses;
 use solana_program::message::{MessageHeader, VersionedMessage};
 use solana_program::pubkey::Pubkey;
 
@@ -16,20 +18,45 @@ pub fn deser_ix_data_from_idl(
     idl: &IdlWithDiscriminators,
     ix_data: Vec<u8>,
 ) -> anyhow::Result<(IdlInstruction, Value)> {
-    let mut first_eight = ix_data.to_vec();
-    first_eight.resize(8, 0);
-    let first_eight: [u8; 8] = first_eight.try_into().unwrap();
-    let ix = idl
+    let (ix, value, _) = deser_ix_data_from_idl_with_strictness(idl, ix_data, Strictness::Lenient)?;
+    Ok((ix, value))
+}
+
+/// Like [deser_ix_data_from_idl], but checks the instruction data against the IDL
+/// according to `strictness`, returning any warnings it raised.
+/// Byte offsets in errors and warnings count from the start of the discriminator.
+pub fn deser_ix_data_from_idl_with_strictness(
+    idl: &IdlWithDiscriminators,
+    ix_data: Vec<u8>,
+    strictness: Strictness,
+) -> anyhow::Result<(IdlInstruction, Value, Vec<DecodeWarning>)> {
+    let (discriminator, ix) = idl
         .discriminators
         .instructions
-        .get(&first_eight)
-        .ok_or(anyhow!(
-            "Could not match instruction against any discriminator"
-        ))?;
-    Ok((
-        ix.clone(),
-        deserialize_idl_fields(&ix.args, &idl, &mut &ix_data[8..])?,
-    ))
+        .get(&ix_data)
+        .ok_or_else(|| IdlItemKind::Instruction.unknown_discriminator(idl.address, &ix_data))?;
+    let mut ctx = DecodeContext::new(strictness, idl.address, &ix_data, &ix.name);
+    let mut data = &ix_data[discriminator.len()..];
+    let value = deserialize_idl_fields_in(&ix.args, &idl.types, &mut data, &mut ctx)?;
+    ctx.finish(data)?;
+    Ok((ix.clone(), value, ctx.warnings))
+}
+
+/// Every account key an instruction in the message can reference, in index order.
+///
+/// For v0 messages, the static keys are followed by the addresses loaded from
+/// address lookup tables, writable ones first. Legacy messages have no loaded addresses.
+pub fn full_account_keys(
+    message: &VersionedMessage,
+    loaded_addresses: &LoadedAddresses,
+) -> Vec<Pubkey> {
+    message
+        .static_account_keys()
+        .iter()
+        .chain(loaded_addresses.writable.iter())
+        .chain(loaded_addresses.readonly.iter())
+        .copied()
+        .collect()
 }
 
 /// For iterating over both a transaction message and an IDL account item,
@@ -47,20 +74,29 @@ pub struct AccountMetaGroups {
 }
 
 impl AccountMetaGroups {
-    pub fn new_from_message(msg: VersionedMessage, instruction_account_indices: Vec<u8>) -> Self {
+    /// Classifies the message's accounts by privilege. Addresses loaded from lookup
+    /// tables can never sign, and are writable or read-only according to which
+    /// list of `loaded_addresses` they were loaded into.
+    pub fn new_from_message(
+        msg: VersionedMessage,
+        loaded_addresses: &LoadedAddresses,
+        instruction_account_indices: Vec<u8>,
+    ) -> Self {
         let MessageHeader {
             num_required_signatures,
             num_readonly_signed_accounts,
             num_readonly_unsigned_accounts,
         } = msg.header();
-        let all_accounts = msg.static_account_keys().to_vec();
-        let signers = all_accounts[0..(*num_required_signatures) as usize].to_vec();
+        let static_accounts = msg.static_account_keys();
+        let signers = static_accounts[0..(*num_required_signatures) as usize].to_vec();
         let signer_mut = signers
             [0..(*num_required_signatures - *num_readonly_signed_accounts) as usize]
             .to_vec();
-        let acts_len = all_accounts.len();
-        let nonsigner_readonly =
-            all_accounts[(acts_len - (*num_readonly_unsigned_accounts as usize))..].to_vec();
+        let acts_len = static_accounts.len();
+        let mut nonsigner_readonly =
+            static_accounts[(acts_len - (*num_readonly_unsigned_accounts as usize))..].to_vec();
+        nonsigner_readonly.extend(loaded_addresses.readonly.iter().copied());
+        let all_accounts = full_account_keys(&msg, loaded_addresses);
         Self {
             all_accounts,
             signers,
@@ -81,8 +117,16 @@ impl AccountMetaGroups {
         for item in items {
             match item {
                 IdlAccountItem::IdlAccount(act) => {
-                    let idx = self.instruction_account_indices[*instruction_account_index];
-                    let pubkey = self.all_accounts[idx as usize].clone();
+                    // The instruction may pass fewer accounts than the IDL lists,
+                    // or reference keys we weren't able to resolve.
+                    let pubkey = match self
+                        .instruction_account_indices
+                        .get(*instruction_account_index)
+                        .and_then(|idx| self.all_accounts.get(*idx as usize))
+                    {
+                        Some(pubkey) => *pubkey,
+                        None => return,
+                    };
                     let json = json!({
                         "name": act.name,
                         "pubkey": pubkey.to_string(),
@@ -121,10 +165,13 @@ impl AccountMetaGroups {
     /// Check that an account was marked mutable appropriately according to what is
     /// stipulated in the IDL.
     fn check_pubkey_is_mut(&self, pubkey: &Pubkey, is_mut: bool) -> AccountMetaStatus {
-        match (
-            is_mut,
-            (self.signer_mut.contains(pubkey) || !self.nonsigner_readonly.contains(pubkey)),
-        ) {
+        // Signers are ordered writable first, the rest of the accounts likewise.
+        let writable = if self.signers.contains(pubkey) {
+            self.signer_mut.contains(pubkey)
+        } else {
+            !self.nonsigner_readonly.contains(pubkey)
+        };
+        match (is_mut, writable) {
             (true, true) => AccountMetaStatus::True,
             (true, false) => AccountMetaStatus::FailedToEscalatePrivilege,
             (false, true) => AccountMetaStatus::UnnecessaryPrivilegeEscalation,
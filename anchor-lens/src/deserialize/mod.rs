@@ -1,6 +1,7 @@
 use crate::deserialize::idl_type_deserializer::TypeDefinitionDeserializer;
 use crate::fetch_idl::discriminators::IdlWithDiscriminators;
-use crate::fetch_idl::fetch_idl;
+use crate::fetch_idl::{fetch_idl, fetch_idl_history, fetch_idl_with_metadata, DiskIdlCache, FetchedIdl, IdlCache, IdlHistory, IdlRegistry};
+use crate::source::{AccountSource, SignatureSource, TransactionSource};
 use anyhow::{anyhow, Result};
 use serde_json::{json, Value};
 use solana_account_decoder::{UiAccountData, UiAccountEncoding};
@@ -9,17 +10,42 @@ use solana_program::pubkey::Pubkey;
 use solana_sdk::account::Account;
 use solana_sdk::bs58;
 use solana_sdk::signature::Signature;
-use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, EncodedTransactionWithStatusMeta, UiInstruction, UiTransactionEncoding, UiTransactionStatusMeta};
-use std::cell::RefCell;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, EncodedTransactionWithStatusMeta, UiInstruction, UiTransactionStatusMeta};
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
 use solana_program::instruction::CompiledInstruction;
+use solana_program::message::v0::LoadedAddresses;
 use solana_program::message::VersionedMessage;
 use solana_transaction_status::option_serializer::OptionSerializer;
-use crate::deserialize::instruction::{AccountMetaGroups, deser_ix_data_from_idl};
+use crate::deserialize::instruction::{AccountMetaGroups, deser_ix_data_from_idl_with_strictness, full_account_keys};
+use crate::deserialize::program_error::{failed_program_from_logs, transaction_error_json};
+use crate::deserialize::event::{
+    deser_event_data_from_idl_with_strictness, extract_logged_events, LoggedEvent, EVENT_IX_TAG_LE,
+};
+use crate::deserialize::context::{DecodeContext, DecodeWarning};
+use crate::deserialize::idl_instruction::{deser_idl_ix_data_with_strictness, written_idls};
+use crate::deserialize::native::compute_budget::ComputeBudgetSummary;
+use crate::deserialize::native::address_lookup_table::{self, read_lookup_table};
+use crate::deserialize::native::{
+    deser_native_account, deser_native_ix_with_strictness, native_program_name, NativeInstruction,
+    NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
 
+pub mod context;
+pub mod decoder;
+pub mod event;
 pub mod field;
+pub mod idl_instruction;
 pub mod idl_type_deserializer;
 pub mod instruction;
+pub mod native;
+pub mod program_error;
+
+pub use context::Strictness;
+pub use decoder::{NativeAccount, ProgramDecoder};
 
 /// The output of a successful account deserialization
 /// aided by its owning program's on-chain IDL.
@@ -32,6 +58,8 @@ pub struct IdlDeserializedAccount {
     pub type_name: String,
     /// The deserialized data. See [idl_type_deserializer::TypeDefinitionDeserializer] for details.
     pub data: Value,
+    /// Mismatches between the data and the IDL, found under [Strictness::Warn].
+    pub warnings: Vec<DecodeWarning>,
 }
 
 /// The transaction message itself, and any inner instructions extracted from it
@@ -40,6 +68,7 @@ pub struct IdlDeserializedAccount {
 /// Since inner instructions are not encoded in a transaction message,
 /// we need to pull it from the metadata sent when querying for historical
 /// transaction data.
+#[derive(Debug, Clone)]
 pub struct HistoricalTransaction {
     /// A message is transaction data ready to be packed and signed.
     /// Since a 2022 update to transaction schemas, there is now the `VersionedMessage`.
@@ -47,6 +76,133 @@ pub struct HistoricalTransaction {
     /// Indexed by instruction number. We do not record nested inner instructions,
     /// as those are not returned from the Solana RPC `get_transaction` endpoint.
     pub inner_instructions: HashMap<u8, Vec<CompiledInstruction>>,
+    /// The program logs, from which events emitted by the transaction are recovered.
+    pub log_messages: Vec<String>,
+    /// The error the transaction failed with, if it failed.
+    pub err: Option<TransactionError>,
+    /// For v0 messages, the account keys the runtime loaded from address lookup tables.
+    pub loaded_addresses: LoadedAddresses,
+    /// The slot the transaction landed in, used to pick the IDL that was live at the time.
+    pub slot: Option<u64>,
+    /// The compute units the transaction consumed, as reported in its metadata.
+    pub compute_units_consumed: Option<u64>,
+}
+
+impl HistoricalTransaction {
+    /// A transaction with no metadata: no inner instructions, logs, error or slot.
+    /// Fields can then be set as needed.
+    pub fn new(message: VersionedMessage) -> Self {
+        HistoricalTransaction {
+            message,
+            inner_instructions: HashMap::new(),
+            log_messages: vec![],
+            err: None,
+            loaded_addresses: LoadedAddresses::default(),
+            slot: None,
+            compute_units_consumed: None,
+        }
+    }
+
+    /// Every program the transaction invoked, including through CPI,
+    /// in the order they're first seen.
+    pub fn program_ids(&self) -> Vec<Pubkey> {
+        let account_keys = full_account_keys(&self.message, &self.loaded_addresses);
+        let mut inner_instructions = self.inner_instructions.iter().collect::<Vec<_>>();
+        inner_instructions.sort_by_key(|(i, _)| **i);
+        let instructions = self
+            .message
+            .instructions()
+            .iter()
+            .chain(inner_instructions.into_iter().flat_map(|(_, ixs)| ixs));
+        let mut program_ids = vec![];
+        for ix in instructions {
+            if let Some(program_id) = account_keys.get(ix.program_id_index as usize) {
+                if !program_ids.contains(program_id) {
+                    program_ids.push(*program_id);
+                }
+            }
+        }
+        program_ids
+    }
+
+    /// Unpacks a transaction as returned by the `get_transaction` RPC endpoint,
+    /// which must have been requested in a binary encoding.
+    pub fn from_encoded(tx: EncodedConfirmedTransactionWithStatusMeta) -> Result<Self> {
+        let EncodedConfirmedTransactionWithStatusMeta {
+            slot,
+            transaction: EncodedTransactionWithStatusMeta { transaction, meta, .. },
+            ..
+        } = tx;
+        let mut inner_instructions = HashMap::new();
+        let mut log_messages = vec![];
+        let mut err = None;
+        let mut loaded_addresses = LoadedAddresses::default();
+        let mut compute_units_consumed = None;
+        if let Some(meta) = meta {
+            let UiTransactionStatusMeta {
+                err: meta_err,
+                inner_instructions: meta_inner_instructions,
+                log_messages: meta_log_messages,
+                loaded_addresses: meta_loaded_addresses,
+                compute_units_consumed: meta_compute_units_consumed,
+                ..
+            } = meta;
+            err = meta_err;
+            if let OptionSerializer::Some(units) = meta_compute_units_consumed {
+                compute_units_consumed = Some(units);
+            }
+            if let OptionSerializer::Some(loaded) = meta_loaded_addresses {
+                let parse_keys = |keys: Vec<String>| {
+                    keys.iter()
+                        .map(|key| Pubkey::from_str(key))
+                        .collect::<std::result::Result<Vec<_>, _>>()
+                };
+                loaded_addresses = LoadedAddresses {
+                    writable: parse_keys(loaded.writable)?,
+                    readonly: parse_keys(loaded.readonly)?,
+                };
+            }
+            if let OptionSerializer::Some(meta) = meta_inner_instructions {
+                for inner_ix in meta.into_iter() {
+                    inner_instructions.insert(
+                        inner_ix.index,
+                        inner_ix.instructions
+                            .into_iter()
+                            .map(|ix| {
+                                match ix {
+                                    UiInstruction::Compiled(ix) => Some(
+                                        CompiledInstruction {
+                                            program_id_index: ix.program_id_index,
+                                            accounts: ix.accounts,
+                                            data: bs58::decode(ix.data).into_vec().unwrap()
+                                        }
+                                    ),
+                                    _ => None,
+                                }
+                            })
+                            .into_iter()
+                            .flatten()
+                            .collect::<Vec<_>>()
+                    );
+                }
+            }
+            if let OptionSerializer::Some(logs) = meta_log_messages {
+                log_messages = logs;
+            }
+        }
+        let transaction = transaction
+            .decode()
+            .ok_or(anyhow!("Failed to decode transaction"))?;
+        Ok(HistoricalTransaction {
+            message: transaction.message,
+            inner_instructions,
+            log_messages,
+            err,
+            loaded_addresses,
+            slot: Some(slot),
+            compute_units_consumed,
+        })
+    }
 }
 
 /// Wraps client calls and optionally caches the IDLs that it fetches.
@@ -58,143 +214,289 @@ pub struct HistoricalTransaction {
 /// Deserializes accounts and instructions, relying on the help
 /// of program IDL accounts. These are found on chain, and they store
 /// an Anchor IDL JSON file in compressed form.
-pub struct AnchorLens {
-    /// This client is used to make RPC calls to get IDLs, account data,
+///
+/// Accounts and transactions are read from an [AccountSource] and [TransactionSource],
+/// which is an [RpcClient] unless another source is given, see [crate::source].
+pub struct AnchorLens<S = RpcClient> {
+    /// This client is used to get IDLs, account data,
     /// and historical transaction data.
-    pub client: RpcClient,
-    /// This struct will optionally cache IDLs to reduce unnecessary RPC calls.
-    /// [crate::fetch_idl::discriminators::IdlWithDiscriminators]
-    /// are stored keyed by the 32-byte array form of the Solana SDK `Pubkey`,
-    /// which is easily accessible through dereferencing.
-    pub idl_cache: RefCell<HashMap<[u8; 32], IdlWithDiscriminators>>,
+    pub client: S,
+    /// This struct will optionally cache IDLs to reduce unnecessary RPC calls,
+    /// including the absence of an IDL. The cache can be shared with other lenses,
+    /// possibly on other threads, by cloning the [Arc].
+    pub idl_cache: Arc<IdlCache>,
     /// Boolean flag that controls caching of IDLs.
     pub cache_idls: bool,
+    /// IDLs loaded from files, which take precedence over those published on chain.
+    pub idl_registry: IdlRegistry,
+    /// Optionally keeps fetched IDLs on disk between runs.
+    pub disk_cache: Option<DiskIdlCache>,
+    /// How closely accounts, instructions and events must match their IDL
+    /// to be deserialized. Defaults to [Strictness::Lenient].
+    pub strictness: Strictness,
+    /// Whether [AnchorLens::deserialize_transaction] reassembles and decompresses
+    /// IDLs written by a transaction's IDL `write` instructions. Defaults to `false`.
+    pub decode_idl_writes: bool,
+    /// Decoders for programs without an IDL, by program ID, consulted before any IDL.
+    /// See [AnchorLens::register_decoder].
+    pub program_decoders: HashMap<Pubkey, Arc<dyn ProgramDecoder>>,
 }
 
-impl AnchorLens {
+impl<S: AccountSource> AnchorLens<S> {
     /// Initializes with caching turned off. This will make [AnchorLens::fetch_idl]
     /// make an RPC call on every call.
-    pub fn new(client: RpcClient) -> Self {
+    pub fn new(client: S) -> Self {
         Self {
             client,
-            idl_cache: RefCell::new(HashMap::new()),
+            idl_cache: Arc::new(IdlCache::new()),
             cache_idls: false,
+            idl_registry: IdlRegistry::new(),
+            disk_cache: None,
+            strictness: Strictness::default(),
+            decode_idl_writes: false,
+            program_decoders: HashMap::new(),
         }
     }
 
     /// Initializes with caching turned off. This will make [AnchorLens::fetch_idl]
-    /// look up an IDL in a [HashMap] before making an RPC call and caching
+    /// look up an IDL in `self.idl_cache` before making an RPC call and caching
     /// the result.
-    pub fn new_with_idl_caching(client: RpcClient) -> Self {
+    pub fn new_with_idl_caching(client: S) -> Self {
         Self {
             client,
-            idl_cache: RefCell::new(HashMap::new()),
+            idl_cache: Arc::new(IdlCache::new()),
             cache_idls: true,
+            idl_registry: IdlRegistry::new(),
+            disk_cache: None,
+            strictness: Strictness::default(),
+            decode_idl_writes: false,
+            program_decoders: HashMap::new(),
         }
     }
 
+    /// Decodes the instructions and accounts of `program_id` with `decoder`, ahead of
+    /// the program's IDL, or its built-in decoder. Replaces any decoder registered before.
+    pub fn register_decoder(&mut self, program_id: Pubkey, decoder: impl ProgramDecoder + 'static) {
+        self.program_decoders.insert(program_id, Arc::new(decoder));
+    }
+
     /// Attempt to find and fetch the IDL from an address.
     ///
     /// You can pass in either the program ID,
     /// or the IDL account address itself if you know it.
-    pub fn fetch_idl(&self, program_id: &Pubkey) -> Result<IdlWithDiscriminators> {
-        // Try to return a cached IDL if self is configured to do so
-        if self.cache_idls {
-            if let Some(idl) = self.idl_cache.borrow_mut().get(&program_id.to_bytes()) {
-                return Ok(idl.clone());
-            }
+    /// IDLs in `self.idl_registry` are returned without making any RPC calls.
+    pub fn fetch_idl(&self, program_id: &Pubkey) -> Result<Arc<IdlWithDiscriminators>, LensError> {
+        if let Some(idl) = self.known_idl(program_id) {
+            return Ok(idl?);
+        }
+        let fetched = self.fetch_onchain_idl(program_id);
+        if !self.cache_idls {
+            return Ok(Arc::new(fetched?));
+        }
+        Ok(self.idl_cache.record(*program_id, fetched)?)
+    }
+
+    /// The IDL of a program from `self.idl_registry`, or from `self.idl_cache` when
+    /// caching is on, without reading any accounts. Returns `None` if it must be fetched.
+    pub(crate) fn known_idl(&self, program_id: &Pubkey) -> Option<Result<Arc<IdlWithDiscriminators>>> {
+        if let Some(idl) = self.idl_registry.get(program_id) {
+            return Some(Ok(idl.clone()));
+        }
+        self.cache_idls
+            .then(|| self.idl_cache.lookup(program_id))
+            .flatten()
+    }
+
+    /// Finds the IDL that was live at `slot`, from the versions in `self.idl_registry`
+    /// or a history reconstructed with [AnchorLens::fetch_idl_history],
+    /// falling back to [AnchorLens::fetch_idl] if there are neither.
+    ///
+    /// Fails with [LensError::IdlNotLive] if `slot` is before the first known version,
+    /// unless an unversioned IDL is registered for the program.
+    pub fn fetch_idl_at(
+        &self,
+        program_id: &Pubkey,
+        slot: Option<u64>,
+    ) -> Result<Arc<IdlWithDiscriminators>, LensError> {
+        let slot = match slot {
+            Some(slot) => slot,
+            None => return self.fetch_idl(program_id),
+        };
+        if let Some(idl) = self.idl_registry.get_at(program_id, slot) {
+            return Ok(idl.clone());
+        }
+        let history = self.idl_cache.history(program_id);
+        if let Some(idl) = history.as_ref().and_then(|history| history.at(slot)) {
+            return Ok(idl.clone());
         }
-        let idl = fetch_idl(&self.client, program_id)?;
-        // Cache the fetched value if self is configured to do so
-        if self.cache_idls {
-            self.idl_cache
-                .borrow_mut()
-                .insert(program_id.to_bytes(), idl.clone());
+        let first_slot = self
+            .idl_registry
+            .versions(program_id)
+            .into_iter()
+            .chain(history.as_deref())
+            .filter_map(IdlHistory::first_slot)
+            .min();
+        match first_slot {
+            Some(first_slot) => Err(LensError::IdlNotLive {
+                program_id: *program_id,
+                slot,
+                first_slot,
+            }),
+            None => self.fetch_idl(program_id),
+        }
+    }
+
+    /// Reconstructs every version of a program's on-chain IDL from the transaction
+    /// history of its IDL account, see [fetch_idl_history]. This takes a transaction
+    /// per IDL write, so the history is kept in `self.idl_cache` for later calls,
+    /// and for [AnchorLens::fetch_idl_at].
+    pub fn fetch_idl_history(&self, program_id: &Pubkey) -> Result<Arc<IdlHistory>, LensError>
+    where
+        S: SignatureSource + TransactionSource,
+    {
+        if let Some(history) = self.idl_cache.history(program_id) {
+            return Ok(history);
+        }
+        let history = Arc::new(fetch_idl_history(&self.client, program_id)?);
+        self.idl_cache.insert_history(*program_id, history.clone());
+        Ok(history)
+    }
+
+    /// Fetches a program's IDL along with its IDL account's authority and size,
+    /// see [fetch_idl_with_metadata]. This always reads the IDL account,
+    /// bypassing `self.idl_registry` and the caches, so the metadata is current.
+    pub fn fetch_idl_with_metadata(&self, program_id: &Pubkey) -> Result<FetchedIdl, LensError> {
+        fetch_idl_with_metadata(&self.client, program_id)
+    }
+
+    /// Fetches an IDL account, through `self.disk_cache` if there is one.
+    fn fetch_onchain_idl(&self, program_id: &Pubkey) -> Result<IdlWithDiscriminators> {
+        match &self.disk_cache {
+            Some(disk_cache) => disk_cache.fetch_idl(&self.client, program_id),
+            None => Ok(fetch_idl(&self.client, program_id)?),
         }
-        Ok(idl)
     }
 
     /// Convenience function, uses `self.client` to fetch the [solana_sdk::account::Account], unserialized.
-    pub fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
-        Ok(self.client.get_account(pubkey)?)
+    pub fn get_account(&self, pubkey: &Pubkey) -> Result<Account, LensError> {
+        self
+            .client
+            .get_account(pubkey)?
+            .ok_or(LensError::AccountNotFound { pubkey: *pubkey })
     }
 
     /// Fetches a historical transaction (the message and its signatures), filtering out
     /// the rest of the usual `get_transaction` RPC response.
-    pub fn get_versioned_transaction(&self, txid: &Signature) -> Result<HistoricalTransaction> {
-        let tx = self
+    pub fn get_versioned_transaction(&self, txid: &Signature) -> Result<HistoricalTransaction, LensError>
+    where
+        S: TransactionSource,
+    {
+        self
             .client
-            .get_transaction(txid, UiTransactionEncoding::Base64)?;
-        let EncodedConfirmedTransactionWithStatusMeta {
-            transaction: EncodedTransactionWithStatusMeta { transaction, meta, .. },
-            ..
-        } = tx;
-        let mut inner_instructions = HashMap::new();
-        if let Some(UiTransactionStatusMeta {
-                        inner_instructions: OptionSerializer::Some(meta),
-                        ..
-                    }) = meta {
-            for inner_ix in meta.into_iter() {
-                inner_instructions.insert(
-                    inner_ix.index,
-                    inner_ix.instructions
-                        .into_iter()
-                        .map(|ix| {
-                            match ix {
-                                UiInstruction::Compiled(ix) => Some(
-                                    CompiledInstruction {
-                                        program_id_index: ix.program_id_index,
-                                        accounts: ix.accounts,
-                                        data: bs58::decode(ix.data).into_vec().unwrap()
-                                    }
-                                ),
-                                _ => None,
-                            }
-                        })
-                        .into_iter()
-                        .flatten()
-                        .collect::<Vec<_>>()
-                );
-            }
-        }
-        let transaction = transaction
-            .decode()
-            .ok_or(anyhow!("Failed to decode transaction"))?;
-        Ok(HistoricalTransaction { message: transaction.message, inner_instructions })
+            .get_transaction(txid)?
+            .ok_or(LensError::TransactionNotFound { signature: *txid })
     }
 
     /// Useful for repeated lookups. You can reduce RPC calls by calling
     /// [AnchorLens::fetch_idl] just once, using it in many calls to this.
+    ///
+    /// Without an IDL, accounts of programs with a registered decoder,
+    /// and of native and SPL programs, are decoded by hand.
     pub fn fetch_and_deserialize_account(
         &self,
         pubkey: &Pubkey,
         idl: Option<&IdlWithDiscriminators>,
-    ) -> Result<IdlDeserializedAccount> {
+    ) -> Result<IdlDeserializedAccount, LensError> {
         let act = self.get_account(pubkey)?;
-        let (program_name, (type_name, value)) = if let Some(idl) = idl {
-            let program_name = idl.name.clone();
-            (program_name, deserialize_account_from_idl(&idl, &act)?)
-        } else {
-            let idl = self.fetch_idl(&act.owner)?;
-            let program_name = idl.name.clone();
-            (program_name, deserialize_account_from_idl(&idl, &act)?)
+        if idl.is_none() {
+            if let Some(decoded) = self.hand_decoded_account(&act) {
+                return Ok(decoded?);
+            }
+        }
+        let fetched;
+        let idl = match idl {
+            Some(idl) => idl,
+            None => {
+                fetched = self.fetch_idl(&act.owner)?;
+                &*fetched
+            }
         };
-        Ok(IdlDeserializedAccount {
+        Ok(self.idl_deserialized_account(idl, &act)?)
+    }
+
+    /// Decodes an account with its owner's registered decoder, or its native decoder,
+    /// if the account is of a native or SPL program. Returns `None` if neither handles it.
+    pub(crate) fn hand_decoded_account(&self, act: &Account) -> Option<Result<IdlDeserializedAccount>> {
+        let registered = self.program_decoders.get(&act.owner).and_then(|decoder| {
+            Some((decoder.program_name().to_string(), decoder.decode_account(&act.data)?))
+        });
+        let (program_name, decoded) = registered.or_else(|| {
+            let decoded = deser_native_account(&act.owner, &act.data)?;
+            Some((native_program_name(&act.owner).unwrap_or_default().to_string(), decoded))
+        })?;
+        Some(decoded.map(|(type_name, data)| IdlDeserializedAccount {
             program_name,
             type_name,
-            data: value,
+            data,
+            warnings: vec![],
+        }))
+    }
+
+    /// Decodes an account with its owner's IDL, at `self.strictness`.
+    pub(crate) fn idl_deserialized_account(
+        &self,
+        idl: &IdlWithDiscriminators,
+        act: &Account,
+    ) -> Result<IdlDeserializedAccount> {
+        let (type_name, data, warnings) =
+            deserialize_account_from_idl_with_strictness(idl, act, self.strictness)?;
+        Ok(IdlDeserializedAccount {
+            program_name: idl.name.clone(),
+            type_name,
+            data,
+            warnings,
+        })
+    }
+
+    /// Like [AnchorLens::fetch_and_deserialize_account], but decodes the account as the
+    /// IDL account or type named `type_name`, see [deserialize_account_as_type].
+    /// The IDL of the account's owner is fetched if none is given.
+    pub fn fetch_and_deserialize_account_as(
+        &self,
+        pubkey: &Pubkey,
+        type_name: &str,
+        idl: Option<&IdlWithDiscriminators>,
+    ) -> Result<IdlDeserializedAccount, LensError> {
+        let act = self.get_account(pubkey)?;
+        let fetched;
+        let idl = match idl {
+            Some(idl) => idl,
+            None => {
+                fetched = self.fetch_idl(&act.owner)?;
+                &*fetched
+            }
+        };
+        let (data, warnings) = deserialize_account_as_type(idl, &act, type_name, self.strictness)?;
+        Ok(IdlDeserializedAccount {
+            program_name: idl.name.clone(),
+            type_name: type_name.to_string(),
+            data,
+            warnings,
         })
     }
 
     /// Attempts deserialization of a given transaction instruction.
-    /// The [VersionedMessage] passed in is from the same transaction.
+    /// The [VersionedMessage] passed in is from the same transaction,
+    /// as are the `loaded_addresses` resolved from its address lookup tables.
     /// If the attempt fails, we return a JSON object indicating the
     /// reason for failure, and any other information.
     fn deserialize_ix(&self,
         i: usize,
         ix: &CompiledInstruction,
         message: &VersionedMessage,
+        loaded_addresses: &LoadedAddresses,
         inner_instructions: Option<&Vec<CompiledInstruction>>,
+        slot: Option<u64>,
     ) -> Result<Value> {
         // Calculate the inner instructions up front.
         let inner_ix = {
@@ -205,7 +507,9 @@ impl AnchorLens {
                         i,
                         ix,
                         message,
+                        loaded_addresses,
                         None,
+                        slot,
                     )?);
                 }
             }
@@ -213,53 +517,173 @@ impl AnchorLens {
         };
         // Get program ID, find IDL
         let idx = ix.program_id_index;
-        let program_id = message.static_account_keys()[idx as usize];
-        let idl = self.fetch_idl(&program_id);
-        // Try fetching the IDL and deserializing.
-        let mut json = if let Ok(idl) = idl {
-            // If there's an IDL, we can try deserializing
-            let maybe_deserialized = deser_ix_data_from_idl(&idl, ix.data.clone());
-            if let Ok((idl_ix, ix_data)) = maybe_deserialized {
-                // If we succeeded in deserializing the instruction data,
-                // then we can also name each account passed in to the instruction.
-                let accounts = {
-                    let mut metas: Vec<Value> = vec![];
-                    let mut increment: usize = 0;
-                    let account_meta_groups =
-                        AccountMetaGroups::new_from_message(message.clone(), ix.accounts.clone());
-                    account_meta_groups.idl_accounts_to_json(
-                        &mut increment,
-                        idl_ix.accounts.clone(),
-                        &mut metas,
-                    );
-                    metas
-                };
-                let json = json!({
-                   "program_id": program_id.to_string(),
-                   "program_name": idl.name,
-                   "instruction": {
-                       "name": idl_ix.name,
-                       "data": ix_data,
-                       "accounts": accounts
+        let program_id = *full_account_keys(message, loaded_addresses)
+            .get(idx as usize)
+            .ok_or(anyhow!("Program ID index {} is out of bounds in instruction {}", idx, i))?;
+        let accounts = |idl_accounts| {
+            let mut metas: Vec<Value> = vec![];
+            let mut increment: usize = 0;
+            let account_meta_groups =
+                AccountMetaGroups::new_from_message(
+                    message.clone(),
+                    loaded_addresses,
+                    ix.accounts.clone(),
+                );
+            account_meta_groups.idl_accounts_to_json(
+                &mut increment,
+                idl_accounts,
+                &mut metas,
+            );
+            metas
+        };
+        let registered = self.program_decoders.get(&program_id).and_then(|decoder| {
+            Some((decoder, decoder.decode_instruction(&ix.data, ix.accounts.len())?))
+        });
+        let mut json = if let Some((decoder, maybe_deserialized)) = registered {
+            // Registered decoders take precedence over any other means of decoding.
+            match maybe_deserialized {
+                Ok((name, data)) => {
+                    let decoded_ix = NativeInstruction {
+                        accounts: decoder.name_accounts(&name, &data, ix.accounts.len()),
+                        name,
+                        data,
+                    };
+                    json!({
+                        "program_id": program_id.to_string(),
+                        "program_name": decoder.program_name(),
+                        "instruction": {
+                            "name": decoded_ix.name,
+                            "data": decoded_ix.data,
+                            "accounts": accounts(decoded_ix.idl_accounts()),
+                        }
+                    })
+                }
+                Err(err) => json!({
+                    "program_id": program_id.to_string(),
+                    "program_name": decoder.program_name(),
+                    "decode_error": err.to_string(),
+                }),
+            }
+        } else if let Some(maybe_deserialized) =
+            deser_native_ix_with_strictness(&program_id, &ix.data, ix.accounts.len(), self.strictness)
+        {
+            // Native and SPL programs don't publish an IDL, so they're decoded by hand.
+            match maybe_deserialized {
+                Ok((native_ix, warnings)) => json!({
+                    "program_id": program_id.to_string(),
+                    "program_name": native_program_name(&program_id),
+                    "instruction": with_warnings(json!({
+                        "name": native_ix.name,
+                        "data": native_ix.data,
+                        "accounts": accounts(native_ix.idl_accounts()),
+                    }), warnings)
+                }),
+                Err(err) if is_decode_error(&err) => json!({
+                    "program_id": program_id.to_string(),
+                    "program_name": native_program_name(&program_id),
+                    "decode_error": err.to_string(),
+                }),
+                Err(_) => json!({
+                    "program_id": program_id.to_string(),
+                    "program_name": native_program_name(&program_id),
+                    "unknown_discriminator": format!("instruction {}", i)
+                }),
+            }
+        } else if let Some(maybe_deserialized) =
+            deser_idl_ix_data_with_strictness(&ix.data, self.strictness)
+        {
+            // Anchor's built-in IDL management instructions aren't listed in the IDL.
+            match maybe_deserialized {
+                Ok((idl_ix, ix_data, warnings)) => json!({
+                    "program_id": program_id.to_string(),
+                    "idl_instruction": with_warnings(json!({
+                        "name": idl_ix.name,
+                        "data": ix_data,
+                        "accounts": accounts(idl_ix.accounts.clone()),
+                    }), warnings)
+                }),
+                Err(err) => json!({
+                    "program_id": program_id.to_string(),
+                    "decode_error": err.to_string(),
+                }),
+            }
+        } else {
+            // Try fetching the IDL and deserializing.
+            let idl = self.fetch_idl_at(&program_id, slot);
+            if let (Ok(idl), Some(event_data)) =
+                (&idl, ix.data.strip_prefix(&EVENT_IX_TAG_LE[..]))
+            {
+                // Events emitted with `emit_cpi!` arrive as a self-invocation
+                // with the event data in place of instruction data.
+                match deser_event_data_from_idl_with_strictness(idl, event_data, self.strictness) {
+                    Ok((idl_event, data, warnings)) => json!({
+                        "program_id": program_id.to_string(),
+                        "program_name": idl.name,
+                        "event": with_warnings(json!({
+                            "name": idl_event.name,
+                            "data": data,
+                        }), warnings)
+                    }),
+                    Err(err) if is_decode_error(&err) => json!({
+                        "program_id": program_id.to_string(),
+                        "program_name": idl.name,
+                        "decode_error": err.to_string(),
+                    }),
+                    Err(_) => json!({
+                        "program_id": program_id.to_string(),
+                        "unknown_event": base64::encode(event_data),
+                    }),
+                }
+            } else if let Ok(idl) = idl {
+                // If there's an IDL, we can try deserializing
+                let maybe_deserialized =
+                    deser_ix_data_from_idl_with_strictness(&idl, ix.data.clone(), self.strictness);
+                match maybe_deserialized {
+                    Ok((idl_ix, ix_data, warnings)) => {
+                        // If we succeeded in deserializing the instruction data,
+                        // then we can also name each account passed in to the instruction.
+                        let accounts = accounts(idl_ix.accounts.clone());
+                        let json = json!({
+                           "program_id": program_id.to_string(),
+                           "program_name": idl.name,
+                           "instruction": with_warnings(json!({
+                               "name": idl_ix.name,
+                               "data": ix_data,
+                               "accounts": accounts
+                            }), warnings)
+                        });
+                        json
                     }
-                });
-                json
+                    Err(err) if is_decode_error(&err) => {
+                        // The discriminator matched, but the data doesn't fit the IDL.
+                        json!({
+                           "program_id": program_id.to_string(),
+                           "program_name": idl.name,
+                           "decode_error": err.to_string(),
+                        })
+                    }
+                    Err(_) => {
+                        // If the IDL contains no matching discriminator,
+                        // then it's not up to date or invalid.
+                        let json = json!({
+                           "program_id": program_id.to_string(),
+                           "unknown_discriminator": format!("instruction {}", i)
+                        });
+                        json
+                    }
+                }
             } else {
-                // If the IDL contains no matching discriminator,
-                // then it's not up to date or invalid.
-                let json = json!({
-                   "program_id": program_id.to_string(),
-                   "unknown_discriminator": format!("instruction {}", i)
-                });
+                // If there's no IDL, we cannot deserialize
+                let mut json = json!({
+                       "program_id": program_id.to_string(),
+                       "unknown_ix": format!("instruction {}", i)
+                    });
+                // Tell a program that hadn't published its IDL yet from one without any.
+                if let Err(err @ LensError::IdlNotLive { .. }) = &idl {
+                    json["decode_error"] = json!(err.to_string());
+                }
                 json
             }
-        } else {
-            // If there's no IDL, we cannot deserialize
-            let json = json!({
-                   "program_id": program_id.to_string(),
-                   "unknown_ix": format!("instruction {}", i)
-                });
-            json
         };
         // Optionally append any inner instructions
         if !inner_ix.is_empty() {
@@ -280,35 +704,223 @@ impl AnchorLens {
     /// marked mutable), this will flag it with an appropriate
     /// [crate::deserialize::instruction::AccountMetaStatus] variant.
     ///
+    /// Events emitted through `emit!` are decoded from the transaction logs, and listed
+    /// under `events` on the instruction (or inner instruction) that emitted them.
+    /// Events emitted through `emit_cpi!` instead show up as inner instructions,
+    /// with an `event` in place of the `instruction`.
+    ///
+    /// Anchor's built-in IDL management instructions are listed with an `idl_instruction`
+    /// in place of the `instruction`. When `self.decode_idl_writes` is set, the `write`
+    /// instruction that completes an IDL also carries the decompressed `written_idl`,
+    /// see [written_idls].
+    ///
+    /// The output is an object with the decoded `instructions`, the transaction
+    /// `error` (`null` if it succeeded), with custom error codes mapped to their
+    /// names and messages from the IDL, or Anchor's built-in errors if the program has
+    /// an Anchor IDL, and the
+    /// `compute_budget` it requested and paid for, see [ComputeBudgetSummary].
+    ///
+    /// Each program's IDL is resolved for the transaction's slot with [AnchorLens::fetch_idl_at],
+    /// so IDL versions in the registry or previously fetched histories are used automatically.
+    /// See [AnchorLens::deserialize_transaction_with_idl_history] to fetch the histories.
+    ///
     /// Caution: This calls the `fetch_idl` method on every instruction. Caching is advised!
-    pub fn deserialize_transaction(&self, tx: HistoricalTransaction) -> Result<Value> {
+    pub fn deserialize_transaction(&self, tx: HistoricalTransaction) -> Result<Value, LensError> {
         let mut instructions_deserialized = vec![];
         for (i, ix) in tx.message.instructions()
             .iter()
             .enumerate() {
             instructions_deserialized.push(
-              self.deserialize_ix(i, ix, &tx.message,
-                                  tx.inner_instructions.get(&u8::try_from(i).unwrap())
+              self.deserialize_ix(i, ix, &tx.message, &tx.loaded_addresses,
+                                  tx.inner_instructions.get(&u8::try_from(i).unwrap()),
+                                  tx.slot,
               )?
             );
         }
-        Ok(Value::Array(instructions_deserialized))
+        // Every instruction's program was found above, and programs are never loaded.
+        let program_ids = tx
+            .message
+            .instructions()
+            .iter()
+            .map(|ix| tx.message.static_account_keys()[ix.program_id_index as usize])
+            .collect::<Vec<_>>();
+        for event in extract_logged_events(&tx.log_messages, &program_ids) {
+            let json = match self.deserialize_logged_event(&event, tx.slot) {
+                Some(json) => json,
+                None => continue,
+            };
+            let emitter = instructions_deserialized
+                .get_mut(event.instruction_index)
+                .and_then(|ix_json| match event.inner_instruction_index {
+                    Some(inner) => ix_json
+                        .get_mut("inner_instructions")
+                        .and_then(|inner_ixs| inner_ixs.get_mut(inner)),
+                    None => Some(ix_json),
+                });
+            if let Some(Value::Object(emitter)) = emitter {
+                emitter
+                    .entry("events")
+                    .or_insert(Value::Array(vec![]))
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json);
+            }
+        }
+        if self.decode_idl_writes {
+            for ((i, inner), idl) in written_idls(&tx) {
+                let writer = instructions_deserialized
+                    .get_mut(i)
+                    .and_then(|ix_json| match inner {
+                        Some(inner) => ix_json
+                            .get_mut("inner_instructions")
+                            .and_then(|inner_ixs| inner_ixs.get_mut(inner)),
+                        None => Some(ix_json),
+                    });
+                if let Some(Value::Object(writer)) = writer {
+                    writer.insert("written_idl".to_string(), idl);
+                }
+            }
+        }
+        let error = match &tx.err {
+            Some(err) => self.deserialize_transaction_error(err, &tx),
+            None => Value::Null,
+        };
+        let compute_budget =
+            ComputeBudgetSummary::from_message(&tx.message, tx.compute_units_consumed);
+        Ok(json!({
+            "instructions": instructions_deserialized,
+            "error": error,
+            "compute_budget": compute_budget,
+        }))
+    }
+
+    /// Like [AnchorLens::deserialize_transaction], but first reconstructs the IDL history
+    /// of every program the transaction invoked, so that old transactions are decoded
+    /// with the IDLs that were live when they landed, rather than today's.
+    /// Programs with an IDL in `self.idl_registry`, versioned or not, are left to it.
+    pub fn deserialize_transaction_with_idl_history(&self, tx: HistoricalTransaction) -> Result<Value, LensError>
+    where
+        S: SignatureSource + TransactionSource,
+    {
+        if tx.slot.is_some() {
+            for program_id in tx.program_ids() {
+                if !self.idl_registry.is_registered(&program_id) {
+                    self.fetch_idl_history(&program_id)?;
+                }
+            }
+        }
+        self.deserialize_transaction(tx)
+    }
+
+    /// Describes why a transaction failed, resolving custom error codes against
+    /// the IDL of the program that raised them. See [transaction_error_json].
+    fn deserialize_transaction_error(
+        &self,
+        err: &TransactionError,
+        tx: &HistoricalTransaction,
+    ) -> Value {
+        // The logs name the innermost failing program, which can be a CPI target
+        // rather than the program of the failing top-level instruction.
+        let program_id = failed_program_from_logs(&tx.log_messages).or_else(|| match err {
+            TransactionError::InstructionError(i, _) => {
+                let ix = tx.message.instructions().get(*i as usize)?;
+                full_account_keys(&tx.message, &tx.loaded_addresses)
+                    .get(ix.program_id_index as usize)
+                    .copied()
+            }
+            _ => None,
+        });
+        let idl = program_id.and_then(|program_id| self.fetch_idl_at(&program_id, tx.slot).ok());
+        transaction_error_json(err, program_id.as_ref(), idl.as_deref())
+    }
+
+    /// Decodes an event found in the transaction logs using the emitting program's IDL.
+    /// Programs without an IDL are skipped entirely, since `Program data:` logs
+    /// aren't exclusive to Anchor. If the program does have an IDL but the event
+    /// doesn't match any of its discriminators, the raw data is reported instead.
+    fn deserialize_logged_event(&self, event: &LoggedEvent, slot: Option<u64>) -> Option<Value> {
+        let idl = self.fetch_idl_at(&event.program_id, slot).ok()?;
+        Some(match deser_event_data_from_idl_with_strictness(&idl, &event.data, self.strictness) {
+            Ok((idl_event, data, warnings)) => with_warnings(json!({
+                "name": idl_event.name,
+                "data": data,
+            }), warnings),
+            Err(err) if is_decode_error(&err) => json!({
+                "decode_error": err.to_string(),
+            }),
+            Err(_) => json!({
+                "unknown_event": base64::encode(&event.data),
+            }),
+        })
     }
 
     /// Deserialize just a transaction message, no inner instructions.
     ///
     /// This is useful for deserializing transaction messages that have no yet been published
     /// to the blockchain, since in that case all you have is the [VersionedMessage].
-    pub fn deserialize_message(&self, message: &VersionedMessage) -> Result<Value> {
+    /// Any address lookup tables the message uses are fetched to resolve its accounts.
+    ///
+    /// The output has the same shape as [AnchorLens::deserialize_transaction]'s, without
+    /// an `error`, and without the compute units consumed, since the message hasn't run.
+    pub fn deserialize_message(&self, message: &VersionedMessage) -> Result<Value, LensError> {
+        let loaded_addresses = self.resolve_address_lookup_tables(message)?;
         let mut instructions_deserialized = vec![];
         for (i, ix) in message.instructions()
             .iter()
             .enumerate() {
             instructions_deserialized.push(
-                self.deserialize_ix(i, ix, message, None)?
+                self.deserialize_ix(i, ix, message, &loaded_addresses, None, None)?
             );
         }
-        Ok(Value::Array(instructions_deserialized))
+        Ok(json!({
+            "instructions": instructions_deserialized,
+            "compute_budget": ComputeBudgetSummary::from_message(message, None),
+        }))
+    }
+
+    /// Fetches the address lookup tables used by a v0 message, and selects the addresses
+    /// the message loads from them, the same way the runtime would. Legacy messages
+    /// don't use lookup tables, so this makes no RPC calls for them.
+    ///
+    /// Historical transactions don't need this, the RPC reports the loaded
+    /// addresses alongside the transaction, see [HistoricalTransaction].
+    pub fn resolve_address_lookup_tables(
+        &self,
+        message: &VersionedMessage,
+    ) -> Result<LoadedAddresses, LensError> {
+        let mut loaded_addresses = LoadedAddresses::default();
+        let lookups = match message {
+            VersionedMessage::Legacy(_) => return Ok(loaded_addresses),
+            VersionedMessage::V0(message) => &message.address_table_lookups,
+        };
+        for lookup in lookups {
+            let table = self.get_account(&lookup.account_key)?;
+            let addresses = lookup_table_addresses(&table.data)?;
+            let select = |indexes: &Vec<u8>| {
+                indexes
+                    .iter()
+                    .map(|i| {
+                        addresses.get(*i as usize).copied().ok_or(anyhow!(
+                            "Index {} is out of bounds in lookup table {}",
+                            i,
+                            lookup.account_key
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            };
+            loaded_addresses.writable.extend(select(&lookup.writable_indexes)?);
+            loaded_addresses.readonly.extend(select(&lookup.readonly_indexes)?);
+        }
+        Ok(loaded_addresses)
+    }
+}
+
+/// Reads the addresses stored in an address lookup table account.
+pub fn lookup_table_addresses(data: &[u8]) -> Result<Vec<Pubkey>> {
+    let mut reader = NativeReader::new(address_lookup_table::ID, data);
+    match read_lookup_table(&mut reader)? {
+        Some(table) => Ok(table.addresses),
+        None => Err(anyhow!("Address lookup table account is uninitialized")),
     }
 }
 
@@ -316,29 +928,86 @@ impl AnchorLens {
 /// which performs just the deserialization attempt based on an IDL.
 /// Returns a tuple of the account type name, and its deserialized
 /// data encoded as a [serde_json::Value].
+///
+/// Leftover bytes after the account's fields are ignored, since accounts are often
+/// allocated with room to grow. Use [deserialize_account_from_idl_with_strictness]
+/// to check that the account matches its IDL type exactly.
 pub fn deserialize_account_from_idl(
     idl: &IdlWithDiscriminators,
     account: &Account,
-) -> Result<(String, Value)> {
+) -> Result<(String, Value), LensError> {
+    let (type_name, value, _) =
+        deserialize_account_from_idl_with_strictness(idl, account, Strictness::Lenient)?;
+    Ok((type_name, value))
+}
+
+/// Like [deserialize_account_from_idl], but checks the account data against the IDL
+/// according to `strictness`, returning any warnings it raised.
+/// Byte offsets in errors and warnings count from the start of the account data.
+pub fn deserialize_account_from_idl_with_strictness(
+    idl: &IdlWithDiscriminators,
+    account: &Account,
+    strictness: Strictness,
+) -> Result<(String, Value, Vec<DecodeWarning>), LensError> {
     let idl_type_defs = idl.types.clone();
-    let mut first_eight = account.data.to_vec();
-    first_eight.resize(8, 0);
-    let first_eight: [u8; 8] = first_eight.try_into().unwrap();
+    let (discriminator, type_def) = idl
+        .discriminators
+        .accounts
+        .get(&account.data)
+        .ok_or_else(|| {
+            IdlItemKind::Account.unknown_discriminator(Some(account.owner), &account.data)
+        })?;
+    let mut ctx = DecodeContext::new(strictness, Some(account.owner), &account.data, &type_def.name);
+    let mut data = &account.data[discriminator.len()..];
+    let value = TypeDefinitionDeserializer {
+        idl_type_defs,
+        curr_type: type_def.clone(),
+    }
+    .deserialize_in(&mut data, &mut ctx)?;
+    ctx.finish(data)?;
+    Ok((type_def.name.clone(), value, ctx.warnings))
+}
+
+/// Decodes the account as the IDL account or type named `type_name`, rather than the one
+/// its discriminator matches. This is how accounts without a discriminator are decoded,
+/// such as those of native programs whose IDL doesn't say how to tell them apart.
+///
+/// If the named account has a discriminator, the data must start with it,
+/// and it's skipped before decoding, as it is by [deserialize_account_from_idl].
+pub fn deserialize_account_as_type(
+    idl: &IdlWithDiscriminators,
+    account: &Account,
+    type_name: &str,
+    strictness: Strictness,
+) -> Result<(Value, Vec<DecodeWarning>), LensError> {
     let type_def = idl
+        .accounts
+        .iter()
+        .chain(&idl.types)
+        .find(|type_def| type_def.name == type_name)
+        .ok_or_else(|| LensError::UnknownType {
+            program_id: Some(account.owner),
+            type_name: type_name.to_string(),
+        })?;
+    let discriminator = idl
         .discriminators
         .accounts
-        .get(&first_eight)
-        .ok_or(anyhow!(
-            "Could not match account data against any discriminator"
-        ))?;
-    Ok((
-        (type_def.name.clone()),
-        TypeDefinitionDeserializer {
-            idl_type_defs,
-            curr_type: type_def.clone(),
-        }
-        .deserialize(&mut account.data.as_slice())?,
-    ))
+        .iter()
+        .find(|(_, account_def)| account_def.name == type_name)
+        .map(|(discriminator, _)| discriminator.as_slice())
+        .unwrap_or_default();
+    if !account.data.starts_with(discriminator) {
+        return Err(IdlItemKind::Account.unknown_discriminator(Some(account.owner), &account.data));
+    }
+    let mut ctx = DecodeContext::new(strictness, Some(account.owner), &account.data, type_name);
+    let mut data = &account.data[discriminator.len()..];
+    let value = TypeDefinitionDeserializer {
+        idl_type_defs: idl.types.clone(),
+        curr_type: type_def.clone(),
+    }
+    .deserialize_in(&mut data, &mut ctx)?;
+    ctx.finish(data)?;
+    Ok((value, ctx.warnings))
 }
 
 /// Fetches the account data, attempts to deserialize it, and returns
@@ -349,9 +1018,50 @@ pub fn deserialized_account_json(
     idl: &IdlWithDiscriminators,
     address: &Pubkey,
     account: Account,
-) -> Result<Value> {
-    let (account_type, deserialized) = deserialize_account_from_idl(idl, &account)?;
-    Ok(json!({
+) -> Result<Value, LensError> {
+    deserialized_account_json_with_strictness(idl, address, account, Strictness::Lenient)
+}
+
+/// Like [deserialized_account_json], checking the account data according to `strictness`.
+/// Any warnings are listed under `warnings`.
+pub fn deserialized_account_json_with_strictness(
+    idl: &IdlWithDiscriminators,
+    address: &Pubkey,
+    account: Account,
+    strictness: Strictness,
+) -> Result<Value, LensError> {
+    let (account_type, deserialized, warnings) =
+        deserialize_account_from_idl_with_strictness(idl, &account, strictness)?;
+    Ok(with_warnings(
+        account_json(address, &account, &idl.name, &account_type, deserialized),
+        warnings,
+    ))
+}
+
+/// Like [deserialized_account_json], for an account of a native or SPL program,
+/// such as a token mint or account. Returns `None` if the account's owner has no
+/// native decoder, see [native].
+pub fn deserialized_native_account_json(address: &Pubkey, account: Account) -> Option<Result<Value, LensError>> {
+    let program_name = native_program_name(&account.owner)?;
+    let decoded = deser_native_account(&account.owner, &account.data)?;
+    Some(
+        decoded
+            .map(|(account_type, deserialized)| {
+                account_json(address, &account, program_name, &account_type, deserialized)
+            })
+            .map_err(LensError::from),
+    )
+}
+
+/// An account as `solana-test-validator --account` reads it, with its decoded data.
+fn account_json(
+    address: &Pubkey,
+    account: &Account,
+    program_name: &str,
+    account_type: &str,
+    deserialized: Value,
+) -> Value {
+    json!({
         "pubkey": address.to_string(),
         "account": {
             "data": UiAccountData::Binary(
@@ -363,8 +1073,25 @@ pub fn deserialized_account_json(
             "executable": account.executable,
             "rent_epoch": account.rent_epoch,
         },
-        "program_name": idl.name.clone(),
+        "program_name": program_name,
         "account_type": account_type,
         "deserialized": deserialized,
-    }))
+    })
+}
+
+/// Whether the data matched a discriminator, but then didn't fit the IDL.
+fn is_decode_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<LensError>()
+        .and_then(LensError::location)
+        .is_some()
+}
+
+/// Lists any warnings under `warnings` on a JSON object, leaving it untouched if there are none.
+fn with_warnings(mut json: Value, warnings: Vec<DecodeWarning>) -> Value {
+    if !warnings.is_empty() {
+        json.as_object_mut()
+            .unwrap()
+            .insert("warnings".to_string(), json!(warnings));
+    }
+    json
 }
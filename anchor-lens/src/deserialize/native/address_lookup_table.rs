@@ -0,0 +1,155 @@
+//! The Address Lookup Table program. Its instructions and account states are
+//! bincode-encoded with a `u32` variant index.
+
+use crate::deserialize::native::{
+    readonly, signer, writable, writable_signer, NativeInstruction, NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::{json, Value};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("AddressLookupTab1e1111111111111111111111111");
+
+/// The size of a lookup table's header, before its addresses.
+pub const LOOKUP_TABLE_META_LEN: usize = 56;
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "create_lookup_table",
+        1 => "freeze_lookup_table",
+        2 => "extend_lookup_table",
+        3 => "deactivate_lookup_table",
+        4 => "close_lookup_table",
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Instruction, &variant.to_le_bytes()));
+        }
+    };
+    reader.set_type_name(name);
+    let (data, accounts) = match variant {
+        // The authority had to sign before lookup tables could be created for any key.
+        0 => (
+            json!({
+                "recent_slot": reader.u64("recent_slot")?,
+                "bump_seed": reader.u8("bump_seed")?,
+            }),
+            vec![
+                writable("lookup_table"),
+                readonly("authority"),
+                writable_signer("payer"),
+                readonly("system_program"),
+            ],
+        ),
+        // The payer and system program are only needed if the table must grow.
+        2 => {
+            let mut new_addresses = vec![];
+            for _ in 0..reader.bincode_len("new_addresses")? {
+                new_addresses.push(reader.pubkey("new_addresses")?.to_string());
+            }
+            (
+                json!({ "new_addresses": new_addresses }),
+                vec![
+                    writable("lookup_table"),
+                    signer("authority"),
+                    writable_signer("payer"),
+                    readonly("system_program"),
+                ],
+            )
+        }
+        4 => (
+            json!({}),
+            vec![
+                writable("lookup_table"),
+                signer("authority"),
+                writable("recipient"),
+            ],
+        ),
+        _ => (
+            json!({}),
+            vec![writable("lookup_table"), signer("authority")],
+        ),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
+
+/// A lookup table's `LookupTableMeta` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupTableMeta {
+    /// `u64::MAX` for tables that aren't deactivated.
+    pub deactivation_slot: u64,
+    pub last_extended_slot: u64,
+    pub last_extended_slot_start_index: u8,
+    /// `None` once the table is frozen.
+    pub authority: Option<Pubkey>,
+}
+
+/// An initialized lookup table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupTable {
+    pub meta: LookupTableMeta,
+    pub addresses: Vec<Pubkey>,
+}
+
+/// Reads a lookup table account, returning `None` if it's uninitialized.
+pub fn read_lookup_table(reader: &mut NativeReader) -> Result<Option<LookupTable>, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "Uninitialized",
+        1 => "LookupTable",
+        _ => return Err(reader.unknown(IdlItemKind::Account, &variant.to_le_bytes())),
+    };
+    reader.set_type_name(name);
+    if variant == 0 {
+        return Ok(None);
+    }
+    let meta = LookupTableMeta {
+        deactivation_slot: reader.u64("meta.deactivation_slot")?,
+        last_extended_slot: reader.u64("meta.last_extended_slot")?,
+        last_extended_slot_start_index: reader.u8("meta.last_extended_slot_start_index")?,
+        authority: reader.option("meta.authority", NativeReader::pubkey)?,
+    };
+    reader.bytes(
+        "meta",
+        LOOKUP_TABLE_META_LEN.saturating_sub(reader.offset()),
+    )?;
+    let addresses = reader
+        .rest()
+        .chunks(32)
+        .map(|chunk| {
+            <[u8; 32]>::try_from(chunk)
+                .map(Pubkey::new_from_array)
+                .map_err(|_| reader.invalid("addresses", "Truncated address".to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(LookupTable { meta, addresses }))
+}
+
+/// Decodes a lookup table, its `LookupTableMeta` and the addresses it holds.
+pub fn decode_account(reader: &mut NativeReader) -> Result<(String, Value), LensError> {
+    let table = match read_lookup_table(reader)? {
+        Some(table) => table,
+        None => return Ok(("Uninitialized".to_string(), json!({}))),
+    };
+    let meta = table.meta;
+    let meta = json!({
+        // Tables that aren't deactivated have a deactivation slot of `u64::MAX`.
+        "deactivation_slot": (meta.deactivation_slot != u64::MAX).then_some(meta.deactivation_slot),
+        "last_extended_slot": meta.last_extended_slot,
+        "last_extended_slot_start_index": meta.last_extended_slot_start_index,
+        "authority": meta.authority.map(|authority| authority.to_string()),
+    });
+    let addresses = table
+        .addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    Ok((
+        "LookupTable".to_string(),
+        json!({ "meta": meta, "addresses": addresses }),
+    ))
+}
@@ -0,0 +1,50 @@
+//! The SPL Associated Token Account program. Its instructions are a borsh enum
+//! without arguments, and an empty instruction is the original `create`.
+
+use crate::deserialize::native::{
+    readonly, writable, writable_signer, NativeInstruction, NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::json;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = if reader.is_exhausted() { 0 } else { reader.u8("")? };
+    let (name, accounts) = match variant {
+        0 | 1 => (
+            match variant {
+                0 => "create",
+                _ => "create_idempotent",
+            },
+            vec![
+                writable_signer("funding_account"),
+                writable("associated_token_account"),
+                readonly("wallet"),
+                readonly("mint"),
+                readonly("system_program"),
+                readonly("token_program"),
+            ],
+        ),
+        2 => (
+            "recover_nested",
+            vec![
+                writable("nested_associated_token_account"),
+                readonly("nested_mint"),
+                writable("destination_associated_token_account"),
+                readonly("owner_associated_token_account"),
+                readonly("owner_mint"),
+                writable_signer("wallet"),
+                readonly("token_program"),
+            ],
+        ),
+        _ => return Err(reader.unknown(IdlItemKind::Instruction, &[variant])),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data: json!({}),
+        accounts,
+    })
+}
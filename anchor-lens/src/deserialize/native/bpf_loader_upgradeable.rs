@@ -0,0 +1,166 @@
+//! The upgradeable BPF loader, which deploys and upgrades programs. Its instructions
+//! and account states are bincode-encoded with a `u32` variant index.
+
+use crate::deserialize::native::{
+    optional_pubkey, readonly, signer, writable, writable_signer, NativeInstruction, NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::{json, Value};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+/// The size of a buffer's header, which has room for an authority even without one.
+pub const BUFFER_METADATA_LEN: usize = 4 + 1 + 32;
+/// The size of a program data account's header, before the program's bytecode.
+pub const PROGRAMDATA_METADATA_LEN: usize = 4 + 8 + 1 + 32;
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "initialize_buffer",
+        1 => "write",
+        2 => "deploy_with_max_data_len",
+        3 => "upgrade",
+        4 => "set_authority",
+        5 => "close",
+        6 => "extend_program",
+        7 => "set_authority_checked",
+        8 => "migrate",
+        9 => "extend_program_checked",
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Instruction, &variant.to_le_bytes()));
+        }
+    };
+    reader.set_type_name(name);
+    let (data, accounts) = match variant {
+        0 => (
+            json!({}),
+            vec![writable("buffer"), readonly("buffer_authority")],
+        ),
+        1 => {
+            let offset = reader.u32("offset")?;
+            let len = reader.bincode_len("bytes")?;
+            let bytes = reader.bytes("bytes", len)?;
+            (
+                json!({ "offset": offset, "bytes": base64::encode(bytes) }),
+                vec![writable("buffer"), signer("buffer_authority")],
+            )
+        }
+        2 => (
+            json!({ "max_data_len": reader.u64("max_data_len")? }),
+            vec![
+                writable_signer("payer"),
+                writable("program_data"),
+                writable("program"),
+                writable("buffer"),
+                readonly("rent_sysvar"),
+                readonly("clock_sysvar"),
+                readonly("system_program"),
+                signer("upgrade_authority"),
+            ],
+        ),
+        3 => (
+            json!({}),
+            vec![
+                writable("program_data"),
+                writable("program"),
+                writable("buffer"),
+                writable("spill"),
+                readonly("rent_sysvar"),
+                readonly("clock_sysvar"),
+                signer("upgrade_authority"),
+            ],
+        ),
+        // Without a new authority, the buffer or program becomes immutable.
+        4 => (
+            json!({}),
+            vec![
+                writable("account"),
+                signer("current_authority"),
+                readonly("new_authority"),
+            ],
+        ),
+        // The program is only passed when closing its program data.
+        5 => (
+            json!({}),
+            vec![
+                writable("account"),
+                writable("recipient"),
+                signer("authority"),
+                writable("program"),
+            ],
+        ),
+        6 => (
+            json!({ "additional_bytes": reader.u32("additional_bytes")? }),
+            vec![
+                writable("program_data"),
+                writable("program"),
+                readonly("system_program"),
+                writable_signer("payer"),
+            ],
+        ),
+        7 => (
+            json!({}),
+            vec![
+                writable("account"),
+                signer("current_authority"),
+                signer("new_authority"),
+            ],
+        ),
+        8 => (
+            json!({}),
+            vec![
+                writable("program_data"),
+                writable("program"),
+                signer("authority"),
+            ],
+        ),
+        _ => (
+            json!({ "additional_bytes": reader.u32("additional_bytes")? }),
+            vec![
+                writable("program_data"),
+                writable("program"),
+                signer("authority"),
+                readonly("system_program"),
+                writable_signer("payer"),
+            ],
+        ),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
+
+/// Decodes a buffer, program or program data account. The bytecode that follows
+/// a buffer's or program data's header is summarized by its length.
+pub fn decode_account(reader: &mut NativeReader) -> Result<(String, Value), LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "Uninitialized",
+        1 => "Buffer",
+        2 => "Program",
+        3 => "ProgramData",
+        _ => return Err(reader.unknown(IdlItemKind::Account, &variant.to_le_bytes())),
+    };
+    reader.set_type_name(name);
+    let value = match variant {
+        0 => json!({}),
+        1 => json!({
+            "authority_address": optional_pubkey(reader, "authority_address")?,
+            "data_len": reader.data().len().saturating_sub(BUFFER_METADATA_LEN),
+        }),
+        2 => json!({
+            "programdata_address": reader.pubkey("programdata_address")?.to_string(),
+        }),
+        _ => json!({
+            "slot": reader.u64("slot")?,
+            "upgrade_authority_address": optional_pubkey(reader, "upgrade_authority_address")?,
+            "data_len": reader.data().len().saturating_sub(PROGRAMDATA_METADATA_LEN),
+        }),
+    };
+    Ok((name.to_string(), value))
+}
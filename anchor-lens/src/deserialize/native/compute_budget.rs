@@ -0,0 +1,166 @@
+//! The Compute Budget program, whose instructions are a borsh enum setting
+//! a transaction's compute unit limit, priority fee, heap and loaded data size.
+
+use crate::deserialize::native::{NativeInstruction, NativeReader};
+use crate::error::{IdlItemKind, LensError};
+use serde::Serialize;
+use serde_json::json;
+use solana_program::message::VersionedMessage;
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("ComputeBudget111111111111111111111111111111");
+
+/// The compute unit limit of each instruction, when a transaction doesn't set one.
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// A Compute Budget instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeBudgetInstruction {
+    /// Sets the compute unit limit, and an additional fee in lamports.
+    /// Replaced by `SetComputeUnitLimit` and `SetComputeUnitPrice`.
+    RequestUnitsDeprecated { units: u32, additional_fee: u32 },
+    RequestHeapFrame { bytes: u32 },
+    SetComputeUnitLimit { units: u32 },
+    SetComputeUnitPrice { micro_lamports: u64 },
+    SetLoadedAccountsDataSizeLimit { bytes: u32 },
+}
+
+impl ComputeBudgetInstruction {
+    pub fn read(reader: &mut NativeReader) -> Result<Self, LensError> {
+        let variant = reader.u8("")?;
+        reader.set_type_name(match variant {
+            0 => "request_units_deprecated",
+            1 => "request_heap_frame",
+            2 => "set_compute_unit_limit",
+            3 => "set_compute_unit_price",
+            4 => "set_loaded_accounts_data_size_limit",
+            _ => return Err(reader.unknown(IdlItemKind::Instruction, &[variant])),
+        });
+        Ok(match variant {
+            0 => Self::RequestUnitsDeprecated {
+                units: reader.u32("units")?,
+                additional_fee: reader.u32("additional_fee")?,
+            },
+            1 => Self::RequestHeapFrame {
+                bytes: reader.u32("bytes")?,
+            },
+            2 => Self::SetComputeUnitLimit {
+                units: reader.u32("units")?,
+            },
+            3 => Self::SetComputeUnitPrice {
+                micro_lamports: reader.u64("micro_lamports")?,
+            },
+            _ => Self::SetLoadedAccountsDataSizeLimit {
+                bytes: reader.u32("bytes")?,
+            },
+        })
+    }
+}
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    use ComputeBudgetInstruction::*;
+    let (name, data) = match ComputeBudgetInstruction::read(reader)? {
+        RequestUnitsDeprecated {
+            units,
+            additional_fee,
+        } => (
+            "request_units_deprecated",
+            json!({ "units": units, "additional_fee": additional_fee }),
+        ),
+        RequestHeapFrame { bytes } => ("request_heap_frame", json!({ "bytes": bytes })),
+        SetComputeUnitLimit { units } => ("set_compute_unit_limit", json!({ "units": units })),
+        SetComputeUnitPrice { micro_lamports } => (
+            "set_compute_unit_price",
+            json!({ "micro_lamports": micro_lamports }),
+        ),
+        SetLoadedAccountsDataSizeLimit { bytes } => (
+            "set_loaded_accounts_data_size_limit",
+            json!({ "bytes": bytes }),
+        ),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts: vec![],
+    })
+}
+
+/// What a transaction asked of the Compute Budget program, and what it paid for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ComputeBudgetSummary {
+    /// The limit requested with `set_compute_unit_limit` or `request_units_deprecated`, if any.
+    pub compute_unit_limit: Option<u32>,
+    /// The limit the runtime applies: the requested one, or a default per instruction.
+    pub effective_compute_unit_limit: u32,
+    /// The price set with `set_compute_unit_price`, in micro-lamports per compute unit.
+    pub compute_unit_price: Option<u64>,
+    pub heap_frame_bytes: Option<u32>,
+    pub loaded_accounts_data_size_limit: Option<u32>,
+    /// The priority fee in lamports: the price times the effective limit, rounded up,
+    /// or the `additional_fee` of `request_units_deprecated`.
+    pub priority_fee: u64,
+    /// The compute units the transaction actually used, when known.
+    pub compute_units_consumed: Option<u64>,
+}
+
+impl ComputeBudgetSummary {
+    /// Summarizes the Compute Budget instructions of a message. Instructions that
+    /// don't decode are skipped, the runtime would have rejected the transaction.
+    pub fn from_message(message: &VersionedMessage, compute_units_consumed: Option<u64>) -> Self {
+        let mut summary = ComputeBudgetSummary {
+            compute_units_consumed,
+            ..Default::default()
+        };
+        let mut other_instructions = 0u32;
+        // `request_units_deprecated` pays its fee in lamports, rather than per unit.
+        let mut deprecated_fee = None;
+        // Programs can't be loaded from lookup tables, so they're all static keys.
+        for ix in message.instructions() {
+            let program_id = message
+                .static_account_keys()
+                .get(ix.program_id_index as usize);
+            if program_id != Some(&ID) {
+                other_instructions += 1;
+                continue;
+            }
+            let mut reader = NativeReader::new(ID, &ix.data);
+            match ComputeBudgetInstruction::read(&mut reader) {
+                Ok(ComputeBudgetInstruction::RequestUnitsDeprecated {
+                    units,
+                    additional_fee,
+                }) => {
+                    summary.compute_unit_limit = Some(units);
+                    deprecated_fee = Some(additional_fee as u64);
+                }
+                Ok(ComputeBudgetInstruction::RequestHeapFrame { bytes }) => {
+                    summary.heap_frame_bytes = Some(bytes)
+                }
+                Ok(ComputeBudgetInstruction::SetComputeUnitLimit { units }) => {
+                    summary.compute_unit_limit = Some(units)
+                }
+                Ok(ComputeBudgetInstruction::SetComputeUnitPrice { micro_lamports }) => {
+                    summary.compute_unit_price = Some(micro_lamports)
+                }
+                Ok(ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit { bytes }) => {
+                    summary.loaded_accounts_data_size_limit = Some(bytes)
+                }
+                Err(_) => {}
+            }
+        }
+        summary.effective_compute_unit_limit = summary
+            .compute_unit_limit
+            .unwrap_or_else(|| {
+                other_instructions.saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+            })
+            .min(MAX_COMPUTE_UNIT_LIMIT);
+        let price = summary.compute_unit_price.unwrap_or(0) as u128;
+        let fee =
+            (price * summary.effective_compute_unit_limit as u128 + MICRO_LAMPORTS_PER_LAMPORT - 1)
+                / MICRO_LAMPORTS_PER_LAMPORT;
+        summary.priority_fee = deprecated_fee.unwrap_or(fee as u64);
+        summary
+    }
+}
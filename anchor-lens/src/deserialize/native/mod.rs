@@ -0,0 +1,435 @@
+//! Hand-written decoders for the native and SPL programs most transactions invoke,
+//! which don't publish an IDL. Instructions are decoded into the same shape as those
+//! decoded with an IDL: a name, the decoded arguments, and named accounts.
+
+pub mod address_lookup_table;
+pub mod associated_token;
+pub mod bpf_loader_upgradeable;
+pub mod compute_budget;
+pub mod stake;
+pub mod system;
+pub mod token;
+pub mod token_extensions;
+pub mod vote;
+
+use crate::deserialize::context::{DecodeContext, DecodeWarning, Strictness};
+use crate::error::{DataLocation, IdlItemKind, LensError};
+use anchor_syn::idl::{IdlAccount, IdlAccountItem};
+use serde_json::{json, Value};
+use solana_program::hash::Hash;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+
+/// An instruction of a native or SPL program, decoded by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeInstruction {
+    pub name: String,
+    /// The decoded arguments, as a JSON object.
+    pub data: Value,
+    /// The accounts the instruction takes, in order.
+    pub accounts: Vec<NativeAccount>,
+}
+
+/// An account a native instruction takes, as an IDL would describe it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeAccount {
+    pub name: String,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+impl NativeInstruction {
+    /// The accounts, as IDL accounts, to be checked against the transaction's account metas.
+    pub fn idl_accounts(&self) -> Vec<IdlAccountItem> {
+        self.accounts
+            .iter()
+            .map(|account| {
+                IdlAccountItem::IdlAccount(IdlAccount {
+                    name: account.name.clone(),
+                    is_mut: account.writable,
+                    is_signer: account.signer,
+                    docs: None,
+                    pda: None,
+                    relations: vec![],
+                })
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn readonly(name: &str) -> NativeAccount {
+    NativeAccount {
+        name: name.to_string(),
+        writable: false,
+        signer: false,
+    }
+}
+
+pub(crate) fn writable(name: &str) -> NativeAccount {
+    NativeAccount {
+        writable: true,
+        ..readonly(name)
+    }
+}
+
+pub(crate) fn signer(name: &str) -> NativeAccount {
+    NativeAccount {
+        signer: true,
+        ..readonly(name)
+    }
+}
+
+pub(crate) fn writable_signer(name: &str) -> NativeAccount {
+    NativeAccount {
+        writable: true,
+        signer: true,
+        ..readonly(name)
+    }
+}
+
+/// An `Option<Pubkey>` as bincode and borsh encode them, as a string or `null`.
+pub(crate) fn optional_pubkey(reader: &mut NativeReader, field: &str) -> Result<Value, LensError> {
+    Ok(match reader.option(field, NativeReader::pubkey)? {
+        Some(pubkey) => json!(pubkey.to_string()),
+        None => Value::Null,
+    })
+}
+
+/// The name of a program that has a native decoder, used in place of an IDL name.
+pub fn native_program_name(program_id: &Pubkey) -> Option<&'static str> {
+    if *program_id == system_program::id() {
+        Some("system_program")
+    } else if *program_id == token::TOKEN_PROGRAM_ID {
+        Some("spl_token")
+    } else if *program_id == token::TOKEN_2022_PROGRAM_ID {
+        Some("spl_token_2022")
+    } else if *program_id == associated_token::ID {
+        Some("spl_associated_token_account")
+    } else if *program_id == compute_budget::ID {
+        Some("compute_budget")
+    } else if *program_id == bpf_loader_upgradeable::ID {
+        Some("bpf_loader_upgradeable")
+    } else if *program_id == stake::ID {
+        Some("stake_program")
+    } else if *program_id == vote::ID {
+        Some("vote_program")
+    } else if *program_id == address_lookup_table::ID {
+        Some("address_lookup_table")
+    } else {
+        None
+    }
+}
+
+/// Decodes an instruction of a native or SPL program. `num_accounts` is the number
+/// of accounts the instruction was passed, which tells multisig signers apart.
+/// Returns `None` if the program has no native decoder.
+///
+/// Fails with [LensError::UnknownDiscriminator] if the instruction isn't known,
+/// and an error with a [LensError::location] if its data can't be decoded.
+pub fn deser_native_ix(
+    program_id: &Pubkey,
+    ix_data: &[u8],
+    num_accounts: usize,
+) -> Option<anyhow::Result<NativeInstruction>> {
+    let decoded = deser_native_ix_with_strictness(
+        program_id,
+        ix_data,
+        num_accounts,
+        Strictness::Lenient,
+    )?;
+    Some(decoded.map(|(native_ix, _)| native_ix))
+}
+
+/// Like [deser_native_ix], but checks for bytes left over after the instruction
+/// according to `strictness`, returning any warnings it raised.
+pub fn deser_native_ix_with_strictness(
+    program_id: &Pubkey,
+    ix_data: &[u8],
+    num_accounts: usize,
+    strictness: Strictness,
+) -> Option<anyhow::Result<(NativeInstruction, Vec<DecodeWarning>)>> {
+    native_program_name(program_id)?;
+    let mut reader = NativeReader::new(*program_id, ix_data);
+    let decoded = if *program_id == system_program::id() {
+        system::decode_instruction(&mut reader)
+    } else if *program_id == associated_token::ID {
+        associated_token::decode_instruction(&mut reader)
+    } else if *program_id == compute_budget::ID {
+        compute_budget::decode_instruction(&mut reader)
+    } else if *program_id == bpf_loader_upgradeable::ID {
+        bpf_loader_upgradeable::decode_instruction(&mut reader)
+    } else if *program_id == stake::ID {
+        stake::decode_instruction(&mut reader)
+    } else if *program_id == vote::ID {
+        vote::decode_instruction(&mut reader)
+    } else if *program_id == address_lookup_table::ID {
+        address_lookup_table::decode_instruction(&mut reader)
+    } else {
+        token::decode_instruction(&mut reader, num_accounts)
+    };
+    Some(decoded.map_err(Into::into).and_then(|native_ix| {
+        let mut context = DecodeContext::new(
+            strictness,
+            Some(*program_id),
+            ix_data,
+            reader.type_name(),
+        );
+        context.finish(&ix_data[reader.offset().min(ix_data.len())..])?;
+        Ok((native_ix, context.warnings))
+    }))
+}
+
+/// Decodes an account owned by a native or SPL program, returning its type name
+/// and decoded data. Returns `None` if the program has no native account decoder.
+pub fn deser_native_account(
+    owner: &Pubkey,
+    data: &[u8],
+) -> Option<anyhow::Result<(String, Value)>> {
+    let mut reader = NativeReader::new(*owner, data);
+    let decoded = if *owner == token::TOKEN_PROGRAM_ID || *owner == token::TOKEN_2022_PROGRAM_ID {
+        token::decode_account(&mut reader)
+    } else if *owner == bpf_loader_upgradeable::ID {
+        bpf_loader_upgradeable::decode_account(&mut reader)
+    } else if *owner == stake::ID {
+        stake::decode_account(&mut reader)
+    } else if *owner == address_lookup_table::ID {
+        address_lookup_table::decode_account(&mut reader)
+    } else {
+        return None;
+    };
+    Some(decoded.map_err(Into::into))
+}
+
+/// Reads little-endian values from native instruction or account data,
+/// raising errors located the way IDL decoding errors are.
+pub struct NativeReader<'a> {
+    program_id: Pubkey,
+    data: &'a [u8],
+    offset: usize,
+    /// The name of the instruction or account being decoded, once known.
+    type_name: String,
+}
+
+impl<'a> NativeReader<'a> {
+    pub fn new(program_id: Pubkey, data: &'a [u8]) -> Self {
+        Self {
+            program_id,
+            data,
+            offset: 0,
+            type_name: String::new(),
+        }
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Names the instruction or account being decoded, for error locations.
+    pub fn set_type_name(&mut self, type_name: &str) {
+        self.type_name = type_name.to_string();
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Fails with [LensError::UnknownDiscriminator], reporting `discriminator`.
+    pub fn unknown(&self, kind: IdlItemKind, discriminator: &[u8]) -> LensError {
+        LensError::UnknownDiscriminator {
+            program_id: Some(self.program_id),
+            kind,
+            discriminator: discriminator.to_vec(),
+        }
+    }
+
+    /// Where the reader is, having just read `field`.
+    pub fn location(&self, field: &str) -> DataLocation {
+        DataLocation {
+            program_id: Some(self.program_id),
+            type_name: self.type_name.clone(),
+            path: if field.is_empty() {
+                self.type_name.clone()
+            } else {
+                format!("{}.{}", self.type_name, field)
+            },
+            offset: self.offset,
+        }
+    }
+
+    pub fn invalid(&self, field: &str, reason: String) -> LensError {
+        LensError::InvalidData {
+            reason,
+            location: self.location(field),
+        }
+    }
+
+    pub fn bytes(&mut self, field: &str, len: usize) -> Result<&'a [u8], LensError> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or_else(|| LensError::BufferUnderflow {
+                location: self.location(field),
+            })?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    /// A reader over the next `len` bytes, whose offsets still count from the start
+    /// of the whole data. Reading past them underflows, rather than running into
+    /// whatever follows.
+    pub fn take(&mut self, field: &str, len: usize) -> Result<NativeReader<'a>, LensError> {
+        let start = self.offset;
+        self.bytes(field, len)?;
+        Ok(NativeReader {
+            program_id: self.program_id,
+            data: &self.data[..start + len],
+            offset: start,
+            type_name: self.type_name.clone(),
+        })
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The bytes not read yet.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.offset.min(self.data.len())..];
+        self.offset = self.data.len();
+        rest
+    }
+
+    /// Whether every byte has been read.
+    pub fn is_exhausted(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    pub fn u8(&mut self, field: &str) -> Result<u8, LensError> {
+        Ok(self.bytes(field, 1)?[0])
+    }
+
+    pub fn u16(&mut self, field: &str) -> Result<u16, LensError> {
+        Ok(u16::from_le_bytes(
+            self.bytes(field, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn i16(&mut self, field: &str) -> Result<i16, LensError> {
+        Ok(i16::from_le_bytes(
+            self.bytes(field, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn u32(&mut self, field: &str) -> Result<u32, LensError> {
+        Ok(u32::from_le_bytes(
+            self.bytes(field, 4)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn u64(&mut self, field: &str) -> Result<u64, LensError> {
+        Ok(u64::from_le_bytes(
+            self.bytes(field, 8)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn i64(&mut self, field: &str) -> Result<i64, LensError> {
+        Ok(i64::from_le_bytes(
+            self.bytes(field, 8)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn f64(&mut self, field: &str) -> Result<f64, LensError> {
+        Ok(f64::from_le_bytes(
+            self.bytes(field, 8)?.try_into().unwrap(),
+        ))
+    }
+
+    /// A `u16` packed into one to three bytes, seven bits at a time,
+    /// as transactions encode the lengths of their arrays.
+    pub fn compact_u16(&mut self, field: &str) -> Result<u16, LensError> {
+        let mut value = 0u32;
+        for i in 0..3 {
+            let byte = self.u8(field)?;
+            value |= ((byte & 0x7f) as u32) << (i * 7);
+            if byte & 0x80 == 0 {
+                return u16::try_from(value)
+                    .map_err(|_| self.invalid(field, format!("{} overflows a u16", value)));
+            }
+        }
+        Err(self.invalid(field, "Compact u16 is longer than 3 bytes".to_string()))
+    }
+
+    /// A `u64` packed into as many bytes as it needs, seven bits at a time.
+    pub fn varint_u64(&mut self, field: &str) -> Result<u64, LensError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8(field)?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(self.invalid(field, "Varint overflows a u64".to_string()))
+    }
+
+    pub fn bool(&mut self, field: &str) -> Result<bool, LensError> {
+        match self.u8(field)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(self.invalid(field, format!("{} is not a bool", byte))),
+        }
+    }
+
+    pub fn pubkey(&mut self, field: &str) -> Result<Pubkey, LensError> {
+        Ok(Pubkey::new_from_array(
+            self.bytes(field, 32)?.try_into().unwrap(),
+        ))
+    }
+
+    pub fn hash(&mut self, field: &str) -> Result<Hash, LensError> {
+        Ok(Hash::new_from_array(
+            self.bytes(field, 32)?.try_into().unwrap(),
+        ))
+    }
+
+    /// An `Option` as bincode and borsh encode them: a `u8` tag, then the value if set.
+    pub fn option<T>(
+        &mut self,
+        field: &str,
+        read: impl FnOnce(&mut Self, &str) -> Result<T, LensError>,
+    ) -> Result<Option<T>, LensError> {
+        match self.u8(field)? {
+            0 => Ok(None),
+            1 => read(self, field).map(Some),
+            tag => Err(self.invalid(field, format!("{} is not an option tag", tag))),
+        }
+    }
+
+    /// A length as bincode encodes them, a `u64`.
+    pub fn bincode_len(&mut self, field: &str) -> Result<usize, LensError> {
+        let len = self.u64(field)?;
+        Ok(usize::try_from(len).unwrap_or(usize::MAX))
+    }
+
+    /// A string prefixed with its length as a `u32`, as borsh encodes them.
+    pub fn borsh_string(&mut self, field: &str) -> Result<String, LensError> {
+        let len = self.u32(field)?;
+        self.utf8(field, len as usize)
+    }
+
+    /// A string prefixed with its length as a `u64`, as bincode encodes them.
+    pub fn bincode_string(&mut self, field: &str) -> Result<String, LensError> {
+        let len = self.bincode_len(field)?;
+        self.utf8(field, len)
+    }
+
+    fn utf8(&mut self, field: &str, len: usize) -> Result<String, LensError> {
+        let bytes = self.bytes(field, len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| self.invalid(field, "String is not valid UTF-8".to_string()))
+    }
+}
@@ -0,0 +1,274 @@
+//! The Stake program. Its instructions and account states are bincode-encoded
+//! with a `u32` variant index.
+
+use crate::deserialize::native::{
+    optional_pubkey, readonly, signer, writable, NativeInstruction, NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::{json, Value};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("Stake11111111111111111111111111111111111111");
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "initialize",
+        1 => "authorize",
+        2 => "delegate_stake",
+        3 => "split",
+        4 => "withdraw",
+        5 => "deactivate",
+        6 => "set_lockup",
+        7 => "merge",
+        8 => "authorize_with_seed",
+        9 => "initialize_checked",
+        10 => "authorize_checked",
+        11 => "authorize_checked_with_seed",
+        12 => "set_lockup_checked",
+        13 => "get_minimum_delegation",
+        14 => "deactivate_delinquent",
+        15 => "redelegate",
+        16 => "move_stake",
+        17 => "move_lamports",
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Instruction, &variant.to_le_bytes()));
+        }
+    };
+    reader.set_type_name(name);
+    // Trailing lockup custodians are only passed when the lockup is in force.
+    let (data, accounts) = match variant {
+        0 => (
+            json!({
+                "authorized": authorized(reader)?,
+                "lockup": lockup(reader)?,
+            }),
+            vec![writable("stake"), readonly("rent_sysvar")],
+        ),
+        1 => (
+            json!({
+                "new_authorized": reader.pubkey("new_authorized")?.to_string(),
+                "stake_authorize": stake_authorize(reader)?,
+            }),
+            vec![
+                writable("stake"),
+                readonly("clock_sysvar"),
+                signer("authority"),
+                signer("lockup_custodian"),
+            ],
+        ),
+        2 => (
+            json!({}),
+            vec![
+                writable("stake"),
+                readonly("vote"),
+                readonly("clock_sysvar"),
+                readonly("stake_history_sysvar"),
+                readonly("stake_config"),
+                signer("stake_authority"),
+            ],
+        ),
+        3 => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![
+                writable("stake"),
+                writable("split_stake"),
+                signer("stake_authority"),
+            ],
+        ),
+        4 => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![
+                writable("stake"),
+                writable("recipient"),
+                readonly("clock_sysvar"),
+                readonly("stake_history_sysvar"),
+                signer("withdraw_authority"),
+                signer("lockup_custodian"),
+            ],
+        ),
+        5 => (
+            json!({}),
+            vec![
+                writable("stake"),
+                readonly("clock_sysvar"),
+                signer("stake_authority"),
+            ],
+        ),
+        6 => (
+            json!({
+                "unix_timestamp": reader.option("unix_timestamp", NativeReader::i64)?,
+                "epoch": reader.option("epoch", NativeReader::u64)?,
+                "custodian": optional_pubkey(reader, "custodian")?,
+            }),
+            vec![writable("stake"), signer("authority")],
+        ),
+        7 => (
+            json!({}),
+            vec![
+                writable("destination_stake"),
+                writable("source_stake"),
+                readonly("clock_sysvar"),
+                readonly("stake_history_sysvar"),
+                signer("stake_authority"),
+            ],
+        ),
+        8 => (
+            json!({
+                "new_authorized": reader.pubkey("new_authorized")?.to_string(),
+                "stake_authorize": stake_authorize(reader)?,
+                "authority_seed": reader.bincode_string("authority_seed")?,
+                "authority_owner": reader.pubkey("authority_owner")?.to_string(),
+            }),
+            vec![
+                writable("stake"),
+                signer("authority_base"),
+                readonly("clock_sysvar"),
+                signer("lockup_custodian"),
+            ],
+        ),
+        9 => (
+            json!({}),
+            vec![
+                writable("stake"),
+                readonly("rent_sysvar"),
+                readonly("stake_authority"),
+                signer("withdraw_authority"),
+            ],
+        ),
+        10 => (
+            json!({ "stake_authorize": stake_authorize(reader)? }),
+            vec![
+                writable("stake"),
+                readonly("clock_sysvar"),
+                signer("authority"),
+                signer("new_authority"),
+                signer("lockup_custodian"),
+            ],
+        ),
+        11 => (
+            json!({
+                "stake_authorize": stake_authorize(reader)?,
+                "authority_seed": reader.bincode_string("authority_seed")?,
+                "authority_owner": reader.pubkey("authority_owner")?.to_string(),
+            }),
+            vec![
+                writable("stake"),
+                signer("authority_base"),
+                readonly("clock_sysvar"),
+                signer("new_authority"),
+                signer("lockup_custodian"),
+            ],
+        ),
+        12 => (
+            json!({
+                "unix_timestamp": reader.option("unix_timestamp", NativeReader::i64)?,
+                "epoch": reader.option("epoch", NativeReader::u64)?,
+            }),
+            vec![
+                writable("stake"),
+                signer("authority"),
+                signer("new_lockup_authority"),
+            ],
+        ),
+        13 => (json!({}), vec![]),
+        14 => (
+            json!({}),
+            vec![
+                writable("stake"),
+                readonly("delinquent_vote"),
+                readonly("reference_vote"),
+            ],
+        ),
+        15 => (
+            json!({}),
+            vec![
+                writable("stake"),
+                writable("uninitialized_stake"),
+                readonly("vote"),
+                readonly("stake_config"),
+                signer("stake_authority"),
+            ],
+        ),
+        _ => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![
+                writable("source_stake"),
+                writable("destination_stake"),
+                signer("stake_authority"),
+            ],
+        ),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
+
+/// Decodes a stake account's `StakeStateV2`.
+pub fn decode_account(reader: &mut NativeReader) -> Result<(String, Value), LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "Uninitialized",
+        1 => "Initialized",
+        2 => "Stake",
+        3 => "RewardsPool",
+        _ => return Err(reader.unknown(IdlItemKind::Account, &variant.to_le_bytes())),
+    };
+    reader.set_type_name(name);
+    let value = match variant {
+        1 => json!({ "meta": meta(reader)? }),
+        2 => json!({
+            "meta": meta(reader)?,
+            "stake": {
+                "delegation": {
+                    "voter_pubkey": reader.pubkey("stake.delegation.voter_pubkey")?.to_string(),
+                    "stake": reader.u64("stake.delegation.stake")?,
+                    "activation_epoch": reader.u64("stake.delegation.activation_epoch")?,
+                    "deactivation_epoch": reader.u64("stake.delegation.deactivation_epoch")?,
+                    "warmup_cooldown_rate": reader.f64("stake.delegation.warmup_cooldown_rate")?,
+                },
+                "credits_observed": reader.u64("stake.credits_observed")?,
+            },
+            "stake_flags": reader.u8("stake_flags")?,
+        }),
+        _ => json!({}),
+    };
+    Ok((name.to_string(), value))
+}
+
+fn meta(reader: &mut NativeReader) -> Result<Value, LensError> {
+    Ok(json!({
+        "rent_exempt_reserve": reader.u64("meta.rent_exempt_reserve")?,
+        "authorized": authorized(reader)?,
+        "lockup": lockup(reader)?,
+    }))
+}
+
+fn authorized(reader: &mut NativeReader) -> Result<Value, LensError> {
+    Ok(json!({
+        "staker": reader.pubkey("authorized.staker")?.to_string(),
+        "withdrawer": reader.pubkey("authorized.withdrawer")?.to_string(),
+    }))
+}
+
+fn lockup(reader: &mut NativeReader) -> Result<Value, LensError> {
+    Ok(json!({
+        "unix_timestamp": reader.i64("lockup.unix_timestamp")?,
+        "epoch": reader.u64("lockup.epoch")?,
+        "custodian": reader.pubkey("lockup.custodian")?.to_string(),
+    }))
+}
+
+fn stake_authorize(reader: &mut NativeReader) -> Result<&'static str, LensError> {
+    match reader.u32("stake_authorize")? {
+        0 => Ok("staker"),
+        1 => Ok("withdrawer"),
+        other => Err(reader.invalid(
+            "stake_authorize",
+            format!("Unknown stake authority type {}", other),
+        )),
+    }
+}
@@ -0,0 +1,132 @@
+//! The System program, whose instructions are bincode-encoded
+//! with a `u32` variant index.
+
+use crate::deserialize::native::{
+    readonly, signer, writable, writable_signer, NativeInstruction, NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::json;
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "create_account",
+        1 => "assign",
+        2 => "transfer",
+        3 => "create_account_with_seed",
+        4 => "advance_nonce_account",
+        5 => "withdraw_nonce_account",
+        6 => "initialize_nonce_account",
+        7 => "authorize_nonce_account",
+        8 => "allocate",
+        9 => "allocate_with_seed",
+        10 => "assign_with_seed",
+        11 => "transfer_with_seed",
+        12 => "upgrade_nonce_account",
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Instruction, &variant.to_le_bytes()));
+        }
+    };
+    reader.set_type_name(name);
+    let (data, accounts) = match variant {
+        0 => (
+            json!({
+                "lamports": reader.u64("lamports")?,
+                "space": reader.u64("space")?,
+                "owner": reader.pubkey("owner")?.to_string(),
+            }),
+            vec![
+                writable_signer("funding_account"),
+                writable_signer("new_account"),
+            ],
+        ),
+        1 => (
+            json!({ "owner": reader.pubkey("owner")?.to_string() }),
+            vec![writable_signer("account")],
+        ),
+        2 => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![writable_signer("from"), writable("to")],
+        ),
+        3 => (
+            json!({
+                "base": reader.pubkey("base")?.to_string(),
+                "seed": reader.bincode_string("seed")?,
+                "lamports": reader.u64("lamports")?,
+                "space": reader.u64("space")?,
+                "owner": reader.pubkey("owner")?.to_string(),
+            }),
+            // The base only signs if it isn't the funding account.
+            vec![
+                writable_signer("funding_account"),
+                writable("new_account"),
+                signer("base"),
+            ],
+        ),
+        4 => (
+            json!({}),
+            vec![
+                writable("nonce_account"),
+                readonly("recent_blockhashes_sysvar"),
+                signer("nonce_authority"),
+            ],
+        ),
+        5 => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![
+                writable("nonce_account"),
+                writable("to"),
+                readonly("recent_blockhashes_sysvar"),
+                readonly("rent_sysvar"),
+                signer("nonce_authority"),
+            ],
+        ),
+        6 => (
+            json!({ "nonce_authority": reader.pubkey("nonce_authority")?.to_string() }),
+            vec![
+                writable("nonce_account"),
+                readonly("recent_blockhashes_sysvar"),
+                readonly("rent_sysvar"),
+            ],
+        ),
+        7 => (
+            json!({ "new_nonce_authority": reader.pubkey("new_nonce_authority")?.to_string() }),
+            vec![writable("nonce_account"), signer("nonce_authority")],
+        ),
+        8 => (
+            json!({ "space": reader.u64("space")? }),
+            vec![writable_signer("account")],
+        ),
+        9 => (
+            json!({
+                "base": reader.pubkey("base")?.to_string(),
+                "seed": reader.bincode_string("seed")?,
+                "space": reader.u64("space")?,
+                "owner": reader.pubkey("owner")?.to_string(),
+            }),
+            vec![writable("account"), signer("base")],
+        ),
+        10 => (
+            json!({
+                "base": reader.pubkey("base")?.to_string(),
+                "seed": reader.bincode_string("seed")?,
+                "owner": reader.pubkey("owner")?.to_string(),
+            }),
+            vec![writable("account"), signer("base")],
+        ),
+        11 => (
+            json!({
+                "lamports": reader.u64("lamports")?,
+                "from_seed": reader.bincode_string("from_seed")?,
+                "from_owner": reader.pubkey("from_owner")?.to_string(),
+            }),
+            vec![writable("from"), signer("from_base"), writable("to")],
+        ),
+        _ => (json!({}), vec![writable("nonce_account")]),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
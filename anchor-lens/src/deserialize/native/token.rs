@@ -0,0 +1,644 @@
+//! The SPL Token program, and Token-2022, which extends its instruction set.
+//! Instructions start with a `u8` tag, and extension instructions with a second
+//! `u8` selecting the extension's instruction.
+
+use crate::deserialize::native::token_extensions;
+use crate::deserialize::native::{
+    optional_pubkey, readonly, signer, writable, writable_signer, NativeAccount, NativeInstruction,
+    NativeReader,
+};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::{json, Value};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Token-2022 instructions that are followed by an extension instruction tag.
+const EXTENSION_TAGS: [u8; 11] = [26, 27, 28, 30, 33, 34, 36, 37, 39, 40, 41];
+
+fn instruction_name(tag: u8, extension_tag: Option<u8>) -> Option<&'static str> {
+    Some(match (tag, extension_tag) {
+        (0, _) => "initialize_mint",
+        (1, _) => "initialize_account",
+        (2, _) => "initialize_multisig",
+        (3, _) => "transfer",
+        (4, _) => "approve",
+        (5, _) => "revoke",
+        (6, _) => "set_authority",
+        (7, _) => "mint_to",
+        (8, _) => "burn",
+        (9, _) => "close_account",
+        (10, _) => "freeze_account",
+        (11, _) => "thaw_account",
+        (12, _) => "transfer_checked",
+        (13, _) => "approve_checked",
+        (14, _) => "mint_to_checked",
+        (15, _) => "burn_checked",
+        (16, _) => "initialize_account2",
+        (17, _) => "sync_native",
+        (18, _) => "initialize_account3",
+        (19, _) => "initialize_multisig2",
+        (20, _) => "initialize_mint2",
+        (21, _) => "get_account_data_size",
+        (22, _) => "initialize_immutable_owner",
+        (23, _) => "amount_to_ui_amount",
+        (24, _) => "ui_amount_to_amount",
+        (25, _) => "initialize_mint_close_authority",
+        (26, Some(0)) => "initialize_transfer_fee_config",
+        (26, Some(1)) => "transfer_checked_with_fee",
+        (26, Some(2)) => "withdraw_withheld_tokens_from_mint",
+        (26, Some(3)) => "withdraw_withheld_tokens_from_accounts",
+        (26, Some(4)) => "harvest_withheld_tokens_to_mint",
+        (26, Some(5)) => "set_transfer_fee",
+        (27, _) => "confidential_transfer_extension",
+        (28, Some(0)) => "initialize_default_account_state",
+        (28, Some(1)) => "update_default_account_state",
+        (29, _) => "reallocate",
+        (30, Some(0)) => "enable_required_transfer_memos",
+        (30, Some(1)) => "disable_required_transfer_memos",
+        (31, _) => "create_native_mint",
+        (32, _) => "initialize_non_transferable_mint",
+        (33, Some(0)) => "initialize_interest_bearing_mint",
+        (33, Some(1)) => "update_interest_rate",
+        (34, Some(0)) => "enable_cpi_guard",
+        (34, Some(1)) => "disable_cpi_guard",
+        (35, _) => "initialize_permanent_delegate",
+        (36, Some(0)) => "initialize_transfer_hook",
+        (36, Some(1)) => "update_transfer_hook",
+        (37, _) => "confidential_transfer_fee_extension",
+        (38, _) => "withdraw_excess_lamports",
+        (39, Some(0)) => "initialize_metadata_pointer",
+        (39, Some(1)) => "update_metadata_pointer",
+        (40, Some(0)) => "initialize_group_pointer",
+        (40, Some(1)) => "update_group_pointer",
+        (41, Some(0)) => "initialize_group_member_pointer",
+        (41, Some(1)) => "update_group_member_pointer",
+        _ => return None,
+    })
+}
+
+/// The name of an `AuthorityType`, as passed to `set_authority`.
+pub fn authority_type_name(authority_type: u8) -> Option<&'static str> {
+    Some(match authority_type {
+        0 => "mint_tokens",
+        1 => "freeze_account",
+        2 => "account_owner",
+        3 => "close_account",
+        4 => "transfer_fee_config",
+        5 => "withheld_withdraw",
+        6 => "close_mint",
+        7 => "interest_rate",
+        8 => "permanent_delegate",
+        9 => "confidential_transfer_mint",
+        10 => "transfer_hook_program_id",
+        11 => "confidential_transfer_fee_config",
+        12 => "metadata_pointer",
+        13 => "group_pointer",
+        14 => "group_member_pointer",
+        _ => return None,
+    })
+}
+
+/// The name of a Token-2022 `ExtensionType`.
+pub fn extension_type_name(extension_type: u16) -> Option<&'static str> {
+    Some(match extension_type {
+        0 => "uninitialized",
+        1 => "transfer_fee_config",
+        2 => "transfer_fee_amount",
+        3 => "mint_close_authority",
+        4 => "confidential_transfer_mint",
+        5 => "confidential_transfer_account",
+        6 => "default_account_state",
+        7 => "immutable_owner",
+        8 => "memo_transfer",
+        9 => "non_transferable",
+        10 => "interest_bearing_config",
+        11 => "cpi_guard",
+        12 => "permanent_delegate",
+        13 => "non_transferable_account",
+        14 => "transfer_hook",
+        15 => "transfer_hook_account",
+        16 => "confidential_transfer_fee_config",
+        17 => "confidential_transfer_fee_amount",
+        18 => "metadata_pointer",
+        19 => "token_metadata",
+        20 => "group_pointer",
+        21 => "token_group",
+        22 => "group_member_pointer",
+        23 => "token_group_member",
+        _ => return None,
+    })
+}
+
+/// The name of a token account's `AccountState`.
+pub fn account_state_name(state: u8) -> Option<&'static str> {
+    Some(match state {
+        0 => "uninitialized",
+        1 => "initialized",
+        2 => "frozen",
+        _ => return None,
+    })
+}
+
+pub fn decode_instruction(
+    reader: &mut NativeReader,
+    num_accounts: usize,
+) -> Result<NativeInstruction, LensError> {
+    let tag = reader.u8("")?;
+    let is_2022 = *reader.program_id() == TOKEN_2022_PROGRAM_ID;
+    let extension_tag = if is_2022 && EXTENSION_TAGS.contains(&tag) {
+        Some(reader.u8("")?)
+    } else {
+        None
+    };
+    let discriminator = [Some(tag), extension_tag]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let name = match instruction_name(tag, extension_tag) {
+        Some(_) if tag >= 25 && !is_2022 => None,
+        name => name,
+    }
+    .ok_or_else(|| reader.unknown(IdlItemKind::Instruction, &discriminator))?;
+    reader.set_type_name(name);
+
+    let (data, accounts) = match (tag, extension_tag) {
+        (0, _) | (20, _) => {
+            let data = json!({
+                "decimals": reader.u8("decimals")?,
+                "mint_authority": reader.pubkey("mint_authority")?.to_string(),
+                "freeze_authority": optional_pubkey(reader, "freeze_authority")?,
+            });
+            match tag {
+                0 => (data, vec![writable("mint"), readonly("rent_sysvar")]),
+                _ => (data, vec![writable("mint")]),
+            }
+        }
+        (1, _) => (
+            json!({}),
+            vec![
+                writable("account"),
+                readonly("mint"),
+                readonly("owner"),
+                readonly("rent_sysvar"),
+            ],
+        ),
+        (2, _) | (19, _) => {
+            let data = json!({ "m": reader.u8("m")? });
+            let mut accounts = vec![writable("multisig")];
+            if tag == 2 {
+                accounts.push(readonly("rent_sysvar"));
+            }
+            let signers = num_accounts.saturating_sub(accounts.len());
+            accounts.extend((0..signers).map(|i| readonly(&format!("signer_{}", i))));
+            (data, accounts)
+        }
+        (3, _) => (
+            json!({ "amount": reader.u64("amount")? }),
+            with_authority(
+                vec![writable("source"), writable("destination")],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (4, _) => (
+            json!({ "amount": reader.u64("amount")? }),
+            with_authority(
+                vec![writable("source"), readonly("delegate")],
+                "owner",
+                num_accounts,
+            ),
+        ),
+        (5, _) => (
+            json!({}),
+            with_authority(vec![writable("source")], "owner", num_accounts),
+        ),
+        (6, _) => {
+            let authority_type = reader.u8("authority_type")?;
+            let authority_type = authority_type_name(authority_type).ok_or_else(|| {
+                reader.invalid(
+                    "authority_type",
+                    format!("Unknown authority type {}", authority_type),
+                )
+            })?;
+            (
+                json!({
+                    "authority_type": authority_type,
+                    "new_authority": optional_pubkey(reader, "new_authority")?,
+                }),
+                with_authority(vec![writable("account")], "current_authority", num_accounts),
+            )
+        }
+        (7, _) | (14, _) => (
+            amount_and_decimals(reader, tag == 14)?,
+            with_authority(
+                vec![writable("mint"), writable("account")],
+                "mint_authority",
+                num_accounts,
+            ),
+        ),
+        (8, _) | (15, _) => (
+            amount_and_decimals(reader, tag == 15)?,
+            with_authority(
+                vec![writable("account"), writable("mint")],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (9, _) => (
+            json!({}),
+            with_authority(
+                vec![writable("account"), writable("destination")],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (10, _) | (11, _) => (
+            json!({}),
+            with_authority(
+                vec![writable("account"), readonly("mint")],
+                "freeze_authority",
+                num_accounts,
+            ),
+        ),
+        (12, _) => (
+            amount_and_decimals(reader, true)?,
+            with_authority(
+                vec![
+                    writable("source"),
+                    readonly("mint"),
+                    writable("destination"),
+                ],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (13, _) => (
+            amount_and_decimals(reader, true)?,
+            with_authority(
+                vec![writable("source"), readonly("mint"), readonly("delegate")],
+                "owner",
+                num_accounts,
+            ),
+        ),
+        (16, _) => (
+            json!({ "owner": reader.pubkey("owner")?.to_string() }),
+            vec![
+                writable("account"),
+                readonly("mint"),
+                readonly("rent_sysvar"),
+            ],
+        ),
+        (17, _) | (22, _) => (json!({}), vec![writable("account")]),
+        (18, _) => (
+            json!({ "owner": reader.pubkey("owner")?.to_string() }),
+            vec![writable("account"), readonly("mint")],
+        ),
+        (21, _) => (
+            json!({ "extension_types": extension_types(reader)? }),
+            vec![readonly("mint")],
+        ),
+        (23, _) => (
+            json!({ "amount": reader.u64("amount")? }),
+            vec![readonly("mint")],
+        ),
+        (24, _) => {
+            let ui_amount = String::from_utf8(reader.rest().to_vec()).map_err(|_| {
+                reader.invalid("ui_amount", "String is not valid UTF-8".to_string())
+            })?;
+            (json!({ "ui_amount": ui_amount }), vec![readonly("mint")])
+        }
+        (25, _) => (
+            json!({ "close_authority": optional_pubkey(reader, "close_authority")? }),
+            vec![writable("mint")],
+        ),
+        (26, Some(0)) => (
+            json!({
+                "transfer_fee_config_authority":
+                    optional_pubkey(reader, "transfer_fee_config_authority")?,
+                "withdraw_withheld_authority":
+                    optional_pubkey(reader, "withdraw_withheld_authority")?,
+                "transfer_fee_basis_points": reader.u16("transfer_fee_basis_points")?,
+                "maximum_fee": reader.u64("maximum_fee")?,
+            }),
+            vec![writable("mint")],
+        ),
+        (26, Some(1)) => (
+            json!({
+                "amount": reader.u64("amount")?,
+                "decimals": reader.u8("decimals")?,
+                "fee": reader.u64("fee")?,
+            }),
+            with_authority(
+                vec![
+                    writable("source"),
+                    readonly("mint"),
+                    writable("destination"),
+                ],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (26, Some(2)) => (
+            json!({}),
+            with_authority(
+                vec![writable("mint"), writable("destination")],
+                "withdraw_withheld_authority",
+                num_accounts,
+            ),
+        ),
+        (26, Some(3)) => {
+            let num_token_accounts = reader.u8("num_token_accounts")?;
+            let sources = num_token_accounts as usize;
+            let mut accounts = with_authority(
+                vec![readonly("mint"), writable("destination")],
+                "withdraw_withheld_authority",
+                num_accounts.saturating_sub(sources),
+            );
+            accounts.extend((0..sources).map(|i| writable(&format!("source_{}", i))));
+            (
+                json!({ "num_token_accounts": num_token_accounts }),
+                accounts,
+            )
+        }
+        (26, Some(4)) => {
+            let sources = num_accounts.saturating_sub(1);
+            let mut accounts = vec![writable("mint")];
+            accounts.extend((0..sources).map(|i| writable(&format!("source_{}", i))));
+            (json!({}), accounts)
+        }
+        (26, Some(5)) => (
+            json!({
+                "transfer_fee_basis_points": reader.u16("transfer_fee_basis_points")?,
+                "maximum_fee": reader.u64("maximum_fee")?,
+            }),
+            with_authority(
+                vec![writable("mint")],
+                "transfer_fee_config_authority",
+                num_accounts,
+            ),
+        ),
+        (28, Some(0)) => (
+            json!({ "state": account_state(reader)? }),
+            vec![writable("mint")],
+        ),
+        (28, Some(1)) => (
+            json!({ "state": account_state(reader)? }),
+            with_authority(vec![writable("mint")], "freeze_authority", num_accounts),
+        ),
+        (29, _) => (
+            json!({ "extension_types": extension_types(reader)? }),
+            with_authority(
+                vec![
+                    writable("account"),
+                    writable_signer("payer"),
+                    readonly("system_program"),
+                ],
+                "owner",
+                num_accounts,
+            ),
+        ),
+        (30, _) | (34, _) => (
+            json!({}),
+            with_authority(vec![writable("account")], "owner", num_accounts),
+        ),
+        (31, _) => (
+            json!({}),
+            vec![
+                writable_signer("payer"),
+                writable("native_mint"),
+                readonly("system_program"),
+            ],
+        ),
+        (32, _) => (json!({}), vec![writable("mint")]),
+        (33, Some(0)) => (
+            json!({
+                "rate_authority": optional_nonzero_pubkey(reader, "rate_authority")?,
+                "rate": reader.i16("rate")?,
+            }),
+            vec![writable("mint")],
+        ),
+        (33, Some(1)) => (
+            json!({ "rate": reader.i16("rate")? }),
+            with_authority(vec![writable("mint")], "rate_authority", num_accounts),
+        ),
+        (35, _) => (
+            json!({ "delegate": reader.pubkey("delegate")?.to_string() }),
+            vec![writable("mint")],
+        ),
+        (36, Some(0)) => (
+            json!({
+                "authority": optional_nonzero_pubkey(reader, "authority")?,
+                "program_id": optional_nonzero_pubkey(reader, "program_id")?,
+            }),
+            vec![writable("mint")],
+        ),
+        (36, Some(1)) => (
+            json!({ "program_id": optional_nonzero_pubkey(reader, "program_id")? }),
+            with_authority(vec![writable("mint")], "authority", num_accounts),
+        ),
+        (38, _) => (
+            json!({}),
+            with_authority(
+                vec![writable("source"), writable("destination")],
+                "authority",
+                num_accounts,
+            ),
+        ),
+        (39..=41, Some(0)) => {
+            let field = pointer_field(tag);
+            let mut data = json!({
+                "authority": optional_nonzero_pubkey(reader, "authority")?,
+            });
+            data[field] = optional_nonzero_pubkey(reader, field)?;
+            (data, vec![writable("mint")])
+        }
+        (39..=41, Some(1)) => {
+            let field = pointer_field(tag);
+            let mut data = json!({});
+            data[field] = optional_nonzero_pubkey(reader, field)?;
+            (
+                data,
+                with_authority(vec![writable("mint")], "authority", num_accounts),
+            )
+        }
+        // Confidential transfers carry zero-knowledge proofs, which are left encoded.
+        _ => (
+            json!({
+                "instruction": extension_tag,
+                "data": base64::encode(reader.rest()),
+            }),
+            vec![],
+        ),
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
+
+/// The size of a mint, before Token-2022 pads it to the size of a token account.
+pub const MINT_LEN: usize = 82;
+/// The size of a token account, which is where Token-2022 extensions start.
+pub const ACCOUNT_LEN: usize = 165;
+pub const MULTISIG_LEN: usize = 355;
+
+/// Decodes a mint, token account or multisig. Token-2022 mints and accounts
+/// are followed by an account type and their extensions, see [token_extensions].
+pub fn decode_account(reader: &mut NativeReader) -> Result<(String, Value), LensError> {
+    let is_2022 = *reader.program_id() == TOKEN_2022_PROGRAM_ID;
+    let len = reader.data().len();
+    // Token-2022 tells mints and accounts apart by a byte after the account layout.
+    let account_type = if is_2022 && len > ACCOUNT_LEN && len != MULTISIG_LEN {
+        reader.data()[ACCOUNT_LEN]
+    } else {
+        0
+    };
+    let (type_name, mut value) = match (len, account_type) {
+        (MINT_LEN, _) | (_, 1) => {
+            reader.set_type_name("Mint");
+            let value = json!({
+                "mint_authority": account_coption_pubkey(reader, "mint_authority")?,
+                "supply": reader.u64("supply")?,
+                "decimals": reader.u8("decimals")?,
+                "is_initialized": reader.bool("is_initialized")?,
+                "freeze_authority": account_coption_pubkey(reader, "freeze_authority")?,
+            });
+            ("Mint", value)
+        }
+        (ACCOUNT_LEN, _) | (_, 2) => {
+            reader.set_type_name("Account");
+            let value = json!({
+                "mint": reader.pubkey("mint")?.to_string(),
+                "owner": reader.pubkey("owner")?.to_string(),
+                "amount": reader.u64("amount")?,
+                "delegate": account_coption_pubkey(reader, "delegate")?,
+                "state": account_state(reader)?,
+                // The rent-exempt reserve of a wrapped SOL account.
+                "is_native": account_coption_u64(reader, "is_native")?,
+                "delegated_amount": reader.u64("delegated_amount")?,
+                "close_authority": account_coption_pubkey(reader, "close_authority")?,
+            });
+            ("Account", value)
+        }
+        (MULTISIG_LEN, _) => {
+            reader.set_type_name("Multisig");
+            let m = reader.u8("m")?;
+            let n = reader.u8("n")?;
+            let is_initialized = reader.bool("is_initialized")?;
+            let mut signers = vec![];
+            for i in 0..11 {
+                let signer = reader.pubkey(&format!("signers[{}]", i))?;
+                if i < n {
+                    signers.push(signer.to_string());
+                }
+            }
+            let value = json!({
+                "m": m,
+                "n": n,
+                "is_initialized": is_initialized,
+                "signers": signers,
+            });
+            ("Multisig", value)
+        }
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Account, &[account_type]));
+        }
+    };
+    if account_type != 0 {
+        let (extensions, unknown) = token_extensions::decode_extensions(reader)?;
+        value["extensions"] = extensions;
+        if !unknown.is_empty() {
+            value["unknown_extensions"] = Value::Array(unknown);
+        }
+    }
+    Ok((type_name.to_string(), value))
+}
+
+/// A `COption<Pubkey>` as stored in accounts: a `u32` tag, then the key, set or not.
+fn account_coption_pubkey(reader: &mut NativeReader, field: &str) -> Result<Value, LensError> {
+    let tag = reader.u32(field)?;
+    let pubkey = reader.pubkey(field)?;
+    match tag {
+        0 => Ok(Value::Null),
+        1 => Ok(json!(pubkey.to_string())),
+        tag => Err(reader.invalid(field, format!("{} is not an option tag", tag))),
+    }
+}
+
+fn account_coption_u64(reader: &mut NativeReader, field: &str) -> Result<Value, LensError> {
+    let tag = reader.u32(field)?;
+    let value = reader.u64(field)?;
+    match tag {
+        0 => Ok(Value::Null),
+        1 => Ok(json!(value)),
+        tag => Err(reader.invalid(field, format!("{} is not an option tag", tag))),
+    }
+}
+
+/// Appends an instruction's authority, which either signs itself, or is
+/// a multisig whose signers are passed after it.
+fn with_authority(
+    mut accounts: Vec<NativeAccount>,
+    authority: &str,
+    num_accounts: usize,
+) -> Vec<NativeAccount> {
+    let signers = num_accounts.saturating_sub(accounts.len() + 1);
+    if signers == 0 {
+        accounts.push(signer(authority));
+    } else {
+        accounts.push(readonly(authority));
+        accounts.extend((0..signers).map(|i| signer(&format!("signer_{}", i))));
+    }
+    accounts
+}
+
+fn amount_and_decimals(reader: &mut NativeReader, checked: bool) -> Result<Value, LensError> {
+    let amount = reader.u64("amount")?;
+    Ok(if checked {
+        json!({ "amount": amount, "decimals": reader.u8("decimals")? })
+    } else {
+        json!({ "amount": amount })
+    })
+}
+
+/// An `OptionalNonZeroPubkey`, where the zero key stands for `None`.
+pub fn optional_nonzero_pubkey(reader: &mut NativeReader, field: &str) -> Result<Value, LensError> {
+    let pubkey = reader.pubkey(field)?;
+    Ok(if pubkey == Pubkey::default() {
+        Value::Null
+    } else {
+        json!(pubkey.to_string())
+    })
+}
+
+pub(crate) fn account_state(reader: &mut NativeReader) -> Result<&'static str, LensError> {
+    let state = reader.u8("state")?;
+    account_state_name(state)
+        .ok_or_else(|| reader.invalid("state", format!("Unknown account state {}", state)))
+}
+
+/// The extension types listed after an instruction, named if they're known.
+fn extension_types(reader: &mut NativeReader) -> Result<Vec<Value>, LensError> {
+    let mut extension_types = vec![];
+    while !reader.is_exhausted() {
+        let extension_type = reader.u16("extension_types")?;
+        extension_types.push(match extension_type_name(extension_type) {
+            Some(name) => json!(name),
+            None => json!(extension_type),
+        });
+    }
+    Ok(extension_types)
+}
+
+/// The address a pointer extension points to.
+fn pointer_field(tag: u8) -> &'static str {
+    match tag {
+        39 => "metadata_address",
+        40 => "group_address",
+        _ => "member_address",
+    }
+}
@@ -0,0 +1,192 @@
+//! Token-2022 extensions, stored after a mint or token account as type-length-value
+//! entries: a `u16` extension type, a `u16` length, then the extension itself.
+
+use crate::deserialize::native::token::{
+    account_state, extension_type_name, optional_nonzero_pubkey, ACCOUNT_LEN,
+};
+use crate::deserialize::native::NativeReader;
+use crate::error::LensError;
+use serde_json::{json, Map, Value};
+
+/// Decodes the extensions following a Token-2022 mint or account, which `reader`
+/// has just read. Returns the known extensions, keyed by name, and the unknown
+/// ones with their raw bytes, along with known ones that don't decode.
+pub fn decode_extensions(reader: &mut NativeReader) -> Result<(Value, Vec<Value>), LensError> {
+    // Mints are padded to the size of an account, and followed by the account type.
+    reader.bytes("account_type", ACCOUNT_LEN + 1 - reader.offset())?;
+    let mut extensions = Map::new();
+    let mut unknown = vec![];
+    while reader.data().len() - reader.offset() >= 4 {
+        let extension_type = reader.u16("extension_type")?;
+        let len = reader.u16("extension_length")?;
+        // Accounts can be allocated with room for extensions that aren't initialized yet.
+        if extension_type == 0 {
+            break;
+        }
+        let mut value = reader.take("extension", len as usize)?;
+        let data = &value.data()[value.offset()..];
+        let name = match extension_type_name(extension_type) {
+            Some(name) => name,
+            None => {
+                unknown.push(json!({
+                    "extension_type": extension_type,
+                    "data": base64::encode(data),
+                }));
+                continue;
+            }
+        };
+        // Extensions can grow fields in newer versions of the program, in which case
+        // they're left undecoded rather than failing the whole account.
+        value.set_type_name(&format!("{}.extensions.{}", reader.type_name(), name));
+        match decode_extension(extension_type, &mut value) {
+            Ok(decoded) if value.is_exhausted() => {
+                extensions.insert(name.to_string(), decoded);
+            }
+            result => unknown.push(json!({
+                "extension_type": extension_type,
+                "name": name,
+                "data": base64::encode(data),
+                "error": match result {
+                    Ok(_) => format!("{} bytes left over", value.data().len() - value.offset()),
+                    Err(e) => e.to_string(),
+                },
+            })),
+        }
+    }
+    Ok((Value::Object(extensions), unknown))
+}
+
+fn decode_extension(extension_type: u16, reader: &mut NativeReader) -> Result<Value, LensError> {
+    Ok(match extension_type {
+        1 => json!({
+            "transfer_fee_config_authority":
+                optional_nonzero_pubkey(reader, "transfer_fee_config_authority")?,
+            "withdraw_withheld_authority":
+                optional_nonzero_pubkey(reader, "withdraw_withheld_authority")?,
+            "withheld_amount": reader.u64("withheld_amount")?,
+            "older_transfer_fee": transfer_fee(reader, "older_transfer_fee")?,
+            "newer_transfer_fee": transfer_fee(reader, "newer_transfer_fee")?,
+        }),
+        2 => json!({ "withheld_amount": reader.u64("withheld_amount")? }),
+        3 => json!({ "close_authority": optional_nonzero_pubkey(reader, "close_authority")? }),
+        4 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "auto_approve_new_accounts": reader.bool("auto_approve_new_accounts")?,
+            "auditor_elgamal_pubkey": optional_ciphertext(reader, "auditor_elgamal_pubkey", 32)?,
+        }),
+        5 => json!({
+            "approved": reader.bool("approved")?,
+            "elgamal_pubkey": ciphertext(reader, "elgamal_pubkey", 32)?,
+            "pending_balance_lo": ciphertext(reader, "pending_balance_lo", 64)?,
+            "pending_balance_hi": ciphertext(reader, "pending_balance_hi", 64)?,
+            "available_balance": ciphertext(reader, "available_balance", 64)?,
+            "decryptable_available_balance":
+                ciphertext(reader, "decryptable_available_balance", 36)?,
+            "allow_confidential_credits": reader.bool("allow_confidential_credits")?,
+            "allow_non_confidential_credits": reader.bool("allow_non_confidential_credits")?,
+            "pending_balance_credit_counter": reader.u64("pending_balance_credit_counter")?,
+            "maximum_pending_balance_credit_counter":
+                reader.u64("maximum_pending_balance_credit_counter")?,
+            "expected_pending_balance_credit_counter":
+                reader.u64("expected_pending_balance_credit_counter")?,
+            "actual_pending_balance_credit_counter":
+                reader.u64("actual_pending_balance_credit_counter")?,
+        }),
+        6 => json!({ "state": account_state(reader)? }),
+        8 => json!({
+            "require_incoming_transfer_memos": reader.bool("require_incoming_transfer_memos")?,
+        }),
+        10 => json!({
+            "rate_authority": optional_nonzero_pubkey(reader, "rate_authority")?,
+            "initialization_timestamp": reader.i64("initialization_timestamp")?,
+            "pre_update_average_rate": reader.i16("pre_update_average_rate")?,
+            "last_update_timestamp": reader.i64("last_update_timestamp")?,
+            "current_rate": reader.i16("current_rate")?,
+        }),
+        11 => json!({ "lock_cpi": reader.bool("lock_cpi")? }),
+        12 => json!({ "delegate": optional_nonzero_pubkey(reader, "delegate")? }),
+        14 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "program_id": optional_nonzero_pubkey(reader, "program_id")?,
+        }),
+        15 => json!({ "transferring": reader.bool("transferring")? }),
+        16 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "withdraw_withheld_authority_elgamal_pubkey":
+                ciphertext(reader, "withdraw_withheld_authority_elgamal_pubkey", 32)?,
+            "harvest_to_mint_enabled": reader.bool("harvest_to_mint_enabled")?,
+            "withheld_amount": ciphertext(reader, "withheld_amount", 64)?,
+        }),
+        17 => json!({ "withheld_amount": ciphertext(reader, "withheld_amount", 64)? }),
+        18 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "metadata_address": optional_nonzero_pubkey(reader, "metadata_address")?,
+        }),
+        19 => {
+            let mut value = json!({
+                "update_authority": optional_nonzero_pubkey(reader, "update_authority")?,
+                "mint": reader.pubkey("mint")?.to_string(),
+                "name": reader.borsh_string("name")?,
+                "symbol": reader.borsh_string("symbol")?,
+                "uri": reader.borsh_string("uri")?,
+            });
+            let mut additional_metadata = vec![];
+            for _ in 0..reader.u32("additional_metadata")? {
+                additional_metadata.push(json!([
+                    reader.borsh_string("additional_metadata")?,
+                    reader.borsh_string("additional_metadata")?,
+                ]));
+            }
+            value["additional_metadata"] = Value::Array(additional_metadata);
+            value
+        }
+        20 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "group_address": optional_nonzero_pubkey(reader, "group_address")?,
+        }),
+        21 => json!({
+            "update_authority": optional_nonzero_pubkey(reader, "update_authority")?,
+            "mint": reader.pubkey("mint")?.to_string(),
+            "size": reader.u64("size")?,
+            "max_size": reader.u64("max_size")?,
+        }),
+        22 => json!({
+            "authority": optional_nonzero_pubkey(reader, "authority")?,
+            "member_address": optional_nonzero_pubkey(reader, "member_address")?,
+        }),
+        23 => json!({
+            "mint": reader.pubkey("mint")?.to_string(),
+            "group": reader.pubkey("group")?.to_string(),
+            "member_number": reader.u64("member_number")?,
+        }),
+        // Extensions that are only a marker: immutable owner, non-transferable mints and accounts.
+        _ => json!({}),
+    })
+}
+
+fn transfer_fee(reader: &mut NativeReader, field: &str) -> Result<Value, LensError> {
+    Ok(json!({
+        "epoch": reader.u64(field)?,
+        "maximum_fee": reader.u64(field)?,
+        "transfer_fee_basis_points": reader.u16(field)?,
+    }))
+}
+
+/// Encryption keys and ciphertexts, which are left encoded.
+fn ciphertext(reader: &mut NativeReader, field: &str, len: usize) -> Result<Value, LensError> {
+    Ok(json!(base64::encode(reader.bytes(field, len)?)))
+}
+
+/// Like [ciphertext], where all zeroes stands for `None`.
+fn optional_ciphertext(
+    reader: &mut NativeReader,
+    field: &str,
+    len: usize,
+) -> Result<Value, LensError> {
+    let bytes = reader.bytes(field, len)?;
+    Ok(if bytes.iter().all(|byte| *byte == 0) {
+        Value::Null
+    } else {
+        json!(base64::encode(bytes))
+    })
+}
@@ -0,0 +1,224 @@
+//! The Vote program. Its instructions are bincode-encoded with a `u32` variant
+//! index, though the compact vote state updates pack their lockouts tighter.
+
+use crate::deserialize::native::{readonly, signer, writable, NativeInstruction, NativeReader};
+use crate::error::{IdlItemKind, LensError};
+use serde_json::{json, Value};
+use solana_program::pubkey;
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("Vote111111111111111111111111111111111111111");
+
+pub fn decode_instruction(reader: &mut NativeReader) -> Result<NativeInstruction, LensError> {
+    let variant = reader.u32("")?;
+    let name = match variant {
+        0 => "initialize_account",
+        1 => "authorize",
+        2 => "vote",
+        3 => "withdraw",
+        4 => "update_validator_identity",
+        5 => "update_commission",
+        6 => "vote_switch",
+        7 => "authorize_checked",
+        8 => "update_vote_state",
+        9 => "update_vote_state_switch",
+        10 => "authorize_with_seed",
+        11 => "authorize_checked_with_seed",
+        12 => "compact_update_vote_state",
+        13 => "compact_update_vote_state_switch",
+        14 => "tower_sync",
+        15 => "tower_sync_switch",
+        _ => {
+            return Err(reader.unknown(IdlItemKind::Instruction, &variant.to_le_bytes()));
+        }
+    };
+    reader.set_type_name(name);
+    let (data, accounts) = match variant {
+        0 => (
+            json!({
+                "node_pubkey": reader.pubkey("node_pubkey")?.to_string(),
+                "authorized_voter": reader.pubkey("authorized_voter")?.to_string(),
+                "authorized_withdrawer": reader.pubkey("authorized_withdrawer")?.to_string(),
+                "commission": reader.u8("commission")?,
+            }),
+            vec![
+                writable("vote"),
+                readonly("rent_sysvar"),
+                readonly("clock_sysvar"),
+                signer("node"),
+            ],
+        ),
+        1 => (
+            json!({
+                "new_authority": reader.pubkey("new_authority")?.to_string(),
+                "vote_authorize": vote_authorize(reader)?,
+            }),
+            vec![
+                writable("vote"),
+                readonly("clock_sysvar"),
+                signer("authority"),
+            ],
+        ),
+        2 | 6 => {
+            let mut data = vote(reader)?;
+            if variant == 6 {
+                data["switch_proof_hash"] = json!(reader.hash("switch_proof_hash")?.to_string());
+            }
+            (
+                data,
+                vec![
+                    writable("vote"),
+                    readonly("slot_hashes_sysvar"),
+                    readonly("clock_sysvar"),
+                    signer("vote_authority"),
+                ],
+            )
+        }
+        3 => (
+            json!({ "lamports": reader.u64("lamports")? }),
+            vec![
+                writable("vote"),
+                writable("recipient"),
+                signer("withdraw_authority"),
+            ],
+        ),
+        4 => (
+            json!({}),
+            vec![
+                writable("vote"),
+                signer("node"),
+                signer("withdraw_authority"),
+            ],
+        ),
+        5 => (
+            json!({ "commission": reader.u8("commission")? }),
+            vec![writable("vote"), signer("withdraw_authority")],
+        ),
+        7 => (
+            json!({ "vote_authorize": vote_authorize(reader)? }),
+            vec![
+                writable("vote"),
+                readonly("clock_sysvar"),
+                signer("authority"),
+                signer("new_authority"),
+            ],
+        ),
+        10 => (
+            json!({
+                "authorization_type": vote_authorize(reader)?,
+                "current_authority_derived_key_owner":
+                    reader.pubkey("current_authority_derived_key_owner")?.to_string(),
+                "current_authority_derived_key_seed":
+                    reader.bincode_string("current_authority_derived_key_seed")?,
+                "new_authority": reader.pubkey("new_authority")?.to_string(),
+            }),
+            vec![
+                writable("vote"),
+                readonly("clock_sysvar"),
+                signer("authority_base"),
+            ],
+        ),
+        11 => (
+            json!({
+                "authorization_type": vote_authorize(reader)?,
+                "current_authority_derived_key_owner":
+                    reader.pubkey("current_authority_derived_key_owner")?.to_string(),
+                "current_authority_derived_key_seed":
+                    reader.bincode_string("current_authority_derived_key_seed")?,
+            }),
+            vec![
+                writable("vote"),
+                readonly("clock_sysvar"),
+                signer("authority_base"),
+                signer("new_authority"),
+            ],
+        ),
+        _ => {
+            let mut data = match variant {
+                8 | 9 => vote_state_update(reader)?,
+                _ => compact_vote_state_update(reader, variant >= 14)?,
+            };
+            if variant % 2 == 1 {
+                data["switch_proof_hash"] = json!(reader.hash("switch_proof_hash")?.to_string());
+            }
+            (data, vec![writable("vote"), signer("vote_authority")])
+        }
+    };
+    Ok(NativeInstruction {
+        name: name.to_string(),
+        data,
+        accounts,
+    })
+}
+
+fn vote(reader: &mut NativeReader) -> Result<Value, LensError> {
+    let mut slots = vec![];
+    for _ in 0..reader.bincode_len("slots")? {
+        slots.push(reader.u64("slots")?);
+    }
+    Ok(json!({
+        "slots": slots,
+        "hash": reader.hash("hash")?.to_string(),
+        "timestamp": reader.option("timestamp", NativeReader::i64)?,
+    }))
+}
+
+fn vote_state_update(reader: &mut NativeReader) -> Result<Value, LensError> {
+    let mut lockouts = vec![];
+    for _ in 0..reader.bincode_len("lockouts")? {
+        lockouts.push(json!({
+            "slot": reader.u64("lockouts.slot")?,
+            "confirmation_count": reader.u32("lockouts.confirmation_count")?,
+        }));
+    }
+    Ok(json!({
+        "lockouts": lockouts,
+        "root": reader.option("root", NativeReader::u64)?,
+        "hash": reader.hash("hash")?.to_string(),
+        "timestamp": reader.option("timestamp", NativeReader::i64)?,
+    }))
+}
+
+/// A vote state update whose lockouts are stored as offsets from the previous slot,
+/// starting from the root, where `u64::MAX` stands for no root. Tower syncs also
+/// carry the id of the voted-on block.
+fn compact_vote_state_update(
+    reader: &mut NativeReader,
+    tower_sync: bool,
+) -> Result<Value, LensError> {
+    let root = reader.u64("root")?;
+    let root = (root != u64::MAX).then_some(root);
+    let mut slot = root.unwrap_or_default();
+    let mut lockouts = vec![];
+    for _ in 0..reader.compact_u16("lockout_offsets")? {
+        let offset = reader.varint_u64("lockout_offsets.offset")?;
+        slot = slot.checked_add(offset).ok_or_else(|| {
+            reader.invalid("lockout_offsets.offset", "Slot overflows a u64".to_string())
+        })?;
+        lockouts.push(json!({
+            "slot": slot,
+            "confirmation_count": reader.u8("lockout_offsets.confirmation_count")?,
+        }));
+    }
+    let mut value = json!({
+        "lockouts": lockouts,
+        "root": root,
+        "hash": reader.hash("hash")?.to_string(),
+        "timestamp": reader.option("timestamp", NativeReader::i64)?,
+    });
+    if tower_sync {
+        value["block_id"] = json!(reader.hash("block_id")?.to_string());
+    }
+    Ok(value)
+}
+
+fn vote_authorize(reader: &mut NativeReader) -> Result<&'static str, LensError> {
+    match reader.u32("vote_authorize")? {
+        0 => Ok("voter"),
+        1 => Ok("withdrawer"),
+        other => Err(reader.invalid(
+            "vote_authorize",
+            format!("Unknown vote authority type {}", other),
+        )),
+    }
+}
@@ -0,0 +1,181 @@
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use serde_json::{json, Value};
+use solana_program::instruction::InstructionError;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+use std::str::FromStr;
+
+/// Anchor's built-in `ErrorCode`s, as `(code, name, message)`.
+/// These are shared by every Anchor program, and sit below the
+/// `6000` offset where IDL-defined errors begin.
+pub const ANCHOR_ERRORS: &[(u32, &str, &str)] = &[
+    (100, "InstructionMissing", "Instruction discriminator not provided"),
+    (101, "InstructionFallbackNotFound", "Fallback functions are not supported"),
+    (102, "InstructionDidNotDeserialize", "The program could not deserialize the given instruction"),
+    (103, "InstructionDidNotSerialize", "The program could not serialize the given instruction"),
+    (1000, "IdlInstructionStub", "The program was compiled without idl instructions"),
+    (1001, "IdlInstructionInvalidProgram", "Invalid program given to the IDL instruction"),
+    (1002, "IdlAccountNotEmpty", "IDL account must be empty in order to resize, try closing first"),
+    (1500, "EventInstructionStub", "The program was compiled without `event-cpi` feature"),
+    (2000, "ConstraintMut", "A mut constraint was violated"),
+    (2001, "ConstraintHasOne", "A has one constraint was violated"),
+    (2002, "ConstraintSigner", "A signer constraint was violated"),
+    (2003, "ConstraintRaw", "A raw constraint was violated"),
+    (2004, "ConstraintOwner", "An owner constraint was violated"),
+    (2005, "ConstraintRentExempt", "A rent exemption constraint was violated"),
+    (2006, "ConstraintSeeds", "A seeds constraint was violated"),
+    (2007, "ConstraintExecutable", "An executable constraint was violated"),
+    (2008, "ConstraintState", "Deprecated Error, feel free to replace with something else"),
+    (2009, "ConstraintAssociated", "An associated constraint was violated"),
+    (2010, "ConstraintAssociatedInit", "An associated init constraint was violated"),
+    (2011, "ConstraintClose", "A close constraint was violated"),
+    (2012, "ConstraintAddress", "An address constraint was violated"),
+    (2013, "ConstraintZero", "Expected zero account discriminant"),
+    (2014, "ConstraintTokenMint", "A token mint constraint was violated"),
+    (2015, "ConstraintTokenOwner", "A token owner constraint was violated"),
+    (2016, "ConstraintMintMintAuthority", "A mint mint authority constraint was violated"),
+    (2017, "ConstraintMintFreezeAuthority", "A mint freeze authority constraint was violated"),
+    (2018, "ConstraintMintDecimals", "A mint decimals constraint was violated"),
+    (2019, "ConstraintSpace", "A space constraint was violated"),
+    (2020, "ConstraintAccountIsNone", "A required account for the constraint is None"),
+    (2021, "ConstraintTokenTokenProgram", "A token account token program constraint was violated"),
+    (2022, "ConstraintMintTokenProgram", "A mint token program constraint was violated"),
+    (2023, "ConstraintAssociatedTokenTokenProgram", "An associated token account token program constraint was violated"),
+    (2024, "ConstraintMintGroupPointerExtension", "A group pointer extension constraint was violated"),
+    (2025, "ConstraintMintGroupPointerExtensionAuthority", "A group pointer extension authority constraint was violated"),
+    (2026, "ConstraintMintGroupPointerExtensionGroupAddress", "A group pointer extension group address constraint was violated"),
+    (2027, "ConstraintMintGroupMemberPointerExtension", "A group member pointer extension constraint was violated"),
+    (2028, "ConstraintMintGroupMemberPointerExtensionAuthority", "A group member pointer extension authority constraint was violated"),
+    (2029, "ConstraintMintGroupMemberPointerExtensionMemberAddress", "A group member pointer extension group address constraint was violated"),
+    (2030, "ConstraintMintMetadataPointerExtension", "A metadata pointer extension constraint was violated"),
+    (2031, "ConstraintMintMetadataPointerExtensionAuthority", "A metadata pointer extension authority constraint was violated"),
+    (2032, "ConstraintMintMetadataPointerExtensionMetadataAddress", "A metadata pointer extension metadata address constraint was violated"),
+    (2033, "ConstraintMintCloseAuthorityExtension", "A close authority constraint was violated"),
+    (2034, "ConstraintMintCloseAuthorityExtensionAuthority", "A close authority extension authority constraint was violated"),
+    (2035, "ConstraintMintPermanentDelegateExtension", "A permanent delegate extension constraint was violated"),
+    (2036, "ConstraintMintPermanentDelegateExtensionDelegate", "A permanent delegate extension delegate constraint was violated"),
+    (2037, "ConstraintMintTransferHookExtension", "A transfer hook extension constraint was violated"),
+    (2038, "ConstraintMintTransferHookExtensionAuthority", "A transfer hook extension authority constraint was violated"),
+    (2039, "ConstraintMintTransferHookExtensionProgramId", "A transfer hook extension transfer hook program id constraint was violated"),
+    (2500, "RequireViolated", "A require expression was violated"),
+    (2501, "RequireEqViolated", "A require_eq expression was violated"),
+    (2502, "RequireKeysEqViolated", "A require_keys_eq expression was violated"),
+    (2503, "RequireNeqViolated", "A require_neq expression was violated"),
+    (2504, "RequireKeysNeqViolated", "A require_keys_neq expression was violated"),
+    (2505, "RequireGtViolated", "A require_gt expression was violated"),
+    (2506, "RequireGteViolated", "A require_gte expression was violated"),
+    (3000, "AccountDiscriminatorAlreadySet", "The account discriminator was already set on this account"),
+    (3001, "AccountDiscriminatorNotFound", "No discriminator was found on the account"),
+    (3002, "AccountDiscriminatorMismatch", "Account discriminator did not match what was expected"),
+    (3003, "AccountDidNotDeserialize", "Failed to deserialize the account"),
+    (3004, "AccountDidNotSerialize", "Failed to serialize the account"),
+    (3005, "AccountNotEnoughKeys", "Not enough account keys given to the instruction"),
+    (3006, "AccountNotMutable", "The given account is not mutable"),
+    (3007, "AccountOwnedByWrongProgram", "The given account is owned by a different program than expected"),
+    (3008, "InvalidProgramId", "Program ID was not as expected"),
+    (3009, "InvalidProgramExecutable", "Program account is not executable"),
+    (3010, "AccountNotSigner", "The given account did not sign"),
+    (3011, "AccountNotSystemOwned", "The given account is not owned by the system program"),
+    (3012, "AccountNotInitialized", "The program expected this account to be already initialized"),
+    (3013, "AccountNotProgramData", "The given account is not a program data account"),
+    (3014, "AccountNotAssociatedTokenAccount", "The given account is not the associated token account"),
+    (3015, "AccountSysvarMismatch", "The given public key does not match the required sysvar"),
+    (3016, "AccountReallocExceedsLimit", "The account reallocation exceeds the MAX_PERMITTED_DATA_INCREASE limit"),
+    (3017, "AccountDuplicateReallocs", "The account was duplicated for more than one reallocation"),
+    (4000, "StateInvalidAddress", "The given state account does not have the correct address"),
+    (4100, "DeclaredProgramIdMismatch", "The declared program id does not match the actual program id"),
+    (4101, "TryingToInitPayerAsProgramAccount", "You cannot/should not initialize the payer account as a program account"),
+    (4102, "InvalidNumericConversion", "Error during numeric conversion"),
+    (5000, "Deprecated", "The API being used is deprecated and should no longer be used"),
+];
+
+/// Where the name and message of a custom error code came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorSource {
+    /// The `errors` block of the failing program's IDL.
+    Idl,
+    /// Anchor's built-in framework errors.
+    Anchor,
+}
+
+impl ErrorSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ErrorSource::Idl => "idl",
+            ErrorSource::Anchor => "anchor",
+        }
+    }
+}
+
+/// Maps a custom program error code to its name and message. The failing program's
+/// IDL is consulted first, then Anchor's built-in error codes.
+///
+/// Anchor's built-in codes are only used for programs with an Anchor IDL. Other programs,
+/// like SPL and Metaplex programs, define their own errors in the same ranges, so their
+/// codes are only named when their IDL lists them.
+pub fn lookup_error_code(
+    idl: Option<&IdlWithDiscriminators>,
+    code: u32,
+) -> Option<(String, Option<String>, ErrorSource)> {
+    let idl = idl?;
+    let idl_error = idl
+        .errors
+        .as_ref()
+        .and_then(|errors| errors.iter().find(|error| error.code == code));
+    if let Some(error) = idl_error {
+        return Some((error.name.clone(), error.msg.clone(), ErrorSource::Idl));
+    }
+    if !idl.is_anchor() {
+        return None;
+    }
+    ANCHOR_ERRORS
+        .iter()
+        .find(|(anchor_code, _, _)| *anchor_code == code)
+        .map(|(_, name, msg)| (name.to_string(), Some(msg.to_string()), ErrorSource::Anchor))
+}
+
+/// The program that raised the error, found in the logs. A failing CPI makes
+/// every program up the invocation stack fail too, so the first `failed`
+/// line belongs to the innermost program, where the error originated.
+pub fn failed_program_from_logs(log_messages: &[String]) -> Option<Pubkey> {
+    log_messages.iter().find_map(|log| {
+        let mut words = log.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("Program"), Some(program_id), Some("failed:")) => {
+                Pubkey::from_str(program_id).ok()
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Describes a transaction error as JSON. Instruction errors report the
+/// failing instruction index and program, and custom error codes are
+/// resolved to their name and message where possible.
+///
+/// `program_id` is the program that raised the error, and `idl` its IDL, if found.
+pub fn transaction_error_json(
+    err: &TransactionError,
+    program_id: Option<&Pubkey>,
+    idl: Option<&IdlWithDiscriminators>,
+) -> Value {
+    let mut json = json!({
+        "raw": serde_json::to_value(err).unwrap_or(Value::Null),
+    });
+    let map = json.as_object_mut().unwrap();
+    if let TransactionError::InstructionError(instruction_index, ix_err) = err {
+        map.insert("instruction_index".to_string(), json!(instruction_index));
+        if let Some(program_id) = program_id {
+            map.insert("program_id".to_string(), json!(program_id.to_string()));
+        }
+        if let InstructionError::Custom(code) = ix_err {
+            map.insert("code".to_string(), json!(code));
+            if let Some((name, msg, source)) = lookup_error_code(idl, *code) {
+                map.insert("name".to_string(), json!(name));
+                map.insert("msg".to_string(), json!(msg));
+                map.insert("source".to_string(), json!(source.as_str()));
+            }
+        }
+    }
+    json
+}
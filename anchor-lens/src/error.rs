@@ -0,0 +1,230 @@
+//! Errors raised by the lens, so callers can tell what went wrong without
+//! matching on messages.
+//!
+//! [AnchorLens](crate::AnchorLens) and the `deserialize_*` functions return
+//! these directly, with failures from elsewhere, like the RPC client, wrapped
+//! in [LensError::Source]:
+//!
+//! ```rust,ignore
+//! match err {
+//!     LensError::IdlAccountNotFound { .. } => { /* not an Anchor program, skip */ }
+//!     LensError::UnknownDiscriminator { .. } => { /* the IDL may be outdated */ }
+//!     err if err.location().is_some() => { /* data doesn't match the IDL, alert */ }
+//!     _ => { /* RPC or other failure, retry */ }
+//! }
+//! ```
+//!
+//! Lower level functions return [anyhow::Result], and their [LensError]s
+//! are recovered with `err.downcast_ref::<LensError>()`.
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::fmt;
+
+/// Something the lens couldn't do, with enough context to decide what to do about it.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LensError {
+    /// The account doesn't exist.
+    AccountNotFound { pubkey: Pubkey },
+    /// The transaction source doesn't know of the transaction.
+    TransactionNotFound { signature: Signature },
+    /// Neither the given address nor the IDL address derived from it holds an account.
+    IdlAccountNotFound { program_id: Pubkey },
+    /// The IDL account exists, but its contents couldn't be read as an IDL.
+    InvalidIdl { program_id: Pubkey, reason: String },
+    /// The program's known IDL versions were all published after the slot.
+    IdlNotLive {
+        program_id: Pubkey,
+        slot: u64,
+        /// The slot the first known version was published in.
+        first_slot: u64,
+    },
+    /// The data doesn't start with the discriminator of any
+    /// account, instruction or event in the IDL.
+    UnknownDiscriminator {
+        program_id: Option<Pubkey>,
+        kind: IdlItemKind,
+        /// The first 8 bytes of the data, or all of it if it's shorter.
+        discriminator: Vec<u8>,
+    },
+    /// An account or type requested by name that the IDL doesn't have.
+    UnknownType {
+        program_id: Option<Pubkey>,
+        type_name: String,
+    },
+    /// A `defined` type that isn't in the IDL's `types` block.
+    TypeNotDefined {
+        defined: String,
+        location: DataLocation,
+    },
+    /// The data ended before the value being decoded did.
+    BufferUnderflow { location: DataLocation },
+    /// The bytes aren't valid for the type, e.g. a bool that isn't 0 or 1.
+    InvalidData {
+        reason: String,
+        location: DataLocation,
+    },
+    /// An enum variant index beyond the enum's variants.
+    InvalidEnumVariant {
+        enum_name: String,
+        variant_index: u8,
+        location: DataLocation,
+    },
+    /// Bytes left over after the whole value was decoded,
+    /// only an error under [crate::deserialize::Strictness::Strict].
+    TrailingBytes { len: usize, location: DataLocation },
+    /// A failure from outside the lens, e.g. the RPC client or account source.
+    Source(anyhow::Error),
+}
+
+/// What a discriminator was being matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdlItemKind {
+    Account,
+    Instruction,
+    Event,
+}
+
+/// Where in some account, instruction or event data decoding diverged from the IDL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataLocation {
+    /// The program the data belongs to, if known.
+    pub program_id: Option<Pubkey>,
+    /// The name of the account, instruction or event being decoded.
+    pub type_name: String,
+    /// The path to the value being decoded, e.g. `Pool.fees[2].numerator`.
+    pub path: String,
+    /// The offset into the data, counting from the start of the discriminator.
+    pub offset: usize,
+}
+
+impl LensError {
+    /// Where in the data the error was raised, for errors raised while decoding.
+    pub fn location(&self) -> Option<&DataLocation> {
+        match self {
+            LensError::TypeNotDefined { location, .. }
+            | LensError::BufferUnderflow { location }
+            | LensError::InvalidData { location, .. }
+            | LensError::InvalidEnumVariant { location, .. }
+            | LensError::TrailingBytes { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The program the error relates to, if known.
+    pub fn program_id(&self) -> Option<Pubkey> {
+        match self {
+            LensError::IdlAccountNotFound { program_id }
+            | LensError::InvalidIdl { program_id, .. }
+            | LensError::IdlNotLive { program_id, .. } => Some(*program_id),
+            LensError::UnknownDiscriminator { program_id, .. }
+            | LensError::UnknownType { program_id, .. } => *program_id,
+            err => err.location().and_then(|location| location.program_id),
+        }
+    }
+
+    /// Describes the error without its location.
+    pub(crate) fn reason(&self) -> String {
+        match self {
+            LensError::AccountNotFound { pubkey } => format!("Account {} not found", pubkey),
+            LensError::TransactionNotFound { signature } => {
+                format!("Transaction {} not found", signature)
+            }
+            LensError::IdlAccountNotFound { program_id } => {
+                format!("IDL account not found for {}", program_id)
+            }
+            LensError::InvalidIdl { program_id, reason } => {
+                format!("Invalid IDL for {}: {}", program_id, reason)
+            }
+            LensError::IdlNotLive {
+                program_id,
+                slot,
+                first_slot,
+            } => format!(
+                "No IDL was live for {} at slot {}, the first was published at slot {}",
+                program_id, slot, first_slot
+            ),
+            LensError::UnknownDiscriminator {
+                kind,
+                discriminator,
+                ..
+            } => format!(
+                "Could not match {} against any discriminator: {:?}",
+                kind, discriminator
+            ),
+            LensError::UnknownType { type_name, .. } => {
+                format!("The IDL has no account or type named {}", type_name)
+            }
+            LensError::TypeNotDefined { defined, .. } => {
+                format!("Couldn't find defined type: {}", defined)
+            }
+            LensError::BufferUnderflow { .. } => "Unexpected end of data".to_string(),
+            LensError::InvalidData { reason, .. } => format!("Invalid data: {}", reason),
+            LensError::InvalidEnumVariant {
+                enum_name,
+                variant_index,
+                ..
+            } => format!(
+                "Enum {} has no variant with index {}",
+                enum_name, variant_index
+            ),
+            LensError::TrailingBytes { len, .. } => format!("{} trailing bytes", len),
+            LensError::Source(err) => err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LensError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason())?;
+        if let Some(location) = self.location() {
+            write!(f, " at {} (byte {})", location.path, location.offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LensError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LensError::Source(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+/// Recovers a [LensError] raised further down, and wraps anything else as [LensError::Source].
+impl From<anyhow::Error> for LensError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<LensError>() {
+            Ok(err) => err,
+            Err(err) => LensError::Source(err),
+        }
+    }
+}
+
+impl fmt::Display for IdlItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdlItemKind::Account => write!(f, "account data"),
+            IdlItemKind::Instruction => write!(f, "instruction"),
+            IdlItemKind::Event => write!(f, "event"),
+        }
+    }
+}
+
+impl IdlItemKind {
+    /// Builds the [LensError::UnknownDiscriminator] for data that matched nothing.
+    pub(crate) fn unknown_discriminator(
+        self,
+        program_id: Option<Pubkey>,
+        data: &[u8],
+    ) -> LensError {
+        LensError::UnknownDiscriminator {
+            program_id,
+            kind: self,
+            discriminator: data[..data.len().min(8)].to_vec(),
+        }
+    }
+}
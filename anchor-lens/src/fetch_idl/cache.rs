@@ -0,0 +1,117 @@
+use crate::error::LensError;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::history::IdlHistory;
+use anyhow::Result;
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// A cache of fetched IDLs that can be shared between threads.
+///
+/// IDLs are stored behind an [Arc], so a cache hit is a reference count increment
+/// rather than a copy of the IDL and its discriminator maps. Programs that turned
+/// out to have no IDL are remembered too, so that every instruction invoking one
+/// doesn't go back to the RPC to find that out again.
+#[derive(Debug, Default)]
+pub struct IdlCache {
+    entries: RwLock<HashMap<Pubkey, CachedIdl>>,
+    histories: RwLock<HashMap<Pubkey, Arc<IdlHistory>>>,
+}
+
+/// What the cache knows about a program.
+#[derive(Debug, Clone)]
+pub enum CachedIdl {
+    Found(Arc<IdlWithDiscriminators>),
+    /// The program has no IDL account.
+    Missing,
+}
+
+impl IdlCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `None` if the program hasn't been cached.
+    pub fn get(&self, program_id: &Pubkey) -> Option<CachedIdl> {
+        self.entries.read().unwrap().get(program_id).cloned()
+    }
+
+    /// Like [IdlCache::get], but a program known to have no IDL
+    /// is reported as [LensError::IdlAccountNotFound].
+    pub fn lookup(&self, program_id: &Pubkey) -> Option<Result<Arc<IdlWithDiscriminators>>> {
+        Some(match self.get(program_id)? {
+            CachedIdl::Found(idl) => Ok(idl),
+            CachedIdl::Missing => Err(LensError::IdlAccountNotFound {
+                program_id: *program_id,
+            }
+            .into()),
+        })
+    }
+
+    /// Caches the result of fetching a program's IDL, and passes it on.
+    /// A missing IDL account is cached as [CachedIdl::Missing], but other errors,
+    /// like a failed RPC call or an unreadable IDL, are not cached.
+    pub fn record(
+        &self,
+        program_id: Pubkey,
+        fetched: Result<IdlWithDiscriminators>,
+    ) -> Result<Arc<IdlWithDiscriminators>> {
+        match fetched {
+            Ok(idl) => {
+                let idl = Arc::new(idl);
+                self.insert(program_id, idl.clone());
+                Ok(idl)
+            }
+            Err(err) => {
+                if let Some(LensError::IdlAccountNotFound { .. }) = err.downcast_ref() {
+                    self.insert_missing(program_id);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    pub fn insert(&self, program_id: Pubkey, idl: Arc<IdlWithDiscriminators>) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(program_id, CachedIdl::Found(idl));
+    }
+
+    /// Records that a program has no IDL.
+    pub fn insert_missing(&self, program_id: Pubkey) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(program_id, CachedIdl::Missing);
+    }
+
+    /// Forgets a program, so that its IDL is fetched again next time.
+    /// Useful after an IDL is published or upgraded.
+    pub fn remove(&self, program_id: &Pubkey) -> Option<CachedIdl> {
+        self.histories.write().unwrap().remove(program_id);
+        self.entries.write().unwrap().remove(program_id)
+    }
+
+    /// A program's IDL history, if it's been reconstructed.
+    pub fn history(&self, program_id: &Pubkey) -> Option<Arc<IdlHistory>> {
+        self.histories.read().unwrap().get(program_id).cloned()
+    }
+
+    pub fn insert_history(&self, program_id: Pubkey, history: Arc<IdlHistory>) {
+        self.histories.write().unwrap().insert(program_id, history);
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+        self.histories.write().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+}
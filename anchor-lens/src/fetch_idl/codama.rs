@@ -0,0 +1,613 @@
+use crate::fetch_idl::discriminators::ExplicitDiscriminators;
+use anchor_syn::idl::{
+    EnumFields, Idl, IdlAccount, IdlAccountItem, IdlEnumVariant, IdlErrorCode, IdlField,
+    IdlInstruction, IdlType, IdlTypeDefinition, IdlTypeDefinitionTy,
+};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::bs58;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// An IDL written by Codama (formerly Kinobi), which describes a program as a tree
+/// of `kind`-tagged nodes rather than Anchor's format. Metaplex and many native
+/// Borsh programs publish these, sometimes converted from a Shank IDL.
+///
+/// The IDL is converted to the legacy [anchor_syn::idl::Idl] representation when
+/// parsed, see [CodamaConverter]. Codama discriminators are explicit: a constant
+/// prefix, or a leading field whose default value tells items apart. The latter field
+/// is dropped from the converted layout, since the discriminator is skipped before
+/// decoding, the same way Anchor's are. Items that are only told apart by their size,
+/// or by a field further in, have no discriminator. Such accounts are decoded as a named
+/// type with [crate::deserialize::deserialize_account_as_type].
+#[derive(Debug, Clone)]
+pub struct CodamaIdl {
+    pub idl: Idl,
+    pub address: Option<Pubkey>,
+    pub discriminators: ExplicitDiscriminators,
+}
+
+impl CodamaIdl {
+    /// Codama IDLs are a `rootNode`, legacy and Anchor 0.30+ IDLs have no `kind`.
+    pub fn is_codama(value: &Value) -> bool {
+        value.get("kind").and_then(Value::as_str) == Some("rootNode")
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self> {
+        let program = field(value, "program")?;
+        CodamaConverter::new(program)?.convert_program()
+    }
+}
+
+/// Converts a Codama `programNode` into the legacy [anchor_syn::idl::Idl] shape.
+///
+/// The legacy format can only reference named types, so structs, enums and tuples
+/// written inline are given a type definition named after where they appear,
+/// e.g. `createMetadata.data`. Type aliases are inlined, and maps become a vector
+/// of `key` and `value` entries, which Borsh encodes the same way.
+struct CodamaConverter<'a> {
+    program: &'a Value,
+    defined_types: &'a [Value],
+    generated: Vec<IdlTypeDefinition>,
+    generated_names: HashSet<String>,
+}
+
+impl<'a> CodamaConverter<'a> {
+    fn new(program: &'a Value) -> Result<Self> {
+        Ok(Self {
+            program,
+            defined_types: optional_array(program, "definedTypes")?,
+            generated: vec![],
+            generated_names: HashSet::new(),
+        })
+    }
+
+    fn convert_program(mut self) -> Result<CodamaIdl> {
+        let program_node = self.program;
+        let name = str_field(program_node, "name")?.to_string();
+        let address = program_node
+            .get("publicKey")
+            .and_then(Value::as_str)
+            .and_then(|address| Pubkey::from_str(address).ok());
+        let mut discriminators = ExplicitDiscriminators {
+            exhaustive: true,
+            ..Default::default()
+        };
+
+        let mut instructions = vec![];
+        for ix in optional_array(program_node, "instructions")? {
+            let ix_name = str_field(ix, "name")?;
+            let mut args = optional_array(ix, "arguments")?.to_vec();
+            if let Some((discriminator, is_field)) = self.discriminator(ix, &args)? {
+                if is_field {
+                    args.remove(0);
+                }
+                discriminators
+                    .instructions
+                    .insert(ix_name.to_string(), discriminator);
+            }
+            instructions.push(IdlInstruction {
+                name: ix_name.to_string(),
+                docs: docs(ix),
+                accounts: optional_array(ix, "accounts")?
+                    .iter()
+                    .map(convert_instruction_account)
+                    .collect::<Result<Vec<_>>>()?,
+                args: self.convert_fields(&args, ix_name)?,
+                returns: None,
+            });
+        }
+
+        let mut accounts = vec![];
+        for account in optional_array(program_node, "accounts")? {
+            let account_name = str_field(account, "name")?;
+            let data = field(account, "data")?;
+            let mut fields = optional_array(data, "fields")?.to_vec();
+            if let Some((discriminator, is_field)) = self.discriminator(account, &fields)? {
+                if is_field {
+                    fields.remove(0);
+                }
+                discriminators
+                    .accounts
+                    .insert(account_name.to_string(), discriminator);
+            }
+            accounts.push(IdlTypeDefinition {
+                name: account_name.to_string(),
+                docs: docs(account),
+                ty: IdlTypeDefinitionTy::Struct {
+                    fields: self.convert_fields(&fields, account_name)?,
+                },
+            });
+        }
+
+        // Aliases have no legacy equivalent, they're inlined where they're referenced.
+        let mut types = vec![];
+        let defined_types = self.defined_types;
+        for defined_type in defined_types {
+            let type_name = str_field(defined_type, "name")?;
+            let ty = field(defined_type, "type")?;
+            if let Some(ty) = self.convert_type_def(ty, type_name)? {
+                types.push(IdlTypeDefinition {
+                    name: type_name.to_string(),
+                    docs: docs(defined_type),
+                    ty,
+                });
+            }
+        }
+        types.append(&mut self.generated);
+
+        let errors = optional_array(program_node, "errors")?
+            .iter()
+            .map(|error| {
+                Ok(IdlErrorCode {
+                    code: field(error, "code")?
+                        .as_u64()
+                        .and_then(|code| u32::try_from(code).ok())
+                        .ok_or(anyhow!("Error code is not a u32"))?,
+                    name: str_field(error, "name")?.to_string(),
+                    msg: error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let idl = Idl {
+            version: program_node
+                .get("version")
+                .and_then(Value::as_str)
+                .unwrap_or("0.0.0")
+                .to_string(),
+            name,
+            docs: docs(program_node),
+            constants: vec![],
+            instructions,
+            state: None,
+            accounts,
+            types,
+            events: None,
+            errors: if errors.is_empty() {
+                None
+            } else {
+                Some(errors)
+            },
+            metadata: Some(json!({
+                "address": address.map(|address| address.to_string()),
+                "origin": "codama",
+            })),
+        };
+        Ok(CodamaIdl {
+            idl,
+            address,
+            discriminators,
+        })
+    }
+
+    /// The first of an item's discriminators that's a prefix of its data, and whether
+    /// it's the item's leading field, rather than a constant before its fields.
+    fn discriminator(&self, item: &Value, fields: &[Value]) -> Result<Option<(Vec<u8>, bool)>> {
+        for discriminator in optional_array(item, "discriminators")? {
+            if discriminator
+                .get("offset")
+                .and_then(Value::as_u64)
+                .unwrap_or(0)
+                != 0
+            {
+                continue;
+            }
+            match kind(discriminator)? {
+                "fieldDiscriminatorNode" => {
+                    let name = str_field(discriminator, "name")?;
+                    let first = match fields.first() {
+                        Some(first) if first.get("name").and_then(Value::as_str) == Some(name) => {
+                            first
+                        }
+                        _ => continue,
+                    };
+                    let value = match first.get("defaultValue") {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    let bytes = self.value_bytes(value, field(first, "type")?)?;
+                    return Ok(Some((bytes, true)));
+                }
+                "constantDiscriminatorNode" => {
+                    let constant = field(discriminator, "constant")?;
+                    let bytes =
+                        self.value_bytes(field(constant, "value")?, field(constant, "type")?)?;
+                    return Ok(Some((bytes, false)));
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Encodes a value node the way the program would serialize it as type `ty`.
+    fn value_bytes(&self, value: &Value, ty: &Value) -> Result<Vec<u8>> {
+        let ty = self.resolve_alias(ty)?;
+        Ok(match kind(value)? {
+            "numberValueNode" => {
+                let number = field(value, "number")?;
+                let number = number
+                    .as_i64()
+                    .map(i128::from)
+                    .or_else(|| number.as_u64().map(i128::from))
+                    .ok_or(anyhow!("Discriminator {} is not an integer", number))?;
+                let width = match number_format(self.number_type(ty)?)? {
+                    IdlType::U8 | IdlType::I8 => 1,
+                    IdlType::U16 | IdlType::I16 => 2,
+                    IdlType::U32 | IdlType::I32 => 4,
+                    IdlType::U64 | IdlType::I64 => 8,
+                    IdlType::U128 | IdlType::I128 => 16,
+                    other => return Err(anyhow!("Discriminators can't be a {:?}", other)),
+                };
+                number.to_le_bytes()[..width].to_vec()
+            }
+            "booleanValueNode" => vec![field(value, "boolean")?.as_bool().unwrap_or(false) as u8],
+            "bytesValueNode" => {
+                let data = str_field(value, "data")?;
+                match str_field(value, "encoding")? {
+                    "base16" => decode_hex(data)?,
+                    "base58" => bs58::decode(data).into_vec()?,
+                    "base64" => base64::decode(data)?,
+                    _ => data.as_bytes().to_vec(),
+                }
+            }
+            "stringValueNode" => str_field(value, "string")?.as_bytes().to_vec(),
+            "enumValueNode" => {
+                let link = field(value, "enum")?;
+                let enum_name = str_field(link, "name")?;
+                let variant = str_field(value, "variant")?;
+                let enum_type = self
+                    .find_defined_type(enum_name)
+                    .map(|defined_type| field(defined_type, "type"))
+                    .transpose()?
+                    .ok_or(anyhow!("Couldn't find defined type: {}", enum_name))?;
+                let index = optional_array(enum_type, "variants")?
+                    .iter()
+                    .position(|v| v.get("name").and_then(Value::as_str) == Some(variant))
+                    .ok_or(anyhow!("Enum {} has no variant {}", enum_name, variant))?;
+                vec![u8::try_from(index)?]
+            }
+            "constantValueNode" => {
+                self.value_bytes(field(value, "value")?, field(value, "type")?)?
+            }
+            other => return Err(anyhow!("Unsupported discriminator value: {}", other)),
+        })
+    }
+
+    /// Unwraps amounts, dates and aliases down to the number type they're stored as.
+    fn number_type<'v>(&self, ty: &'v Value) -> Result<&'v Value> {
+        match kind(ty)? {
+            "amountTypeNode" | "dateTimeTypeNode" | "solAmountTypeNode" => {
+                self.number_type(field(ty, "number")?)
+            }
+            _ => Ok(ty),
+        }
+    }
+
+    fn find_defined_type(&self, name: &str) -> Option<&'a Value> {
+        self.defined_types
+            .iter()
+            .find(|defined_type| defined_type.get("name").and_then(Value::as_str) == Some(name))
+    }
+
+    /// Follows links to defined types that aren't structs or enums.
+    fn resolve_alias<'v>(&self, ty: &'v Value) -> Result<&'v Value>
+    where
+        'a: 'v,
+    {
+        if kind(ty)? != "definedTypeLinkNode" {
+            return Ok(ty);
+        }
+        match self.find_defined_type(str_field(ty, "name")?) {
+            Some(defined_type) => self.resolve_alias(field(defined_type, "type")?),
+            None => Ok(ty),
+        }
+    }
+
+    fn convert_type_def(&mut self, ty: &Value, name: &str) -> Result<Option<IdlTypeDefinitionTy>> {
+        Ok(Some(match kind(ty)? {
+            "structTypeNode" => IdlTypeDefinitionTy::Struct {
+                fields: self.convert_fields(optional_array(ty, "fields")?, name)?,
+            },
+            // Tuples are named by position, the way Rust accesses them.
+            "tupleTypeNode" => IdlTypeDefinitionTy::Struct {
+                fields: optional_array(ty, "items")?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        Ok(IdlField {
+                            name: i.to_string(),
+                            docs: None,
+                            ty: self.convert_type(item, &format!("{}.{}", name, i))?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            },
+            "enumTypeNode" => {
+                if let Some(size) = ty.get("size") {
+                    if !matches!(number_format(size)?, IdlType::U8) {
+                        return Err(anyhow!("Enum {} isn't indexed by a u8", name));
+                    }
+                }
+                IdlTypeDefinitionTy::Enum {
+                    variants: optional_array(ty, "variants")?
+                        .iter()
+                        .map(|variant| self.convert_variant(variant, name))
+                        .collect::<Result<Vec<_>>>()?,
+                }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    fn convert_variant(&mut self, variant: &Value, enum_name: &str) -> Result<IdlEnumVariant> {
+        let name = str_field(variant, "name")?;
+        let path = format!("{}.{}", enum_name, name);
+        Ok(IdlEnumVariant {
+            name: name.to_string(),
+            fields: match kind(variant)? {
+                "enumStructVariantTypeNode" => {
+                    let struct_node = field(variant, "struct")?;
+                    Some(EnumFields::Named(self.convert_fields(
+                        optional_array(struct_node, "fields")?,
+                        &path,
+                    )?))
+                }
+                "enumTupleVariantTypeNode" => {
+                    let tuple = field(variant, "tuple")?;
+                    Some(EnumFields::Tuple(
+                        optional_array(tuple, "items")?
+                            .iter()
+                            .enumerate()
+                            .map(|(i, item)| self.convert_type(item, &format!("{}.{}", path, i)))
+                            .collect::<Result<Vec<_>>>()?,
+                    ))
+                }
+                _ => None,
+            },
+        })
+    }
+
+    fn convert_fields(&mut self, fields: &[Value], parent: &str) -> Result<Vec<IdlField>> {
+        fields
+            .iter()
+            .map(|field_node| {
+                let name = str_field(field_node, "name")?;
+                Ok(IdlField {
+                    name: name.to_string(),
+                    docs: docs(field_node),
+                    ty: self.convert_type(
+                        field(field_node, "type")?,
+                        &format!("{}.{}", parent, name),
+                    )?,
+                })
+            })
+            .collect()
+    }
+
+    /// Converts a type node. `path` names the definition generated for inline structs,
+    /// enums and tuples.
+    fn convert_type(&mut self, ty: &Value, path: &str) -> Result<IdlType> {
+        Ok(match kind(ty)? {
+            "numberTypeNode" => number_format(ty)?,
+            "amountTypeNode" | "dateTimeTypeNode" | "solAmountTypeNode" => {
+                self.convert_type(field(ty, "number")?, path)?
+            }
+            "booleanTypeNode" => {
+                if let Some(size) = ty.get("size") {
+                    if !matches!(number_format(size)?, IdlType::U8) {
+                        return Err(anyhow!("{}: booleans must be stored as a u8", path));
+                    }
+                }
+                IdlType::Bool
+            }
+            "publicKeyTypeNode" => IdlType::PublicKey,
+            "sizePrefixTypeNode" => {
+                require_u32(field(ty, "prefix")?, path)?;
+                match kind(field(ty, "type")?)? {
+                    "stringTypeNode" => IdlType::String,
+                    "bytesTypeNode" => IdlType::Bytes,
+                    other => return Err(anyhow!("{}: unsupported size prefixed {}", path, other)),
+                }
+            }
+            "fixedSizeTypeNode" => {
+                let size = field(ty, "size")?
+                    .as_u64()
+                    .ok_or(anyhow!("{}: fixed size is not a number", path))?;
+                match kind(field(ty, "type")?)? {
+                    "stringTypeNode" | "bytesTypeNode" => {
+                        IdlType::Array(Box::new(IdlType::U8), size as usize)
+                    }
+                    other => return Err(anyhow!("{}: unsupported fixed size {}", path, other)),
+                }
+            }
+            "optionTypeNode" => {
+                if ty.get("fixed").and_then(Value::as_bool).unwrap_or(false) {
+                    return Err(anyhow!("{}: fixed size options are not supported", path));
+                }
+                if let Some(prefix) = ty.get("prefix") {
+                    if !matches!(number_format(prefix)?, IdlType::U8) {
+                        return Err(anyhow!("{}: option tags must be a u8", path));
+                    }
+                }
+                IdlType::Option(Box::new(self.convert_type(field(ty, "item")?, path)?))
+            }
+            "arrayTypeNode" | "setTypeNode" => {
+                let item = self.convert_type(field(ty, "item")?, path)?;
+                self.convert_count(field(ty, "count")?, item, path)?
+            }
+            "mapTypeNode" => {
+                let entry = IdlTypeDefinitionTy::Struct {
+                    fields: vec![
+                        IdlField {
+                            name: "key".to_string(),
+                            docs: None,
+                            ty: self.convert_type(field(ty, "key")?, &format!("{}.key", path))?,
+                        },
+                        IdlField {
+                            name: "value".to_string(),
+                            docs: None,
+                            ty: self
+                                .convert_type(field(ty, "value")?, &format!("{}.value", path))?,
+                        },
+                    ],
+                };
+                let entry = self.generate(&format!("{}.entry", path), entry);
+                self.convert_count(field(ty, "count")?, entry, path)?
+            }
+            "structTypeNode" | "enumTypeNode" | "tupleTypeNode" => {
+                let definition = self.convert_type_def(ty, path)?.unwrap();
+                self.generate(path, definition)
+            }
+            "definedTypeLinkNode" => {
+                let name = str_field(ty, "name")?;
+                match self.find_defined_type(name) {
+                    Some(defined_type) => {
+                        let aliased = field(defined_type, "type")?;
+                        match kind(aliased)? {
+                            "structTypeNode" | "enumTypeNode" | "tupleTypeNode" => {
+                                IdlType::Defined(name.to_string())
+                            }
+                            _ => self.convert_type(aliased, path)?,
+                        }
+                    }
+                    // Deserialization reports types that aren't defined when it gets there.
+                    None => IdlType::Defined(name.to_string()),
+                }
+            }
+            other => return Err(anyhow!("{}: unsupported type {}", path, other)),
+        })
+    }
+
+    fn convert_count(&mut self, count: &Value, item: IdlType, path: &str) -> Result<IdlType> {
+        Ok(match kind(count)? {
+            "fixedCountNode" => {
+                let len = field(count, "value")?
+                    .as_u64()
+                    .ok_or(anyhow!("{}: array length is not a number", path))?;
+                IdlType::Array(Box::new(item), len as usize)
+            }
+            "prefixedCountNode" => {
+                require_u32(field(count, "prefix")?, path)?;
+                IdlType::Vec(Box::new(item))
+            }
+            other => return Err(anyhow!("{}: unsupported count {}", path, other)),
+        })
+    }
+
+    fn generate(&mut self, name: &str, ty: IdlTypeDefinitionTy) -> IdlType {
+        if self.generated_names.insert(name.to_string()) {
+            self.generated.push(IdlTypeDefinition {
+                name: name.to_string(),
+                docs: None,
+                ty,
+            });
+        }
+        IdlType::Defined(name.to_string())
+    }
+}
+
+/// Accounts that may or may not sign are listed as non-signers.
+fn convert_instruction_account(account: &Value) -> Result<IdlAccountItem> {
+    let signer = account
+        .get("isSigner")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    Ok(IdlAccountItem::IdlAccount(IdlAccount {
+        name: str_field(account, "name")?.to_string(),
+        is_mut: account
+            .get("isWritable")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        is_signer: signer,
+        docs: docs(account),
+        pda: None,
+        relations: vec![],
+    }))
+}
+
+fn number_format(ty: &Value) -> Result<IdlType> {
+    if kind(ty)? != "numberTypeNode" {
+        return Err(anyhow!("Expected a number type, found {}", kind(ty)?));
+    }
+    if ty.get("endian").and_then(Value::as_str).unwrap_or("le") != "le" {
+        return Err(anyhow!("Big endian numbers are not supported"));
+    }
+    Ok(match str_field(ty, "format")? {
+        "u8" => IdlType::U8,
+        "i8" => IdlType::I8,
+        "u16" => IdlType::U16,
+        "i16" => IdlType::I16,
+        "u32" => IdlType::U32,
+        "i32" => IdlType::I32,
+        "u64" => IdlType::U64,
+        "i64" => IdlType::I64,
+        "u128" => IdlType::U128,
+        "i128" => IdlType::I128,
+        "f32" => IdlType::F32,
+        "f64" => IdlType::F64,
+        other => return Err(anyhow!("Unsupported number format: {}", other)),
+    })
+}
+
+/// Borsh prefixes strings, bytes and collections with their length as a `u32`.
+fn require_u32(prefix: &Value, path: &str) -> Result<()> {
+    match number_format(prefix)? {
+        IdlType::U32 => Ok(()),
+        other => Err(anyhow!("{}: unsupported {:?} length prefix", path, other)),
+    }
+}
+
+fn kind(node: &Value) -> Result<&str> {
+    str_field(node, "kind")
+}
+
+fn field<'v>(node: &'v Value, key: &str) -> Result<&'v Value> {
+    node.get(key).ok_or_else(|| {
+        let kind = node.get("kind").and_then(Value::as_str).unwrap_or("node");
+        anyhow!("Codama {} has no {}", kind, key)
+    })
+}
+
+fn str_field<'v>(node: &'v Value, key: &str) -> Result<&'v str> {
+    field(node, key)?
+        .as_str()
+        .ok_or(anyhow!("Codama {} is not a string", key))
+}
+
+fn optional_array<'v>(node: &'v Value, key: &str) -> Result<&'v [Value]> {
+    match node.get(key) {
+        None | Some(Value::Null) => Ok(&[]),
+        Some(Value::Array(items)) => Ok(items),
+        Some(_) => Err(anyhow!("Codama {} is not a list", key)),
+    }
+}
+
+fn docs(node: &Value) -> Option<Vec<String>> {
+    let docs = node
+        .get("docs")?
+        .as_array()?
+        .iter()
+        .filter_map(|doc| doc.as_str().map(str::to_string))
+        .collect::<Vec<_>>();
+    if docs.is_empty() {
+        None
+    } else {
+        Some(docs)
+    }
+}
+
+fn decode_hex(data: &str) -> Result<Vec<u8>> {
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [_, _] => Ok(u8::from_str_radix(std::str::from_utf8(pair)?, 16)?),
+            _ => Err(anyhow!("Odd length hex string: {}", data)),
+        })
+        .collect()
+}
@@ -1,48 +1,153 @@
+use crate::fetch_idl::versioned::VersionedIdl;
 use anchor_syn::hash::hash;
-use anchor_syn::idl::{Idl, IdlInstruction, IdlTypeDefinition};
-use std::collections::BTreeMap;
+use anchor_syn::idl::{Idl, IdlEvent, IdlInstruction, IdlTypeDefinition};
+use solana_program::pubkey::Pubkey;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ops::Deref;
 
 /// Provides a means of looking up by discriminator to retrieve
-/// the IDL definitions for their associated account or instruction schema.
+/// the IDL definitions for their associated account, instruction or event schema.
 ///
-/// Discriminators are calculated taking one of the following strings:
+/// Anchor 0.30+, Shank and Codama IDLs carry explicit discriminators, which can be
+/// of any length, and those are used as-is. Otherwise discriminators are
+/// calculated taking one of the following strings:
 /// - Accounts -- `"account:<AccountStructName>"`
 /// - Instructions -- `"global:<IxName>"` or `"state:<IxName>"`
+/// - Events -- `"event:<EventStructName>"`
 ///
 /// hashing it, and keeping only the first 8 bytes.
 #[derive(Debug, Clone)]
 pub struct Discriminators {
-    pub instructions: BTreeMap<[u8; 8], IdlInstruction>,
-    pub accounts: BTreeMap<[u8; 8], IdlTypeDefinition>,
+    pub instructions: DiscriminatorMap<IdlInstruction>,
+    pub accounts: DiscriminatorMap<IdlTypeDefinition>,
+    pub events: DiscriminatorMap<IdlEvent>,
 }
 
 impl Discriminators {
-    /// Calculates account and instruction discriminators, indexes
+    /// Calculates account, instruction and event discriminators, indexes
     /// hashmaps according to a key-value structure of:
     /// `(discriminator, idl_schema)`.
     pub fn from_idl(idl: Idl) -> Self {
+        Self::from_idl_with_explicit(idl, &ExplicitDiscriminators::default())
+    }
+
+    /// Same as [Discriminators::from_idl], but any discriminator listed in `explicit`
+    /// takes precedence over the one derived from the item's name.
+    pub fn from_idl_with_explicit(idl: Idl, explicit: &ExplicitDiscriminators) -> Self {
         Self {
             instructions: idl
                 .instructions
                 .iter()
-                .map(|ix| {
-                    vec![
-                        (ix_state_discriminator(&ix.name), ix.clone()),
-                        (ix_discriminator(&ix.name), ix.clone()),
-                    ]
+                .map(|ix| match explicit.instructions.get(&ix.name) {
+                    Some(discriminator) => vec![(discriminator.clone(), ix.clone())],
+                    None if explicit.exhaustive => vec![],
+                    None => vec![
+                        (ix_state_discriminator(&ix.name).to_vec(), ix.clone()),
+                        (ix_discriminator(&ix.name).to_vec(), ix.clone()),
+                    ],
                 })
                 .flatten()
                 .collect(),
             accounts: idl
                 .accounts
                 .into_iter()
-                .map(|act| (account_discriminator(&act.name), act))
+                .filter_map(|act| match explicit.accounts.get(&act.name) {
+                    Some(discriminator) => Some((discriminator.clone(), act)),
+                    None if explicit.exhaustive => None,
+                    None => Some((account_discriminator(&act.name).to_vec(), act)),
+                })
+                .collect(),
+            events: idl
+                .events
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|event| match explicit.events.get(&event.name) {
+                    Some(discriminator) => Some((discriminator.clone(), event)),
+                    None if explicit.exhaustive => None,
+                    None => Some((event_discriminator(&event.name).to_vec(), event)),
+                })
                 .collect(),
         }
     }
 }
 
+/// Discriminators that an IDL states outright, keyed by item name,
+/// as opposed to ones derived by hashing the name.
+#[derive(Debug, Clone, Default)]
+pub struct ExplicitDiscriminators {
+    pub instructions: HashMap<String, Vec<u8>>,
+    pub accounts: HashMap<String, Vec<u8>>,
+    pub events: HashMap<String, Vec<u8>>,
+    /// Whether items missing from these maps have no discriminator, rather than
+    /// one derived from their name. Native programs don't hash item names, so their
+    /// accounts without a discriminator are decoded as a named type, see
+    /// [crate::deserialize::deserialize_account_as_type].
+    pub exhaustive: bool,
+}
+
+/// A lookup table from discriminator bytes to an IDL item, where the
+/// discriminators need not all be the same length.
+#[derive(Debug, Clone)]
+pub struct DiscriminatorMap<T> {
+    entries: BTreeMap<Vec<u8>, T>,
+    /// Every distinct discriminator length in `entries`, so lookups
+    /// only need to try the prefixes that could possibly match.
+    lengths: BTreeSet<usize>,
+}
+
+impl<T> DiscriminatorMap<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            lengths: BTreeSet::new(),
+        }
+    }
+
+    pub fn insert(&mut self, discriminator: Vec<u8>, item: T) -> Option<T> {
+        self.lengths.insert(discriminator.len());
+        self.entries.insert(discriminator, item)
+    }
+
+    /// Finds the item whose discriminator is a prefix of `data`, returning the matched
+    /// discriminator along with it. If several match, the longest discriminator wins.
+    pub fn get(&self, data: &[u8]) -> Option<(&[u8], &T)> {
+        self.lengths
+            .iter()
+            .rev()
+            .filter_map(|len| data.get(..*len))
+            .find_map(|prefix| self.entries.get_key_value(prefix))
+            .map(|(discriminator, item)| (discriminator.as_slice(), item))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &T)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<T> Default for DiscriminatorMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<(Vec<u8>, T)> for DiscriminatorMap<T> {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, T)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (discriminator, item) in iter {
+            map.insert(discriminator, item);
+        }
+        map
+    }
+}
+
 /// Calculates the discriminator for an account based on its name,
 /// which would be found in an IDL.
 fn account_discriminator(name: &str) -> [u8; 8] {
@@ -59,6 +164,14 @@ fn ix_discriminator(name: &str) -> [u8; 8] {
         .unwrap()
 }
 
+/// Calculates the discriminator for an event based on its name,
+/// which would be found in an IDL.
+fn event_discriminator(name: &str) -> [u8; 8] {
+    hash(format!("event:{}", name).as_bytes()).to_bytes()[0..8]
+        .try_into()
+        .unwrap()
+}
+
 /// Calculates the discriminator for a state-modifying instruction based on its name,
 /// which would be found in an IDL.
 fn ix_state_discriminator(name: &str) -> [u8; 8] {
@@ -72,20 +185,52 @@ fn ix_state_discriminator(name: &str) -> [u8; 8] {
 /// discriminator to its associated `anchor_syn` IDL type.
 /// Accounts are parsed from [anchor_syn::idl::IdlTypeDefinition].
 /// Instructions are parsed from an [anchor_syn::idl::IdlInstruction].
+///
+/// IDLs following the Anchor 0.30+ specification are converted
+/// to the legacy format first, see [VersionedIdl].
 #[derive(Debug, Clone)]
 pub struct IdlWithDiscriminators {
     idl: Idl,
+    /// The program ID, as recorded in the IDL, or the program it was fetched for.
+    pub address: Option<Pubkey>,
     pub discriminators: Discriminators,
 }
 
 impl IdlWithDiscriminators {
     pub fn new(idl: Idl) -> Self {
+        let address = VersionedIdl::Legacy(idl.clone()).address();
         let discriminators = Discriminators::from_idl(idl.clone());
         Self {
             idl,
+            address,
             discriminators,
         }
     }
+
+    /// Accepts an IDL in either the legacy or the Anchor 0.30+ format.
+    pub fn from_versioned(idl: VersionedIdl) -> anyhow::Result<Self> {
+        let address = idl.address();
+        let explicit = idl.explicit_discriminators();
+        let idl = idl.into_legacy()?;
+        let discriminators = Discriminators::from_idl_with_explicit(idl.clone(), &explicit);
+        Ok(Self {
+            idl,
+            address,
+            discriminators,
+        })
+    }
+
+    /// Whether the IDL describes an Anchor program, rather than a native program
+    /// described by Shank or Codama, which mark the IDL with their `metadata.origin`.
+    pub fn is_anchor(&self) -> bool {
+        let origin = self
+            .idl
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("origin"))
+            .and_then(|origin| origin.as_str());
+        !matches!(origin, Some("shank") | Some("codama"))
+    }
 }
 
 impl Deref for IdlWithDiscriminators {
@@ -0,0 +1,199 @@
+use crate::error::LensError;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::workspace::cluster_for_url;
+use crate::fetch_idl::{decompress_idl_account, find_idl_account, parse_idl_json};
+use crate::source::AccountSource;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::hash::hash;
+use solana_program::pubkey::Pubkey;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+/// IDLs fetched from chain, saved on disk so that later runs can skip
+/// decompressing and parsing them, or even run offline.
+///
+/// Each IDL is stored decompressed, in `<dir>/<cluster>/<program_id>.json`, along with
+/// the address and a hash of the IDL account it was read from, and the newest
+/// transaction that referenced it. Before a cached IDL is used, the source is asked
+/// for the newest transaction referencing the IDL account, see
+/// [AccountSource::get_latest_signature]. If there's none newer, the cached IDL is used,
+/// and otherwise the IDL account is fetched and its hash compared, so an upgraded IDL
+/// replaces the cached one. If the IDL account can't be fetched, the cached IDL is used.
+#[derive(Debug, Clone)]
+pub struct DiskIdlCache {
+    pub dir: PathBuf,
+    /// Keys the cache along with the program ID, since a program can
+    /// have a different IDL on each cluster.
+    pub cluster: String,
+    /// Whether to check the IDL account before using a cached IDL. Without it,
+    /// cached IDLs are used without making any RPC calls. Defaults to `true`.
+    pub revalidate: bool,
+}
+
+/// The file stored for each program.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    idl_account: String,
+    /// The SHA-256 hash of the IDL account data.
+    data_hash: String,
+    /// The newest transaction that referenced the IDL account when it was read.
+    #[serde(default)]
+    latest_signature: Option<String>,
+    /// The decompressed IDL JSON.
+    idl: Value,
+}
+
+impl DiskIdlCache {
+    pub fn new(dir: impl Into<PathBuf>, cluster: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            cluster: cluster.into(),
+            revalidate: true,
+        }
+    }
+
+    /// Keys the cache by the cluster an RPC URL points at, or by the URL itself
+    /// if it isn't one of the public clusters or a local validator.
+    pub fn for_url(dir: impl Into<PathBuf>, url: &str) -> Self {
+        let cluster = match cluster_for_url(url) {
+            Some(cluster) => cluster.to_string(),
+            None => url
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect(),
+        };
+        Self::new(dir, cluster)
+    }
+
+    /// `$XDG_CACHE_HOME/solana-anchor-lens/idls`, or `~/.cache/solana-anchor-lens/idls`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(cache_home.join("solana-anchor-lens").join("idls"))
+    }
+
+    /// Where the IDL for a program is stored.
+    pub fn path(&self, program_id: &Pubkey) -> PathBuf {
+        self.dir
+            .join(&self.cluster)
+            .join(format!("{}.json", program_id))
+    }
+
+    /// Fetches an IDL like [crate::fetch_idl::fetch_idl], reusing the cached IDL
+    /// as long as the IDL account is unchanged, and caching it otherwise.
+    pub fn fetch_idl<S: AccountSource + ?Sized>(
+        &self,
+        client: &S,
+        program_id: &Pubkey,
+    ) -> Result<IdlWithDiscriminators> {
+        let cached = self.read(program_id);
+        // The signature is read before the account, so that a transaction landing
+        // in between makes the cached IDL look outdated, rather than current.
+        let mut latest_signature = None;
+        let fetched = match &cached {
+            Some((_, entry)) if !self.revalidate => return entry_idl(program_id, entry),
+            // The IDL account of a program never moves, so there's no need
+            // to look up the program account again.
+            Some((idl_account, entry)) => {
+                latest_signature = client
+                    .get_latest_signature(idl_account)
+                    .ok()
+                    .flatten()
+                    .map(|signature| signature.to_string());
+                if latest_signature.is_some() && latest_signature == entry.latest_signature {
+                    return entry_idl(program_id, entry);
+                }
+                client
+                    .get_idl_account_with_slot(idl_account)
+                    .map(|(account, _)| account.map(|account| (*idl_account, account)))
+            }
+            None => find_idl_account(client, program_id),
+        };
+        let (idl_account, account) = match (fetched, cached.as_ref()) {
+            (Ok(Some(found)), _) => found,
+            (Ok(None), _) => {
+                self.remove(program_id)?;
+                return Err(LensError::IdlAccountNotFound {
+                    program_id: *program_id,
+                }
+                .into());
+            }
+            // We might be offline, or the RPC might be down.
+            (Err(_), Some((_, entry))) => return entry_idl(program_id, entry),
+            (Err(err), None) => return Err(err),
+        };
+        let data_hash = hash(&account.data).to_string();
+        if let Some((_, mut entry)) = cached {
+            if entry.data_hash == data_hash {
+                // Only transactions that didn't change the IDL landed, so remember
+                // the newest, to skip reading the IDL account next time.
+                if latest_signature.is_some() {
+                    entry.latest_signature = latest_signature;
+                    self.write(program_id, &entry)?;
+                }
+                return entry_idl(program_id, &entry);
+            }
+        }
+        let json = decompress_idl_account(program_id, &account.data)?;
+        let entry = CacheEntry {
+            idl_account: idl_account.to_string(),
+            data_hash,
+            latest_signature,
+            idl: serde_json::from_slice(&json).map_err(|err| LensError::InvalidIdl {
+                program_id: *program_id,
+                reason: format!("Could not deserialize decompressed IDL data: {}", err),
+            })?,
+        };
+        let idl = entry_idl(program_id, &entry)?;
+        self.write(program_id, &entry)?;
+        Ok(idl)
+    }
+
+    /// Deletes the cached IDL for a program, if there is one.
+    pub fn remove(&self, program_id: &Pubkey) -> Result<()> {
+        let path = self.path(program_id);
+        if path.is_file() {
+            fs::remove_file(&path)
+                .map_err(|e| anyhow!("Could not remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Unreadable entries are treated as missing, and get replaced.
+    fn read(&self, program_id: &Pubkey) -> Option<(Pubkey, CacheEntry)> {
+        let data = fs::read(self.path(program_id)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&data).ok()?;
+        let idl_account = Pubkey::from_str(&entry.idl_account).ok()?;
+        Some((idl_account, entry))
+    }
+
+    fn write(&self, program_id: &Pubkey, entry: &CacheEntry) -> Result<()> {
+        let path = self.path(program_id);
+        let dir = self.dir.join(&self.cluster);
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow!("Could not create IDL cache {}: {}", dir.display(), e))?;
+        // Write then rename, so that concurrent runs, and threads, never read a partial file.
+        let write = || -> std::io::Result<()> {
+            let mut tmp = NamedTempFile::new_in(&dir)?;
+            tmp.write_all(&serde_json::to_vec(entry)?)?;
+            tmp.persist(&path)?;
+            Ok(())
+        };
+        write().map_err(|e| anyhow!("Could not write {}: {}", path.display(), e))
+    }
+}
+
+fn entry_idl(program_id: &Pubkey, entry: &CacheEntry) -> Result<IdlWithDiscriminators> {
+    let mut idl = parse_idl_json(program_id, &serde_json::to_vec(&entry.idl)?)?;
+    // Legacy IDLs don't usually record their address.
+    if entry.idl_account != program_id.to_string() {
+        idl.address = idl.address.or(Some(*program_id));
+    }
+    Ok(idl)
+}
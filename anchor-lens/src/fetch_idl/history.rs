@@ -0,0 +1,157 @@
+use crate::deserialize::idl_instruction::idl_instructions as transaction_idl_instructions;
+use crate::deserialize::HistoricalTransaction;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::{inflate, parse_idl_json};
+use crate::source::{SignatureSource, TransactionSource};
+use anchor_lang::idl::{IdlAccount, IdlInstruction};
+use anyhow::Result;
+use solana_program::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// The IDLs a program has published over time, so that old transactions
+/// can be decoded with the IDL that was live when they landed.
+#[derive(Debug, Clone, Default)]
+pub struct IdlHistory {
+    /// Ordered by slot.
+    pub versions: Vec<IdlVersion>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IdlVersion {
+    /// The slot the IDL was published in.
+    pub slot: u64,
+    pub idl: Arc<IdlWithDiscriminators>,
+}
+
+impl IdlHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a version, keeping versions ordered by slot.
+    /// A version published in the same slot as an existing one replaces it.
+    pub fn insert(&mut self, slot: u64, idl: Arc<IdlWithDiscriminators>) {
+        match self
+            .versions
+            .binary_search_by_key(&slot, |version| version.slot)
+        {
+            Ok(i) => self.versions[i].idl = idl,
+            Err(i) => self.versions.insert(i, IdlVersion { slot, idl }),
+        }
+    }
+
+    /// The IDL that was live at `slot`, which is the last one published at or before it.
+    /// Returns `None` if the program hadn't published an IDL yet.
+    pub fn at(&self, slot: u64) -> Option<&Arc<IdlWithDiscriminators>> {
+        let published = self
+            .versions
+            .partition_point(|version| version.slot <= slot);
+        Some(&self.versions.get(published.checked_sub(1)?)?.idl)
+    }
+
+    /// The slot the first version was published in.
+    pub fn first_slot(&self) -> Option<u64> {
+        self.versions.first().map(|version| version.slot)
+    }
+
+    pub fn latest(&self) -> Option<&Arc<IdlWithDiscriminators>> {
+        self.versions.last().map(|version| &version.idl)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+}
+
+/// Reconstructs the history of a program's on-chain IDL by replaying
+/// the IDL instructions sent to its IDL account.
+///
+/// `anchor idl init` writes the IDL into the IDL account directly, while
+/// `anchor idl upgrade` writes it into a buffer account, then copies the buffer
+/// over with `SetBuffer`. In that case the buffer's history is replayed too.
+pub fn fetch_idl_history<S: SignatureSource + TransactionSource + ?Sized>(
+    client: &S,
+    program_id: &Pubkey,
+) -> Result<IdlHistory> {
+    let idl_addr = IdlAccount::address(program_id);
+    let mut history = IdlHistory::new();
+    // The compressed IDL, as stored in the IDL account.
+    let mut data = vec![];
+    for (signature, slot) in client.get_signatures_for_address(&idl_addr)? {
+        let tx = match client.get_transaction(&signature)? {
+            Some(tx) if tx.err.is_none() => tx,
+            _ => continue,
+        };
+        let mut changed = false;
+        for (ix, accounts) in idl_instructions(&tx, program_id) {
+            match ix {
+                IdlInstruction::Create { .. } if accounts.get(1) == Some(&idl_addr) => {
+                    data.clear();
+                    changed = true;
+                }
+                IdlInstruction::Write { data: chunk } if accounts.first() == Some(&idl_addr) => {
+                    data.extend(chunk);
+                    changed = true;
+                }
+                IdlInstruction::SetBuffer if accounts.get(1) == Some(&idl_addr) => {
+                    data = replay_buffer(client, program_id, &accounts[0], slot)?;
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        // An IDL written over several transactions is only complete after the last one.
+        if changed {
+            let idl = inflate(&data).and_then(|json| parse_idl_json(program_id, &json).ok());
+            if let Some(mut idl) = idl {
+                idl.address = idl.address.or(Some(*program_id));
+                history.insert(slot, Arc::new(idl));
+            }
+        }
+    }
+    Ok(history)
+}
+
+/// The compressed IDL written into a buffer account, up to and including `slot`.
+fn replay_buffer<S: SignatureSource + TransactionSource + ?Sized>(
+    client: &S,
+    program_id: &Pubkey,
+    buffer: &Pubkey,
+    slot: u64,
+) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    for (signature, _) in client
+        .get_signatures_for_address(buffer)?
+        .into_iter()
+        .take_while(|(_, tx_slot)| *tx_slot <= slot)
+    {
+        let tx = match client.get_transaction(&signature)? {
+            Some(tx) if tx.err.is_none() => tx,
+            _ => continue,
+        };
+        for (ix, accounts) in idl_instructions(&tx, program_id) {
+            if accounts.first() != Some(buffer) {
+                continue;
+            }
+            match ix {
+                IdlInstruction::CreateBuffer => data.clear(),
+                IdlInstruction::Write { data: chunk } => data.extend(chunk),
+                _ => {}
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// The IDL instructions a transaction sent to `program_id`, with their accounts,
+/// in execution order, including those invoked through CPI.
+fn idl_instructions(
+    tx: &HistoricalTransaction,
+    program_id: &Pubkey,
+) -> Vec<(IdlInstruction, Vec<Pubkey>)> {
+    transaction_idl_instructions(tx)
+        .into_iter()
+        .filter(|ix| ix.program_id == *program_id)
+        .map(|ix| (ix.instruction, ix.accounts))
+        .collect()
+}
@@ -0,0 +1,122 @@
+use crate::error::LensError;
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::{
+    decompress_idl_data, deserialize_idl_account, find_idl_account_with_slot, parse_idl_json,
+};
+use crate::source::AccountSource;
+use anchor_lang::idl::IdlAccount;
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+
+/// Bytes of an IDL account taken up before the compressed IDL: the account
+/// discriminator, the authority, and the length prefix of the data.
+pub const IDL_ACCOUNT_HEADER_LEN: usize = 8 + 32 + 4;
+
+/// An IDL fetched from chain, along with the IDL account it was read from,
+/// to audit who can change it, and how much room is left to upgrade it in place.
+#[derive(Debug, Clone)]
+pub struct FetchedIdl {
+    pub idl: IdlWithDiscriminators,
+    /// The program the IDL describes, which owns its IDL account.
+    pub program_id: Pubkey,
+    /// The IDL account address.
+    pub idl_account: Pubkey,
+    /// Whether the IDL account is a buffer staged by `anchor idl write-buffer`,
+    /// rather than the program's IDL account.
+    pub is_buffer: bool,
+    /// The only key allowed to write, upgrade, or hand over the IDL.
+    pub authority: Pubkey,
+    /// The space allocated to the IDL account.
+    pub data_len: usize,
+    /// The length of the compressed IDL stored in the IDL account.
+    pub compressed_len: usize,
+    /// The length of the decompressed IDL JSON.
+    pub decompressed_len: usize,
+    /// The slot the IDL account was read at, if the source reports it.
+    pub slot: Option<u64>,
+}
+
+impl FetchedIdl {
+    /// Decodes an IDL account. `idl_addr` is the address the IDL was requested for,
+    /// either the program ID or the IDL account itself, and is only used to report errors.
+    pub fn from_account(
+        idl_addr: &Pubkey,
+        idl_account: Pubkey,
+        account: &Account,
+        slot: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        let IdlAccount { authority, data } = deserialize_idl_account(idl_addr, &account.data)?;
+        let json = decompress_idl_data(idl_addr, &data)?;
+        let mut idl = parse_idl_json(idl_addr, &json)?;
+        // IDL accounts and their buffers are created by, and so owned by, the program.
+        let program_id = account.owner;
+        // Legacy IDLs don't usually record their address.
+        idl.address = idl.address.or(Some(program_id));
+        Ok(Self {
+            idl,
+            program_id,
+            idl_account,
+            is_buffer: IdlAccount::address(&program_id) != idl_account,
+            authority,
+            data_len: account.data.len(),
+            compressed_len: data.len(),
+            decompressed_len: json.len(),
+            slot,
+        })
+    }
+
+    /// Bytes left in the IDL account, which bound the size of an IDL it can be upgraded to.
+    pub fn free_space(&self) -> usize {
+        self.data_len
+            .saturating_sub(IDL_ACCOUNT_HEADER_LEN + self.compressed_len)
+    }
+
+    /// A summary of the IDL account, without the IDL itself.
+    pub fn info(&self) -> IdlAccountInfo {
+        IdlAccountInfo {
+            program_id: self.program_id.to_string(),
+            name: self.idl.name.clone(),
+            version: self.idl.version.clone(),
+            idl_account: self.idl_account.to_string(),
+            is_buffer: self.is_buffer,
+            authority: self.authority.to_string(),
+            data_len: self.data_len,
+            compressed_len: self.compressed_len,
+            decompressed_len: self.decompressed_len,
+            free_space: self.free_space(),
+            slot: self.slot,
+        }
+    }
+}
+
+/// The serializable summary of a [FetchedIdl], see [FetchedIdl::info].
+#[derive(Debug, Clone, Serialize)]
+pub struct IdlAccountInfo {
+    pub program_id: String,
+    pub name: String,
+    pub version: String,
+    pub idl_account: String,
+    pub is_buffer: bool,
+    pub authority: String,
+    pub data_len: usize,
+    pub compressed_len: usize,
+    pub decompressed_len: usize,
+    pub free_space: usize,
+    pub slot: Option<u64>,
+}
+
+/// Like [crate::fetch_idl::fetch_idl], keeping the IDL account's metadata.
+///
+/// Fails with [LensError::IdlAccountNotFound] if there's no IDL account,
+/// and [LensError::InvalidIdl] if its data can't be read.
+pub fn fetch_idl_with_metadata<S: AccountSource + ?Sized>(
+    client: &S,
+    idl_addr: &Pubkey,
+) -> Result<FetchedIdl, LensError> {
+    let (address, account, slot) =
+        find_idl_account_with_slot(client, idl_addr)?.ok_or(LensError::IdlAccountNotFound {
+            program_id: *idl_addr,
+        })?;
+    Ok(FetchedIdl::from_account(idl_addr, address, &account, slot)?)
+}
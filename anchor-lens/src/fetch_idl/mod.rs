@@ -1,43 +1,121 @@
+pub mod cache;
+pub mod codama;
 pub mod discriminators;
+pub mod disk_cache;
+pub mod history;
+pub mod info;
+pub mod registry;
+pub mod shank;
+pub mod versioned;
+pub mod workspace;
 
+use crate::error::LensError;
+use crate::source::AccountSource;
 use anchor_lang::idl::IdlAccount;
-use anyhow::anyhow;
 use borsh::BorshDeserialize as AnchorDeserialize;
 use flate2::read::ZlibDecoder;
-use solana_client::rpc_client::RpcClient;
 use solana_program::pubkey::Pubkey;
-use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::account::Account;
 use std::io::Read;
 
+pub use cache::{CachedIdl, IdlCache};
 pub use discriminators::IdlWithDiscriminators;
+pub use disk_cache::DiskIdlCache;
+pub use history::{fetch_idl_history, IdlHistory};
+pub use info::{fetch_idl_with_metadata, FetchedIdl, IdlAccountInfo};
+pub use registry::IdlRegistry;
+pub use versioned::VersionedIdl;
+pub use workspace::AnchorWorkspace;
 
 /// Fetches an IDL from on-chain account data, if it exists, and returns an
-/// [IdlWithDiscriminators].
-pub fn fetch_idl(client: &RpcClient, idl_addr: &Pubkey) -> anyhow::Result<IdlWithDiscriminators> {
-    let mut account = client
-        .get_account_with_commitment(idl_addr, CommitmentConfig::processed())?
-        .value
-        .map_or(Err(anyhow!("IDL account not found")), Ok)?;
-
-    if account.executable {
-        let idl_addr = IdlAccount::address(idl_addr);
-        account = client
-            .get_account_with_commitment(&idl_addr, CommitmentConfig::processed())?
-            .value
-            .map_or(Err(anyhow!("IDL account not found")), Ok)?;
+/// [IdlWithDiscriminators]. See [fetch_idl_with_metadata] to also get the IDL account's
+/// authority and size.
+///
+/// Fails with [LensError::IdlAccountNotFound] if there's no IDL account,
+/// and [LensError::InvalidIdl] if its data can't be read.
+pub fn fetch_idl<S: AccountSource + ?Sized>(
+    client: &S,
+    idl_addr: &Pubkey,
+) -> Result<IdlWithDiscriminators, LensError> {
+    Ok(fetch_idl_with_metadata(client, idl_addr)?.idl)
+}
+
+/// Finds the IDL account and its address, given either a program ID
+/// or the IDL account address itself. Returns `None` if there's no IDL account.
+pub fn find_idl_account<S: AccountSource + ?Sized>(
+    client: &S,
+    idl_addr: &Pubkey,
+) -> anyhow::Result<Option<(Pubkey, Account)>> {
+    Ok(find_idl_account_with_slot(client, idl_addr)?.map(|(address, account, _)| (address, account)))
+}
+
+/// Like [find_idl_account], along with the slot the IDL account was read at.
+pub fn find_idl_account_with_slot<S: AccountSource + ?Sized>(
+    client: &S,
+    idl_addr: &Pubkey,
+) -> anyhow::Result<Option<(Pubkey, Account, Option<u64>)>> {
+    let (account, slot) = match client.get_idl_account_with_slot(idl_addr)? {
+        (Some(account), slot) => (account, slot),
+        (None, _) => return Ok(None),
+    };
+    if !account.executable {
+        return Ok(Some((*idl_addr, account, slot)));
     }
+    let address = IdlAccount::address(idl_addr);
+    let (account, slot) = client.get_idl_account_with_slot(&address)?;
+    Ok(account.map(|account| (address, account, slot)))
+}
+
+/// Decompresses and parses the data of an IDL account. `idl_addr` is the address
+/// the IDL was requested for, and is only used to report errors.
+pub fn parse_idl_account(idl_addr: &Pubkey, data: &[u8]) -> anyhow::Result<IdlWithDiscriminators> {
+    parse_idl_json(idl_addr, &decompress_idl_account(idl_addr, data)?)
+}
 
-    if account.data.len() < 8 {
-        return Err(anyhow!("IDL account is the wrong size"));
+/// Decompresses the data of an IDL account into IDL JSON.
+pub fn decompress_idl_account(idl_addr: &Pubkey, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let idl_account = deserialize_idl_account(idl_addr, data)?;
+    decompress_idl_data(idl_addr, &idl_account.data)
+}
+
+/// Deserializes the data of an IDL account, leaving the IDL compressed.
+pub fn deserialize_idl_account(idl_addr: &Pubkey, data: &[u8]) -> anyhow::Result<IdlAccount> {
+    let invalid = |reason: &str| LensError::InvalidIdl {
+        program_id: *idl_addr,
+        reason: reason.to_string(),
+    };
+    if data.len() < 8 {
+        return Err(invalid("IDL account is the wrong size").into());
     }
     // Cut off account discriminator.
-    let mut d: &[u8] = &account.data[8..];
-    let idl_account: IdlAccount = AnchorDeserialize::deserialize(&mut d)?;
-
-    let mut z = ZlibDecoder::new(&idl_account.data[..]);
-    let mut s = Vec::new();
-    z.read_to_end(&mut s)?;
-    let idl = serde_json::from_slice(&s[..])
-        .map_err(|_| anyhow!("Could not deserialize decompressed IDL data"))?;
-    Ok(IdlWithDiscriminators::new(idl))
+    let mut d: &[u8] = &data[8..];
+    Ok(AnchorDeserialize::deserialize(&mut d)
+        .map_err(|_| invalid("Could not deserialize IDL account"))?)
+}
+
+/// Decompresses the IDL stored in an IDL account into IDL JSON.
+pub fn decompress_idl_data(idl_addr: &Pubkey, compressed: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Ok(inflate(compressed).ok_or(LensError::InvalidIdl {
+        program_id: *idl_addr,
+        reason: "Could not decompress IDL data".to_string(),
+    })?)
+}
+
+/// Decompresses zlib data, as IDLs are stored.
+pub(crate) fn inflate(compressed: &[u8]) -> Option<Vec<u8>> {
+    let mut data = vec![];
+    ZlibDecoder::new(compressed).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// Parses decompressed IDL JSON, in either the legacy or the Anchor 0.30+ format.
+pub fn parse_idl_json(idl_addr: &Pubkey, json: &[u8]) -> anyhow::Result<IdlWithDiscriminators> {
+    let invalid = |reason: &str| LensError::InvalidIdl {
+        program_id: *idl_addr,
+        reason: reason.to_string(),
+    };
+    let idl = VersionedIdl::from_slice(json).map_err(|err| {
+        invalid(&format!("Could not deserialize decompressed IDL data: {:#}", err))
+    })?;
+    Ok(IdlWithDiscriminators::from_versioned(idl).map_err(|err| invalid(&err.to_string()))?)
 }
@@ -0,0 +1,201 @@
+use crate::fetch_idl::discriminators::IdlWithDiscriminators;
+use crate::fetch_idl::history::IdlHistory;
+use crate::fetch_idl::versioned::VersionedIdl;
+use anyhow::{anyhow, Result};
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// IDLs loaded from outside the chain, keyed by program ID.
+///
+/// [crate::AnchorLens] looks here before fetching an IDL account over RPC, which
+/// makes it possible to decode programs that never published an IDL, or whose
+/// published IDL is older than a local build.
+///
+/// Older versions of an IDL can be registered along with the slot they were deployed in,
+/// to decode old transactions with the IDL that was live at the time.
+#[derive(Debug, Clone, Default)]
+pub struct IdlRegistry {
+    idls: HashMap<Pubkey, Arc<IdlWithDiscriminators>>,
+    versions: HashMap<Pubkey, IdlHistory>,
+}
+
+impl IdlRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an IDL for a program, replacing and returning any previous one.
+    pub fn insert(
+        &mut self,
+        program_id: Pubkey,
+        mut idl: IdlWithDiscriminators,
+    ) -> Option<Arc<IdlWithDiscriminators>> {
+        idl.address = Some(program_id);
+        self.idls.insert(program_id, Arc::new(idl))
+    }
+
+    pub fn get(&self, program_id: &Pubkey) -> Option<&Arc<IdlWithDiscriminators>> {
+        self.idls.get(program_id)
+    }
+
+    /// Registers a version of a program's IDL, deployed at `slot`.
+    pub fn insert_version(&mut self, program_id: Pubkey, slot: u64, mut idl: IdlWithDiscriminators) {
+        idl.address = Some(program_id);
+        self.versions
+            .entry(program_id)
+            .or_default()
+            .insert(slot, Arc::new(idl));
+    }
+
+    /// The version of a program's IDL that was live at `slot`, if any were registered,
+    /// or otherwise its unversioned IDL.
+    pub fn get_at(&self, program_id: &Pubkey, slot: u64) -> Option<&Arc<IdlWithDiscriminators>> {
+        self.versions
+            .get(program_id)
+            .and_then(|versions| versions.at(slot))
+            .or_else(|| self.get(program_id))
+    }
+
+    /// The versions registered for a program with [IdlRegistry::insert_version].
+    pub fn versions(&self, program_id: &Pubkey) -> Option<&IdlHistory> {
+        self.versions.get(program_id)
+    }
+
+    /// Whether an IDL, or any version of one, is registered for a program,
+    /// in which case its on-chain IDL history isn't needed.
+    pub fn is_registered(&self, program_id: &Pubkey) -> bool {
+        self.idls.contains_key(program_id) || self.versions.contains_key(program_id)
+    }
+
+    pub fn contains(&self, program_id: &Pubkey) -> bool {
+        self.idls.contains_key(program_id)
+    }
+
+    pub fn program_ids(&self) -> impl Iterator<Item = &Pubkey> {
+        self.idls.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.idls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idls.is_empty()
+    }
+
+    /// Loads an IDL JSON file, in either the legacy or the Anchor 0.30+ format,
+    /// registering it under the program ID it records. See [VersionedIdl::address].
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<Pubkey> {
+        let path = path.as_ref();
+        let idl = read_idl_file(path)?;
+        let program_id = idl.address.ok_or(anyhow!(
+            "IDL {} does not record its program ID, it must be given explicitly",
+            path.display()
+        ))?;
+        self.insert(program_id, idl);
+        Ok(program_id)
+    }
+
+    /// Loads an IDL JSON file, registering it under `program_id`
+    /// regardless of any program ID it records.
+    pub fn load_file_for(&mut self, program_id: Pubkey, path: impl AsRef<Path>) -> Result<()> {
+        let idl = read_idl_file(path.as_ref())?;
+        self.insert(program_id, idl);
+        Ok(())
+    }
+
+    /// Loads an IDL JSON file as the version of a program's IDL deployed at `slot`.
+    pub fn load_file_at(
+        &mut self,
+        program_id: Pubkey,
+        slot: u64,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let idl = read_idl_file(path.as_ref())?;
+        self.insert_version(program_id, slot, idl);
+        Ok(())
+    }
+
+    /// Loads every `.json` file in a directory, such as an Anchor workspace's `target/idl`.
+    ///
+    /// Each IDL is registered under the program ID in `program_ids` keyed by
+    /// its program name or file stem, or otherwise the program ID it records.
+    /// Returns the program IDs that were loaded, in file name order.
+    ///
+    /// Fails if an IDL has no program ID, see [IdlRegistry::load_dir_skipping_unknown]
+    /// to skip it instead.
+    pub fn load_dir(
+        &mut self,
+        dir: impl AsRef<Path>,
+        program_ids: &HashMap<String, Pubkey>,
+    ) -> Result<Vec<Pubkey>> {
+        let (loaded, skipped) = self.load_dir_skipping_unknown(dir, program_ids)?;
+        match skipped.first() {
+            Some(skipped) => Err(anyhow!(
+                "IDL {} does not record its program ID, and none was given for {}",
+                skipped.path.display(),
+                skipped.name
+            )),
+            None => Ok(loaded),
+        }
+    }
+
+    /// Like [IdlRegistry::load_dir], but IDLs with no program ID, like legacy IDLs
+    /// of programs that haven't been deployed, are skipped. Returns the program IDs
+    /// that were loaded, and the IDLs that were skipped.
+    pub fn load_dir_skipping_unknown(
+        &mut self,
+        dir: impl AsRef<Path>,
+        program_ids: &HashMap<String, Pubkey>,
+    ) -> Result<(Vec<Pubkey>, Vec<SkippedIdl>)> {
+        let dir = dir.as_ref();
+        let mut paths = fs::read_dir(dir)
+            .map_err(|e| anyhow!("Could not read IDL directory {}: {}", dir.display(), e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        paths.retain(|path| path.extension().map_or(false, |ext| ext == "json"));
+        paths.sort();
+        let mut loaded = vec![];
+        let mut skipped = vec![];
+        for path in paths {
+            let idl = read_idl_file(&path)?;
+            let stem = path.file_stem().and_then(|stem| stem.to_str());
+            let program_id = program_ids
+                .get(&idl.name)
+                .or_else(|| stem.and_then(|stem| program_ids.get(stem)))
+                .copied()
+                .or(idl.address);
+            match program_id {
+                Some(program_id) => {
+                    self.insert(program_id, idl);
+                    loaded.push(program_id);
+                }
+                None => skipped.push(SkippedIdl {
+                    path,
+                    name: idl.name.clone(),
+                }),
+            }
+        }
+        Ok((loaded, skipped))
+    }
+}
+
+/// An IDL file skipped by [IdlRegistry::load_dir_skipping_unknown] for having no program ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedIdl {
+    pub path: PathBuf,
+    /// The program name in the IDL, which a program ID can be given for.
+    pub name: String,
+}
+
+/// Reads an IDL JSON file, in either the legacy or the Anchor 0.30+ format.
+pub fn read_idl_file(path: &Path) -> Result<IdlWithDiscriminators> {
+    let data =
+        fs::read(path).map_err(|e| anyhow!("Could not read IDL file {}: {}", path.display(), e))?;
+    let idl = VersionedIdl::from_slice(&data)
+        .map_err(|e| anyhow!("Could not parse IDL file {}: {}", path.display(), e))?;
+    IdlWithDiscriminators::from_versioned(idl)
+}
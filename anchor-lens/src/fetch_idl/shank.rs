@@ -0,0 +1,299 @@
+use crate::fetch_idl::discriminators::ExplicitDiscriminators;
+use anchor_syn::idl::Idl;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// An IDL generated by Shank for a native Borsh program. Shank IDLs follow the legacy
+/// Anchor layout, marked with `metadata.origin: "shank"`, but each instruction states a
+/// `discriminant`, usually a single `u8`, rather than using a hashed discriminator.
+///
+/// Accounts don't state a discriminator. Programs following Metaplex's convention start
+/// each account with a `key` enum field, whose variant is named after the account, e.g.
+/// `Key::MetadataV1` for `Metadata`, and that variant's index is used as the account's
+/// discriminator. Like Anchor's, it's skipped before decoding, so the field is dropped
+/// from the account's layout. Other accounts have no discriminator, and are decoded as a
+/// named type with [crate::deserialize::deserialize_account_as_type].
+///
+/// Shank also writes tuples, maps and sets, which the legacy format has no types for.
+/// Tuples and map entries are given type definitions named after where they appear,
+/// e.g. `createMetadata.data`, and sets become vectors, which Borsh encodes the same way.
+#[derive(Debug, Clone)]
+pub struct ShankIdl {
+    pub idl: Idl,
+    pub address: Option<Pubkey>,
+    pub discriminators: ExplicitDiscriminators,
+}
+
+impl ShankIdl {
+    pub fn is_shank(value: &Value) -> bool {
+        value
+            .get("metadata")
+            .and_then(|metadata| metadata.get("origin"))
+            .and_then(Value::as_str)
+            == Some("shank")
+    }
+
+    pub fn from_value(mut value: Value) -> Result<Self> {
+        let mut discriminators = ExplicitDiscriminators {
+            exhaustive: true,
+            ..Default::default()
+        };
+        let mut generated = vec![];
+        if let Some(instructions) = value.get_mut("instructions").and_then(Value::as_array_mut) {
+            for ix in instructions {
+                let name = ix
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or(anyhow!("Shank instruction has no name"))?
+                    .to_string();
+                if let Some(discriminant) =
+                    ix.as_object_mut().and_then(|ix| ix.remove("discriminant"))
+                {
+                    discriminators
+                        .instructions
+                        .insert(name.clone(), discriminant_bytes(&discriminant)?);
+                }
+                rewrite_fields(ix.get_mut("args"), &name, &mut generated)?;
+            }
+        }
+        let types = value
+            .get("types")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(accounts) = value.get_mut("accounts").and_then(Value::as_array_mut) {
+            for account in accounts {
+                if let Some((name, discriminator)) = key_discriminator(account, &types) {
+                    discriminators.accounts.insert(name, discriminator);
+                }
+            }
+        }
+        for key in ["accounts", "types"] {
+            if let Some(type_defs) = value.get_mut(key).and_then(Value::as_array_mut) {
+                for type_def in type_defs {
+                    rewrite_type_def(type_def, &mut generated)?;
+                }
+            }
+        }
+        if let Some(types) = value.get_mut("types").and_then(Value::as_array_mut) {
+            types.append(&mut generated);
+        } else if !generated.is_empty() {
+            value["types"] = Value::Array(generated);
+        }
+        let idl: Idl = serde_json::from_value(value)?;
+        let address = idl
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("address"))
+            .and_then(Value::as_str)
+            .and_then(|address| Pubkey::from_str(address).ok());
+        Ok(Self {
+            idl,
+            address,
+            discriminators,
+        })
+    }
+}
+
+/// Encodes a `{"type": "u8", "value": 3}` discriminant, little endian.
+fn discriminant_bytes(discriminant: &Value) -> Result<Vec<u8>> {
+    let value = discriminant
+        .get("value")
+        .and_then(Value::as_u64)
+        .ok_or(anyhow!(
+            "Discriminant {} has no integer value",
+            discriminant
+        ))?;
+    let width = match discriminant
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("u8")
+    {
+        "u8" => 1,
+        "u16" => 2,
+        "u32" => 4,
+        "u64" => 8,
+        other => return Err(anyhow!("Unsupported discriminant type: {}", other)),
+    };
+    Ok(value.to_le_bytes()[..width].to_vec())
+}
+
+/// The discriminator of an account that starts with a `key` or `kind` enum field,
+/// which is removed from the account's fields, see [ShankIdl].
+fn key_discriminator(account: &mut Value, types: &[Value]) -> Option<(String, Vec<u8>)> {
+    let name = account.get("name")?.as_str()?.to_string();
+    let fields = account.get_mut("type")?.get_mut("fields")?.as_array_mut()?;
+    let first = fields.first()?;
+    if !matches!(first.get("name")?.as_str()?, "key" | "kind") {
+        return None;
+    }
+    let enum_name = first.get("type")?.get("defined")?.as_str()?;
+    let enum_type = types
+        .iter()
+        .find(|ty| ty.get("name").and_then(Value::as_str) == Some(enum_name))?
+        .get("type")?;
+    if enum_type.get("kind")?.as_str()? != "enum" {
+        return None;
+    }
+    let variants = enum_type
+        .get("variants")?
+        .as_array()?
+        .iter()
+        .map(|variant| {
+            variant
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let index = u8::try_from(account_variant(&name, &variants)?).ok()?;
+    fields.remove(0);
+    Some((name, vec![index]))
+}
+
+/// The variant named after the account, or else the latest version of it,
+/// e.g. `MasterEditionV2` for `MasterEdition`.
+fn account_variant(account: &str, variants: &[&str]) -> Option<usize> {
+    if let Some(index) = variants
+        .iter()
+        .position(|variant| variant.eq_ignore_ascii_case(account))
+    {
+        return Some(index);
+    }
+    variants
+        .iter()
+        .enumerate()
+        .filter_map(|(index, variant)| {
+            let prefix = variant.get(..account.len())?;
+            if !prefix.eq_ignore_ascii_case(account) {
+                return None;
+            }
+            let version = variant[account.len()..]
+                .strip_prefix('V')?
+                .parse::<u32>()
+                .ok()?;
+            Some((version, index))
+        })
+        .max()
+        .map(|(_, index)| index)
+}
+
+fn rewrite_type_def(type_def: &mut Value, generated: &mut Vec<Value>) -> Result<()> {
+    let name = type_def
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or(anyhow!("Shank type has no name"))?
+        .to_string();
+    let ty = match type_def.get_mut("type") {
+        Some(ty) => ty,
+        None => return Ok(()),
+    };
+    rewrite_fields(ty.get_mut("fields"), &name, generated)?;
+    if let Some(variants) = ty.get_mut("variants").and_then(Value::as_array_mut) {
+        for variant in variants {
+            let path = format!(
+                "{}.{}",
+                name,
+                variant
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            );
+            // Variant fields are either named fields, or a tuple of bare types.
+            if let Some(fields) = variant.get_mut("fields").and_then(Value::as_array_mut) {
+                for (i, field) in fields.iter_mut().enumerate() {
+                    if field.get("name").is_some() {
+                        rewrite_field(field, &path, generated)?;
+                    } else {
+                        rewrite_type(field, &format!("{}.{}", path, i), generated)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_fields(
+    fields: Option<&mut Value>,
+    parent: &str,
+    generated: &mut Vec<Value>,
+) -> Result<()> {
+    if let Some(fields) = fields.and_then(Value::as_array_mut) {
+        for field in fields {
+            rewrite_field(field, parent, generated)?;
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_field(field: &mut Value, parent: &str, generated: &mut Vec<Value>) -> Result<()> {
+    let path = format!(
+        "{}.{}",
+        parent,
+        field
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    );
+    match field.get_mut("type") {
+        Some(ty) => rewrite_type(ty, &path, generated),
+        None => Ok(()),
+    }
+}
+
+/// Rewrites the types Shank has and the legacy format doesn't, see [ShankIdl].
+fn rewrite_type(ty: &mut Value, path: &str, generated: &mut Vec<Value>) -> Result<()> {
+    let object = match ty.as_object_mut() {
+        Some(object) if object.len() == 1 => object,
+        _ => return Ok(()),
+    };
+    let (key, inner) = object.iter_mut().next().unwrap();
+    match key.clone().as_str() {
+        "option" | "vec" => rewrite_type(inner, path, generated),
+        "array" => match inner.get_mut(0) {
+            Some(item) => rewrite_type(item, path, generated),
+            None => Ok(()),
+        },
+        "hashSet" | "bTreeSet" => {
+            let mut item = inner.take();
+            rewrite_type(&mut item, path, generated)?;
+            *ty = json!({ "vec": item });
+            Ok(())
+        }
+        "tuple" => {
+            let items = inner
+                .as_array_mut()
+                .ok_or(anyhow!("{}: tuple is not a list", path))?;
+            let mut fields = vec![];
+            for (i, item) in items.iter_mut().enumerate() {
+                rewrite_type(item, &format!("{}.{}", path, i), generated)?;
+                fields.push(json!({ "name": i.to_string(), "type": item.take() }));
+            }
+            *ty = generate(path, fields, generated);
+            Ok(())
+        }
+        "hashMap" | "bTreeMap" => {
+            let entry = inner
+                .as_array_mut()
+                .filter(|entry| entry.len() == 2)
+                .ok_or(anyhow!("{}: map doesn't list a key and a value type", path))?;
+            rewrite_type(&mut entry[0], &format!("{}.key", path), generated)?;
+            rewrite_type(&mut entry[1], &format!("{}.value", path), generated)?;
+            let fields = vec![
+                json!({ "name": "key", "type": entry[0].take() }),
+                json!({ "name": "value", "type": entry[1].take() }),
+            ];
+            *ty = json!({ "vec": generate(&format!("{}.entry", path), fields, generated) });
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn generate(name: &str, fields: Vec<Value>, generated: &mut Vec<Value>) -> Value {
+    generated.push(json!({ "name": name, "type": { "kind": "struct", "fields": fields } }));
+    json!({ "defined": name })
+}
@@ -0,0 +1,631 @@
+use crate::fetch_idl::codama::CodamaIdl;
+use crate::fetch_idl::discriminators::ExplicitDiscriminators;
+use crate::fetch_idl::shank::ShankIdl;
+use anchor_lang_idl_spec as spec;
+use anchor_syn::idl::{
+    EnumFields, Idl, IdlAccount, IdlAccountItem, IdlAccounts, IdlConst, IdlEnumVariant,
+    IdlErrorCode, IdlEvent, IdlEventField, IdlField, IdlInstruction, IdlType, IdlTypeDefinition,
+    IdlTypeDefinitionTy,
+};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// An IDL in either of the two JSON formats Anchor has published over time,
+/// or one written by Shank or Codama for a native Borsh program.
+///
+/// Anchor 0.30 replaced the IDL format with a new specification (`metadata.spec`),
+/// which carries the program `address`, explicit `discriminator` byte arrays,
+/// generics, and a `serialization` field on every type. Everything downstream of
+/// IDL parsing in this crate works on the legacy [anchor_syn::idl::Idl] representation,
+/// so new-spec IDLs are converted into that shape with [VersionedIdl::into_legacy].
+/// Shank and Codama IDLs are converted as soon as they're parsed.
+#[derive(Debug, Clone)]
+pub enum VersionedIdl {
+    /// An IDL produced by Anchor versions prior to 0.30.
+    Legacy(Idl),
+    /// An IDL following the Anchor 0.30+ specification.
+    Spec(spec::Idl),
+    /// An IDL generated by Shank, see [ShankIdl].
+    Shank(ShankIdl),
+    /// An IDL written by Codama or Kinobi, see [CodamaIdl].
+    Codama(CodamaIdl),
+}
+
+impl VersionedIdl {
+    /// Parses raw IDL JSON, detecting which format it is written in.
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let value: Value = serde_json::from_slice(data)?;
+        Self::from_value(value)
+    }
+
+    /// Detects the IDL format from already-parsed JSON. New-spec IDLs
+    /// always carry a `metadata.spec` version string, legacy ones never do.
+    /// Shank IDLs are legacy ones with a `metadata.origin` of `shank`,
+    /// and Codama IDLs are a tree of nodes, starting from a `rootNode`.
+    pub fn from_value(value: Value) -> Result<Self> {
+        let is_spec = value
+            .get("metadata")
+            .and_then(|metadata| metadata.get("spec"))
+            .is_some();
+        if CodamaIdl::is_codama(&value) {
+            Ok(Self::Codama(CodamaIdl::from_value(&value)?))
+        } else if ShankIdl::is_shank(&value) {
+            Ok(Self::Shank(ShankIdl::from_value(value)?))
+        } else if is_spec {
+            Ok(Self::Spec(serde_json::from_value(value)?))
+        } else {
+            Ok(Self::Legacy(serde_json::from_value(value)?))
+        }
+    }
+
+    /// The program ID this IDL belongs to, if the IDL records it.
+    /// New-spec IDLs store it as `address`, legacy IDLs only have it
+    /// when `anchor build` wrote it to `metadata.address`.
+    pub fn address(&self) -> Option<Pubkey> {
+        let address = match self {
+            Self::Legacy(idl) => idl.metadata.as_ref()?.get("address")?.as_str()?,
+            Self::Spec(idl) => idl.address.as_str(),
+            Self::Shank(idl) => return idl.address,
+            Self::Codama(idl) => return idl.address,
+        };
+        Pubkey::from_str(address).ok()
+    }
+
+    /// The discriminators the IDL states outright. Legacy IDLs have none,
+    /// their discriminators are always derived from item names. Shank and Codama
+    /// items without a discriminator have none at all.
+    pub fn explicit_discriminators(&self) -> ExplicitDiscriminators {
+        match self {
+            Self::Legacy(_) => ExplicitDiscriminators::default(),
+            Self::Shank(idl) => idl.discriminators.clone(),
+            Self::Codama(idl) => idl.discriminators.clone(),
+            Self::Spec(idl) => ExplicitDiscriminators {
+                instructions: idl
+                    .instructions
+                    .iter()
+                    .map(|ix| (ix.name.clone(), ix.discriminator.clone()))
+                    .collect(),
+                accounts: idl
+                    .accounts
+                    .iter()
+                    .map(|account| (account.name.clone(), account.discriminator.clone()))
+                    .collect(),
+                events: idl
+                    .events
+                    .iter()
+                    .map(|event| (event.name.clone(), event.discriminator.clone()))
+                    .collect(),
+                exhaustive: false,
+            },
+        }
+    }
+
+    /// Converts the IDL into the legacy representation, which is a no-op for
+    /// legacy IDLs. See [SpecConverter] for how new-spec concepts are mapped.
+    pub fn into_legacy(self) -> Result<Idl> {
+        match self {
+            Self::Legacy(idl) => Ok(idl),
+            Self::Shank(idl) => Ok(idl.idl),
+            Self::Codama(idl) => Ok(idl.idl),
+            Self::Spec(idl) => SpecConverter::new(&idl.types).convert_idl(&idl),
+        }
+    }
+}
+
+/// A concrete value substituted for a generic parameter
+/// while instantiating a generic type definition.
+#[derive(Debug, Clone)]
+enum GenericValue {
+    Type(IdlType),
+    Const(String),
+}
+
+/// The size and alignment of a zero-copy type, and whether it contains padding.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    size: usize,
+    align: usize,
+    padded: bool,
+}
+
+/// Converts a new-spec IDL into the legacy [anchor_syn::idl::Idl] shape.
+///
+/// Most of the mapping is one-to-one, but the legacy format has no notion of
+/// generics or type aliases, so:
+///
+/// - Type aliases are inlined wherever they are referenced.
+/// - Every distinct use of a generic type, e.g. `Pool<u64, 8>`, is instantiated into
+///  its own concrete type definition named after the use site, with all generic
+///  parameters (including const array lengths) substituted.
+/// - `u256` and `i256` have no legacy equivalent and become `[u8; 32]`.
+///
+/// Zero-copy (`bytemuck`) types are converted like any other, which is only correct when
+/// their fields are laid out back to back in little endian, as Borsh would read them.
+/// `repr(C)` inserts padding to align fields, which `Pod` types can't have, but
+/// `zero_copy(unsafe)` types can; types whose layout has padding are rejected.
+struct SpecConverter<'a> {
+    types: &'a [spec::IdlTypeDef],
+    /// Instantiations of generic type definitions, accumulated during conversion.
+    instantiated: Vec<IdlTypeDefinition>,
+    instantiated_names: HashSet<String>,
+    /// Zero-copy types whose layout has padding, left out of the converted IDL.
+    padded: HashSet<String>,
+}
+
+impl<'a> SpecConverter<'a> {
+    fn new(types: &'a [spec::IdlTypeDef]) -> Self {
+        Self {
+            types,
+            instantiated: vec![],
+            instantiated_names: HashSet::new(),
+            padded: HashSet::new(),
+        }
+    }
+
+    fn convert_idl(mut self, idl: &spec::Idl) -> Result<Idl> {
+        let env = HashMap::new();
+        let constants = idl
+            .constants
+            .iter()
+            .map(|constant| {
+                Ok(IdlConst {
+                    name: constant.name.clone(),
+                    ty: self.convert_type(&constant.ty, &env)?,
+                    value: constant.value.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let instructions = idl
+            .instructions
+            .iter()
+            .map(|ix| self.convert_instruction(ix))
+            .collect::<Result<Vec<_>>>()?;
+        let accounts = idl
+            .accounts
+            .iter()
+            .map(|account| self.convert_named_type_def(&account.name))
+            .collect::<Result<Vec<_>>>()?;
+        let events = idl
+            .events
+            .iter()
+            .map(|event| self.convert_event(&event.name))
+            .collect::<Result<Vec<_>>>()?;
+        // Generic definitions and aliases can't be represented directly,
+        // they are only reachable through their instantiations.
+        let mut types = vec![];
+        let type_defs = self.types;
+        for type_def in type_defs {
+            if type_def.generics.is_empty()
+                && !matches!(type_def.ty, spec::IdlTypeDefTy::Type { .. })
+            {
+                types.push(self.convert_type_def(type_def, type_def.name.clone(), &env)?);
+            }
+        }
+        types.append(&mut self.instantiated);
+        // Padded types would decode as garbage, so anything using them
+        // fails to decode with an undefined type instead.
+        let accounts = accounts
+            .into_iter()
+            .filter(|account| !self.padded.contains(&account.name))
+            .collect();
+        types.retain(|type_def| !self.padded.contains(&type_def.name));
+        let errors = idl
+            .errors
+            .iter()
+            .map(|error| IdlErrorCode {
+                code: error.code,
+                name: error.name.clone(),
+                msg: error.msg.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut metadata = serde_json::to_value(&idl.metadata)?;
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.insert("address".to_string(), Value::String(idl.address.clone()));
+        }
+        Ok(Idl {
+            version: idl.metadata.version.clone(),
+            name: idl.metadata.name.clone(),
+            docs: non_empty(idl.docs.clone()),
+            constants,
+            instructions,
+            state: None,
+            accounts,
+            types,
+            events: non_empty(events),
+            errors: non_empty(errors),
+            metadata: Some(metadata),
+        })
+    }
+
+    fn convert_instruction(&mut self, ix: &spec::IdlInstruction) -> Result<IdlInstruction> {
+        let env = HashMap::new();
+        Ok(IdlInstruction {
+            name: ix.name.clone(),
+            docs: non_empty(ix.docs.clone()),
+            accounts: ix.accounts.iter().map(convert_account_item).collect(),
+            args: self.convert_fields(&ix.args, &env)?,
+            returns: ix
+                .returns
+                .as_ref()
+                .map(|ty| self.convert_type(ty, &env))
+                .transpose()?,
+        })
+    }
+
+    /// Accounts and events only reference their layout by name in the new spec,
+    /// the layout itself lives in the `types` block.
+    fn find_type_def(&self, name: &str) -> Result<&'a spec::IdlTypeDef> {
+        self.types
+            .iter()
+            .find(|type_def| type_def.name == name)
+            .ok_or(anyhow!("Couldn't find defined type: {}", name))
+    }
+
+    fn convert_named_type_def(&mut self, name: &str) -> Result<IdlTypeDefinition> {
+        let type_def = self.find_type_def(name)?;
+        self.convert_type_def(type_def, name.to_string(), &HashMap::new())
+    }
+
+    fn convert_event(&mut self, name: &str) -> Result<IdlEvent> {
+        let IdlTypeDefinition { ty, .. } = self.convert_named_type_def(name)?;
+        let fields = match ty {
+            IdlTypeDefinitionTy::Struct { fields } => fields
+                .into_iter()
+                .map(|field| IdlEventField {
+                    name: field.name,
+                    ty: field.ty,
+                    index: false,
+                })
+                .collect(),
+            IdlTypeDefinitionTy::Enum { .. } => {
+                return Err(anyhow!("Event {} is not a struct", name));
+            }
+        };
+        Ok(IdlEvent {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    fn convert_type_def(
+        &mut self,
+        type_def: &spec::IdlTypeDef,
+        name: String,
+        env: &HashMap<String, GenericValue>,
+    ) -> Result<IdlTypeDefinition> {
+        let ty = match &type_def.ty {
+            spec::IdlTypeDefTy::Struct { fields } => IdlTypeDefinitionTy::Struct {
+                fields: match fields {
+                    None => vec![],
+                    Some(spec::IdlDefinedFields::Named(fields)) => {
+                        self.convert_fields(fields, env)?
+                    }
+                    // Tuple structs are named by position, the way Rust accesses them.
+                    Some(spec::IdlDefinedFields::Tuple(types)) => types
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| {
+                            Ok(IdlField {
+                                name: i.to_string(),
+                                docs: None,
+                                ty: self.convert_type(ty, env)?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                },
+            },
+            spec::IdlTypeDefTy::Enum { variants } => IdlTypeDefinitionTy::Enum {
+                variants: variants
+                    .iter()
+                    .map(|variant| {
+                        Ok(IdlEnumVariant {
+                            name: variant.name.clone(),
+                            fields: match &variant.fields {
+                                None => None,
+                                Some(spec::IdlDefinedFields::Named(fields)) => {
+                                    Some(EnumFields::Named(self.convert_fields(fields, env)?))
+                                }
+                                Some(spec::IdlDefinedFields::Tuple(types)) => {
+                                    Some(EnumFields::Tuple(
+                                        types
+                                            .iter()
+                                            .map(|ty| self.convert_type(ty, env))
+                                            .collect::<Result<Vec<_>>>()?,
+                                    ))
+                                }
+                            },
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            },
+            spec::IdlTypeDefTy::Type { .. } => {
+                return Err(anyhow!(
+                    "Type alias {} can't be used as a type definition",
+                    type_def.name
+                ));
+            }
+        };
+        if let (
+            spec::IdlSerialization::Bytemuck | spec::IdlSerialization::BytemuckUnsafe,
+            IdlTypeDefinitionTy::Struct { fields },
+        ) = (&type_def.serialization, &ty)
+        {
+            if let Some(layout) = self.struct_layout(fields, type_def.repr.as_ref())? {
+                if layout.padded && self.padded.insert(name.clone()) {
+                    log::warn!(
+                        "Zero-copy type {} has padding in its layout, which can't be decoded",
+                        name
+                    );
+                }
+            }
+        }
+        Ok(IdlTypeDefinition {
+            name,
+            docs: non_empty(type_def.docs.clone()),
+            ty,
+        })
+    }
+
+    /// The in-memory layout of a zero-copy struct, as on the SBF target.
+    /// `None` if a field's layout isn't known, e.g. a type missing from the IDL.
+    fn struct_layout(
+        &mut self,
+        fields: &[IdlField],
+        repr: Option<&spec::IdlRepr>,
+    ) -> Result<Option<Layout>> {
+        let modifier = match repr {
+            Some(spec::IdlRepr::C(modifier) | spec::IdlRepr::Rust(modifier)) => Some(modifier),
+            _ => None,
+        };
+        let packed = matches!(modifier, Some(modifier) if modifier.packed);
+        let mut layout = Layout {
+            size: 0,
+            align: modifier.and_then(|modifier| modifier.align).unwrap_or(1),
+            padded: false,
+        };
+        for field in fields {
+            let field = match self.type_layout(&field.ty)? {
+                Some(field) => field,
+                None => return Ok(None),
+            };
+            let align = if packed { 1 } else { field.align };
+            let offset = align_up(layout.size, align);
+            layout.padded |= field.padded || offset != layout.size;
+            layout.size = offset + field.size;
+            layout.align = layout.align.max(align);
+        }
+        let size = align_up(layout.size, layout.align);
+        layout.padded |= size != layout.size;
+        layout.size = size;
+        Ok(Some(layout))
+    }
+
+    fn type_layout(&mut self, ty: &IdlType) -> Result<Option<Layout>> {
+        let primitive = |size| Layout {
+            size,
+            align: size,
+            padded: false,
+        };
+        Ok(Some(match ty {
+            IdlType::Bool | IdlType::U8 | IdlType::I8 => primitive(1),
+            IdlType::U16 | IdlType::I16 => primitive(2),
+            IdlType::U32 | IdlType::I32 | IdlType::F32 => primitive(4),
+            IdlType::U64 | IdlType::I64 | IdlType::F64 => primitive(8),
+            IdlType::U128 | IdlType::I128 => primitive(16),
+            IdlType::PublicKey => Layout {
+                size: 32,
+                align: 1,
+                padded: false,
+            },
+            IdlType::Array(inner, len) => match self.type_layout(inner)? {
+                Some(inner) => Layout {
+                    size: inner.size * len,
+                    ..inner
+                },
+                None => return Ok(None),
+            },
+            IdlType::Defined(name) => {
+                let instance = self
+                    .instantiated
+                    .iter()
+                    .find(|type_def| type_def.name == *name)
+                    .cloned();
+                // Instantiations of generic types are named after their definition.
+                let def_name = name.split('<').next().unwrap_or(name);
+                let type_def = match self.types.iter().find(|type_def| type_def.name == def_name) {
+                    Some(type_def) => type_def,
+                    None => return Ok(None),
+                };
+                let instance = match instance {
+                    Some(instance) => instance,
+                    None if type_def.generics.is_empty() => {
+                        self.convert_type_def(type_def, name.clone(), &HashMap::new())?
+                    }
+                    None => return Ok(None),
+                };
+                match &instance.ty {
+                    IdlTypeDefinitionTy::Struct { fields } => {
+                        return self.struct_layout(fields, type_def.repr.as_ref())
+                    }
+                    IdlTypeDefinitionTy::Enum { .. } => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    fn convert_fields(
+        &mut self,
+        fields: &[spec::IdlField],
+        env: &HashMap<String, GenericValue>,
+    ) -> Result<Vec<IdlField>> {
+        fields
+            .iter()
+            .map(|field| {
+                Ok(IdlField {
+                    name: field.name.clone(),
+                    docs: non_empty(field.docs.clone()),
+                    ty: self.convert_type(&field.ty, env)?,
+                })
+            })
+            .collect()
+    }
+
+    fn convert_type(
+        &mut self,
+        ty: &spec::IdlType,
+        env: &HashMap<String, GenericValue>,
+    ) -> Result<IdlType> {
+        Ok(match ty {
+            spec::IdlType::Bool => IdlType::Bool,
+            spec::IdlType::U8 => IdlType::U8,
+            spec::IdlType::I8 => IdlType::I8,
+            spec::IdlType::U16 => IdlType::U16,
+            spec::IdlType::I16 => IdlType::I16,
+            spec::IdlType::U32 => IdlType::U32,
+            spec::IdlType::I32 => IdlType::I32,
+            spec::IdlType::F32 => IdlType::F32,
+            spec::IdlType::U64 => IdlType::U64,
+            spec::IdlType::I64 => IdlType::I64,
+            spec::IdlType::F64 => IdlType::F64,
+            spec::IdlType::U128 => IdlType::U128,
+            spec::IdlType::I128 => IdlType::I128,
+            spec::IdlType::U256 | spec::IdlType::I256 => {
+                IdlType::Array(Box::new(IdlType::U8), 32)
+            }
+            spec::IdlType::Bytes => IdlType::Bytes,
+            spec::IdlType::String => IdlType::String,
+            spec::IdlType::Pubkey => IdlType::PublicKey,
+            spec::IdlType::Option(inner) => IdlType::Option(Box::new(self.convert_type(inner, env)?)),
+            spec::IdlType::Vec(inner) => IdlType::Vec(Box::new(self.convert_type(inner, env)?)),
+            spec::IdlType::Array(inner, len) => {
+                let len = match len {
+                    spec::IdlArrayLen::Value(len) => *len,
+                    spec::IdlArrayLen::Generic(generic) => match env.get(generic) {
+                        Some(GenericValue::Const(value)) => value.parse()?,
+                        _ => return Err(anyhow!("Unresolved array length: {}", generic)),
+                    },
+                };
+                IdlType::Array(Box::new(self.convert_type(inner, env)?), len)
+            }
+            spec::IdlType::Generic(generic) => match env.get(generic) {
+                Some(GenericValue::Type(ty)) => ty.clone(),
+                _ => return Err(anyhow!("Unresolved generic type: {}", generic)),
+            },
+            spec::IdlType::Defined { name, generics } => {
+                self.convert_defined(name, generics, env)?
+            }
+            _ => return Err(anyhow!("Unsupported IDL type: {:?}", ty)),
+        })
+    }
+
+    /// Resolves a reference to a defined type, inlining aliases and
+    /// instantiating generic definitions as needed.
+    fn convert_defined(
+        &mut self,
+        name: &str,
+        generics: &[spec::IdlGenericArg],
+        env: &HashMap<String, GenericValue>,
+    ) -> Result<IdlType> {
+        // Types from other crates may not be included in the IDL at all,
+        // in which case deserialization reports it when it gets there.
+        let type_def = match self.types.iter().find(|type_def| type_def.name == name) {
+            Some(type_def) => type_def,
+            None => return Ok(IdlType::Defined(name.to_string())),
+        };
+        if type_def.generics.is_empty() {
+            return match &type_def.ty {
+                spec::IdlTypeDefTy::Type { alias } => self.convert_type(alias, &HashMap::new()),
+                _ => Ok(IdlType::Defined(name.to_string())),
+            };
+        }
+        if type_def.generics.len() != generics.len() {
+            return Err(anyhow!(
+                "Type {} expects {} generic arguments, found {}",
+                name,
+                type_def.generics.len(),
+                generics.len()
+            ));
+        }
+        let mut inner_env = HashMap::new();
+        let mut labels = vec![];
+        for (param, arg) in type_def.generics.iter().zip(generics) {
+            let value = match arg {
+                spec::IdlGenericArg::Type { ty } => GenericValue::Type(self.convert_type(ty, env)?),
+                spec::IdlGenericArg::Const { value } => GenericValue::Const(value.clone()),
+            };
+            labels.push(match &value {
+                GenericValue::Type(ty) => type_label(ty),
+                GenericValue::Const(value) => value.clone(),
+            });
+            let param_name = match param {
+                spec::IdlTypeDefGeneric::Type { name } => name,
+                spec::IdlTypeDefGeneric::Const { name, .. } => name,
+            };
+            inner_env.insert(param_name.clone(), value);
+        }
+        if let spec::IdlTypeDefTy::Type { alias } = &type_def.ty {
+            return self.convert_type(alias, &inner_env);
+        }
+        let instance_name = format!("{}<{}>", name, labels.join(", "));
+        // Mark the name before converting, so recursive references terminate.
+        if self.instantiated_names.insert(instance_name.clone()) {
+            let instance = self.convert_type_def(type_def, instance_name.clone(), &inner_env)?;
+            self.instantiated.push(instance);
+        }
+        Ok(IdlType::Defined(instance_name))
+    }
+}
+
+fn convert_account_item(item: &spec::IdlInstructionAccountItem) -> IdlAccountItem {
+    match item {
+        spec::IdlInstructionAccountItem::Single(account) => IdlAccountItem::IdlAccount(IdlAccount {
+            name: account.name.clone(),
+            is_mut: account.writable,
+            is_signer: account.signer,
+            docs: non_empty(account.docs.clone()),
+            pda: None,
+            relations: account.relations.clone(),
+        }),
+        spec::IdlInstructionAccountItem::Composite(accounts) => {
+            IdlAccountItem::IdlAccounts(IdlAccounts {
+                name: accounts.name.clone(),
+                accounts: accounts.accounts.iter().map(convert_account_item).collect(),
+            })
+        }
+    }
+}
+
+/// Renders a type the way it would be written in Rust,
+/// for naming instantiations of generic types.
+fn type_label(ty: &IdlType) -> String {
+    match ty {
+        IdlType::Bytes => "Vec<u8>".to_string(),
+        IdlType::PublicKey => "Pubkey".to_string(),
+        IdlType::Defined(name) => name.clone(),
+        IdlType::Option(inner) => format!("Option<{}>", type_label(inner)),
+        IdlType::Vec(inner) => format!("Vec<{}>", type_label(inner)),
+        IdlType::Array(inner, len) => format!("[{}; {}]", type_label(inner), len),
+        primitive => serde_json::to_value(primitive)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default(),
+    }
+}
+
+/// Rounds `offset` up to a multiple of `align`.
+fn align_up(offset: usize, align: usize) -> usize {
+    offset + (align - offset % align) % align
+}
+
+/// The legacy format uses `None` where the new spec uses an empty list.
+fn non_empty<T>(items: Vec<T>) -> Option<Vec<T>> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
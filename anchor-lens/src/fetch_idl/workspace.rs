@@ -0,0 +1,191 @@
+use crate::fetch_idl::registry::IdlRegistry;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The program IDs and IDLs of an Anchor workspace, read from its `Anchor.toml`.
+///
+/// Programs are listed per cluster in `[programs.<cluster>]` sections, and their
+/// IDLs are read from `target/idl/<program>.json` unless the entry names another file.
+#[derive(Debug, Clone)]
+pub struct AnchorWorkspace {
+    /// The directory containing `Anchor.toml`.
+    pub root: PathBuf,
+    /// The cluster under `[provider]`, which Anchor deploys to by default, named
+    /// as in [cluster_name]. `None` if it's an RPC URL of no known cluster.
+    pub provider_cluster: Option<String>,
+    /// Keyed by cluster name, as in [cluster_name], then by program name.
+    pub programs: HashMap<String, HashMap<String, WorkspaceProgram>>,
+}
+
+/// A program listed in `Anchor.toml`.
+#[derive(Debug, Clone)]
+pub struct WorkspaceProgram {
+    pub address: Pubkey,
+    /// An IDL file given explicitly, relative to the workspace root.
+    pub idl: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct AnchorToml {
+    #[serde(default)]
+    programs: HashMap<String, HashMap<String, ProgramEntry>>,
+    provider: Option<Provider>,
+}
+
+#[derive(Deserialize)]
+struct Provider {
+    cluster: Option<String>,
+}
+
+/// Anchor accepts either `name = "<address>"` or `name = { address = "...", idl = "..." }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProgramEntry {
+    Address(String),
+    Details {
+        address: String,
+        idl: Option<String>,
+    },
+}
+
+impl AnchorWorkspace {
+    /// Reads a workspace, given either its root directory or its `Anchor.toml`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (root, manifest) = if path.is_dir() {
+            (path.to_path_buf(), path.join("Anchor.toml"))
+        } else {
+            let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            (root, path.to_path_buf())
+        };
+        let contents = fs::read_to_string(&manifest)
+            .map_err(|e| anyhow!("Could not read {}: {}", manifest.display(), e))?;
+        let anchor_toml: AnchorToml = toml::from_str(&contents)
+            .map_err(|e| anyhow!("Could not parse {}: {}", manifest.display(), e))?;
+        let mut programs = HashMap::new();
+        for (cluster, entries) in anchor_toml.programs {
+            let mut cluster_programs = HashMap::new();
+            for (name, entry) in entries {
+                let (address, idl) = match entry {
+                    ProgramEntry::Address(address) => (address, None),
+                    ProgramEntry::Details { address, idl } => (address, idl.map(PathBuf::from)),
+                };
+                let address = Pubkey::from_str(&address).map_err(|_| {
+                    anyhow!(
+                        "Invalid address for {} in [programs.{}]: {}",
+                        name,
+                        cluster,
+                        address
+                    )
+                })?;
+                cluster_programs.insert(name, WorkspaceProgram { address, idl });
+            }
+            let cluster = cluster_name(&cluster).unwrap_or(cluster);
+            programs.insert(cluster, cluster_programs);
+        }
+        Ok(Self {
+            root,
+            provider_cluster: anchor_toml
+                .provider
+                .and_then(|provider| provider.cluster)
+                .and_then(|cluster| cluster_name(&cluster)),
+            programs,
+        })
+    }
+
+    /// Finds the workspace containing `dir`, checking it and then each of its parents
+    /// for an `Anchor.toml`, the same way the Anchor CLI does.
+    pub fn find(dir: impl AsRef<Path>) -> Result<Option<Self>> {
+        for dir in dir.as_ref().ancestors() {
+            if dir.join("Anchor.toml").is_file() {
+                return Ok(Some(Self::load(dir)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The programs listed for a cluster, keyed by name.
+    pub fn cluster_programs(&self, cluster: &str) -> Option<&HashMap<String, WorkspaceProgram>> {
+        self.programs
+            .get(&cluster_name(cluster).unwrap_or_else(|| cluster.to_string()))
+    }
+
+    /// Where `anchor build` writes the workspace's IDLs.
+    pub fn idl_dir(&self) -> PathBuf {
+        self.root.join("target").join("idl")
+    }
+
+    /// Registers the IDL of every program listed for `cluster`, under its address.
+    ///
+    /// Programs that haven't been built yet have no IDL file, and are skipped,
+    /// unless their `Anchor.toml` entry names an IDL file explicitly.
+    /// Returns the program IDs that were loaded.
+    ///
+    /// Fails if `Anchor.toml` has no `[programs.<cluster>]` section, since program IDs
+    /// usually differ between clusters, and another cluster's would be the wrong ones.
+    pub fn load_idls(&self, cluster: &str, registry: &mut IdlRegistry) -> Result<Vec<Pubkey>> {
+        let mut loaded = vec![];
+        let programs = self.cluster_programs(cluster).ok_or(anyhow!(
+            "{} has no [programs.{}] section",
+            self.root.join("Anchor.toml").display(),
+            cluster
+        ))?;
+        for (name, program) in programs {
+            let path = match &program.idl {
+                Some(idl) => self.root.join(idl),
+                None => {
+                    // Program names in `Anchor.toml` may be written in kebab case,
+                    // but the IDL file is named after the crate's lib name.
+                    let path = self
+                        .idl_dir()
+                        .join(format!("{}.json", name.replace('-', "_")));
+                    if !path.is_file() {
+                        continue;
+                    }
+                    path
+                }
+            };
+            registry.load_file_for(program.address, path)?;
+            loaded.push(program.address);
+        }
+        Ok(loaded)
+    }
+}
+
+/// Names a cluster the way `Anchor.toml` sections do, given a cluster as Anchor
+/// accepts it: a name in any case, its first letter, or an RPC URL.
+/// Returns `None` for a URL of no known cluster, or a name Anchor doesn't know.
+pub fn cluster_name(cluster: &str) -> Option<String> {
+    if cluster.contains("://") {
+        return cluster_for_url(cluster).map(String::from);
+    }
+    let name = match cluster.to_lowercase().as_str() {
+        "l" | "localnet" => "localnet",
+        "d" | "devnet" => "devnet",
+        "t" | "testnet" => "testnet",
+        "m" | "mainnet" | "mainnet-beta" => "mainnet",
+        "debug" => "debug",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Guesses the `Anchor.toml` cluster name for an RPC URL.
+pub fn cluster_for_url(url: &str) -> Option<&'static str> {
+    if url.contains("devnet") {
+        Some("devnet")
+    } else if url.contains("testnet") {
+        Some("testnet")
+    } else if url.contains("mainnet") {
+        Some("mainnet")
+    } else if url.contains("localhost") || url.contains("127.0.0.1") {
+        Some("localnet")
+    } else {
+        None
+    }
+}
@@ -2,32 +2,40 @@
 //!
 //! A deserializer for accounts and instructions that come from Anchor programs.
 //!
-//! ```rust
+//! ```rust,no_run
 //! use solana_client::rpc_client::RpcClient;
 //! use solana_sdk::pubkey;
 //! use solana_anchor_lens::AnchorLens;
+//! use solana_anchor_lens::deserialize::{deserialize_account_from_idl, IdlDeserializedAccount};
 //!
-//! fn main() {
+//! fn main() -> anyhow::Result<()> {
 //!   let client = RpcClient::new("https://api.mainnet-beta.solana.com");
 //!   // This type is the most convenient way to interact with the library,
 //!   // but every step of the process is exposed if you need more fine-grained control.
-//!   // See `AnchorDeserializer::new_with_caching` for IDL caching to save on RPC calls.
+//!   // See `AnchorLens::new_with_idl_caching` for IDL caching to save on RPC calls.
 //!   let deser = AnchorLens::new(client);
 //!   // The Marinade Finance mSOL state account.
 //!   let key = pubkey!("8szGkuLTAux9XMgZ2vtY39jVSowEcpBfFfD8hXSEqdGC");
-//!   let (prog_id, ix_name, json) = deser.fetch_and_deserialize_account_without_idl(&key);
+//!   let IdlDeserializedAccount { program_name, type_name, data, .. } =
+//!       deser.fetch_and_deserialize_account(&key, None)?;
 //!   println!("Found program: {}", program_name);
-//!   println!("Found account type: {}", act_type);
-//!   println!("{}", serde_json::to_string_pretty(&value)?);
+//!   println!("Found account type: {}", type_name);
+//!   println!("{}", serde_json::to_string_pretty(&data)?);
 //!
 //!   // But instead of one big, monolithic call, you can break it up
 //!   // and save your IDL object for subsequent calls.
-//!   let idl = deser.get_idl(pubkey!("MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD"))?;
-//!   let (ix_name, json) = deser.deserialize_account_from_idl(&idl, &key);
+//!   let idl = deser.fetch_idl(&pubkey!("MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD"))?;
+//!   let account = deser.get_account(&key)?;
+//!   let (type_name, json) = deserialize_account_from_idl(&idl, &account)?;
+//!   Ok(())
 //! }
 //! ```
 //!
 pub mod deserialize;
+pub mod error;
 pub mod fetch_idl;
+pub mod nonblocking;
+pub mod source;
 
 pub use deserialize::AnchorLens;
+pub use error::LensError;
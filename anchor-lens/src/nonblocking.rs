@@ -0,0 +1,408 @@
+//! An async counterpart to [crate::AnchorLens], built on the nonblocking RPC client.
+//!
+//! Decoding a transaction needs the IDL of every program it invokes. Rather than
+//! fetching them one after another, [AsyncAnchorLens] fetches them all at once,
+//! then decodes offline with an [AnchorLens] over the fetched data. IDLs are looked
+//! up by [AnchorLens::fetch_idl] too, over accounts fetched ahead of time, so both
+//! lenses go through the same registry, caches and IDL account lookup.
+
+use crate::deserialize::native::native_program_name;
+use crate::deserialize::{HistoricalTransaction, IdlDeserializedAccount, ProgramDecoder, Strictness};
+use crate::error::LensError;
+use crate::fetch_idl::{
+    fetch_idl_history, DiskIdlCache, IdlCache, IdlHistory, IdlRegistry, IdlWithDiscriminators,
+};
+use crate::source::{
+    get_transaction_params, push_signatures_page, signatures_page_config, AccountSource,
+    InMemorySource, SignatureSource, TransactionSource,
+};
+use crate::AnchorLens;
+use anchor_lang::idl::IdlAccount;
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde_json::Value;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
+use solana_program::message::VersionedMessage;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The most transactions fetched at once while reconstructing an IDL history,
+/// so public RPC nodes don't rate limit the requests.
+const MAX_CONCURRENT_TRANSACTIONS: usize = 8;
+
+/// Like [AnchorLens], but every RPC call is async, and the IDLs a transaction
+/// needs are fetched concurrently.
+pub struct AsyncAnchorLens {
+    /// This client is used to get IDLs, account data,
+    /// and historical transaction data.
+    pub client: RpcClient,
+    /// Fetched IDLs, and programs found to have no IDL, when `cache_idls` is set.
+    /// The cache can be shared with an [AnchorLens] by cloning the [Arc].
+    pub idl_cache: Arc<IdlCache>,
+    /// Boolean flag that controls caching of IDLs.
+    pub cache_idls: bool,
+    /// IDLs loaded from files, which take precedence over those published on chain.
+    pub idl_registry: IdlRegistry,
+    /// Optionally keeps fetched IDLs on disk between runs.
+    pub disk_cache: Option<DiskIdlCache>,
+    /// How closely accounts, instructions and events must match their IDL
+    /// to be deserialized. Defaults to [Strictness::Lenient].
+    pub strictness: Strictness,
+    /// See [AnchorLens::decode_idl_writes].
+    pub decode_idl_writes: bool,
+    /// See [AnchorLens::program_decoders].
+    pub program_decoders: HashMap<Pubkey, Arc<dyn ProgramDecoder>>,
+}
+
+impl AsyncAnchorLens {
+    /// Initializes with caching turned off.
+    pub fn new(client: RpcClient) -> Self {
+        Self {
+            client,
+            idl_cache: Arc::new(IdlCache::new()),
+            cache_idls: false,
+            idl_registry: IdlRegistry::new(),
+            disk_cache: None,
+            strictness: Strictness::default(),
+            decode_idl_writes: false,
+            program_decoders: HashMap::new(),
+        }
+    }
+
+    /// Initializes with caching turned on, see [AnchorLens::new_with_idl_caching].
+    pub fn new_with_idl_caching(client: RpcClient) -> Self {
+        Self {
+            cache_idls: true,
+            ..Self::new(client)
+        }
+    }
+
+    /// See [AnchorLens::register_decoder].
+    pub fn register_decoder(&mut self, program_id: Pubkey, decoder: impl ProgramDecoder + 'static) {
+        self.program_decoders.insert(program_id, Arc::new(decoder));
+    }
+
+    /// Attempt to find and fetch the IDL from an address, see [AnchorLens::fetch_idl].
+    /// The IDL is looked up in the same order, and the address and the IDL account
+    /// derived from it are fetched concurrently, in case the address is a program.
+    pub async fn fetch_idl(&self, program_id: &Pubkey) -> Result<Arc<IdlWithDiscriminators>, LensError> {
+        let mut lens = self.lens_over(PrefetchedAccounts::default());
+        if let Some(idl) = lens.known_idl(program_id) {
+            return Ok(idl?);
+        }
+        lens.client = self
+            .prefetch(&[*program_id, IdlAccount::address(program_id)])
+            .await;
+        lens.fetch_idl(program_id)
+    }
+
+    /// Fetches accounts concurrently, for a synchronous IDL lookup to read. They're read
+    /// at [CommitmentConfig::processed], see [AccountSource::get_idl_account_with_slot].
+    async fn prefetch(&self, addresses: &[Pubkey]) -> PrefetchedAccounts {
+        let fetched = join_all(addresses.iter().map(|address| async move {
+            let response = self
+                .client
+                .get_account_with_commitment(address, CommitmentConfig::processed())
+                .await?;
+            Ok((response.value, Some(response.context.slot)))
+        }))
+        .await;
+        PrefetchedAccounts(RefCell::new(
+            addresses.iter().copied().zip(fetched).collect(),
+        ))
+    }
+
+    /// Fetches the IDLs of many programs concurrently.
+    /// Programs whose IDL can't be fetched are left out.
+    pub async fn fetch_idls(&self, program_ids: &[Pubkey]) -> HashMap<Pubkey, Arc<IdlWithDiscriminators>> {
+        let idls = join_all(program_ids.iter().map(|program_id| async move {
+            (*program_id, self.fetch_idl(program_id).await)
+        }))
+        .await;
+        idls.into_iter()
+            .filter_map(|(program_id, idl)| Some((program_id, idl.ok()?)))
+            .collect()
+    }
+
+    /// Fetches the [Account], unserialized.
+    pub async fn get_account(&self, pubkey: &Pubkey) -> Result<Account, LensError> {
+        self
+            .get_optional_account(pubkey)
+            .await?
+            .ok_or(LensError::AccountNotFound { pubkey: *pubkey })
+    }
+
+    async fn get_optional_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .client
+            .get_account_with_commitment(pubkey, self.client.commitment())
+            .await?
+            .value)
+    }
+
+    /// Fetches a historical transaction, see [AnchorLens::get_versioned_transaction].
+    pub async fn get_versioned_transaction(&self, txid: &Signature) -> Result<HistoricalTransaction, LensError> {
+        self
+            .get_optional_transaction(txid)
+            .await?
+            .ok_or(LensError::TransactionNotFound { signature: *txid })
+    }
+
+    async fn get_optional_transaction(&self, txid: &Signature) -> Result<Option<HistoricalTransaction>> {
+        let tx: Option<EncodedConfirmedTransactionWithStatusMeta> = self
+            .client
+            .send(RpcRequest::GetTransaction, get_transaction_params(txid))
+            .await?;
+        tx.map(HistoricalTransaction::from_encoded).transpose()
+    }
+
+    /// See [AnchorLens::fetch_and_deserialize_account]. Accounts of native and SPL
+    /// programs, and of programs with a registered decoder, are decoded without an IDL.
+    pub async fn fetch_and_deserialize_account(
+        &self,
+        pubkey: &Pubkey,
+        idl: Option<&IdlWithDiscriminators>,
+    ) -> Result<IdlDeserializedAccount, LensError> {
+        let account = self.get_account(pubkey).await?;
+        let lens = self.lens_over(InMemorySource::new());
+        if idl.is_none() {
+            if let Some(decoded) = lens.hand_decoded_account(&account) {
+                return Ok(decoded?);
+            }
+        }
+        let fetched;
+        let idl = match idl {
+            Some(idl) => idl,
+            None => {
+                fetched = self.fetch_idl(&account.owner).await?;
+                &*fetched
+            }
+        };
+        Ok(lens.idl_deserialized_account(idl, &account)?)
+    }
+
+    /// See [AnchorLens::fetch_idl_history]. The transactions of the IDL account,
+    /// and of each buffer copied into it, are fetched concurrently.
+    pub async fn fetch_idl_history(&self, program_id: &Pubkey) -> Result<Arc<IdlHistory>, LensError> {
+        if let Some(history) = self.idl_cache.history(program_id) {
+            return Ok(history);
+        }
+        let mut source = PrefetchedHistory::default();
+        let mut address = IdlAccount::address(program_id);
+        // Buffers are only found while replaying, so replay until none are missing.
+        let history = loop {
+            self.prefetch_history(&mut source, &address).await?;
+            match fetch_idl_history(&source, program_id) {
+                Ok(history) => break history,
+                Err(err) => address = source.unfetched.take().ok_or(err)?,
+            }
+        };
+        let history = Arc::new(history);
+        self.idl_cache.insert_history(*program_id, history.clone());
+        Ok(history)
+    }
+
+    /// Fetches the signatures of an address, then its transactions concurrently.
+    async fn prefetch_history(&self, source: &mut PrefetchedHistory, address: &Pubkey) -> Result<()> {
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = self
+                .client
+                .get_signatures_for_address_with_config(address, signatures_page_config(before))
+                .await?;
+            before = match push_signatures_page(&mut signatures, page)? {
+                Some(oldest) => Some(oldest),
+                None => break,
+            };
+        }
+        signatures.reverse();
+        let transactions: Vec<_> = stream::iter(&signatures)
+            .map(|(signature, _)| self.get_optional_transaction(signature))
+            .buffered(MAX_CONCURRENT_TRANSACTIONS)
+            .try_collect()
+            .await?;
+        for ((signature, _), tx) in signatures.iter().zip(transactions) {
+            if let Some(tx) = tx {
+                source.transactions.insert(*signature, tx);
+            }
+        }
+        source.signatures.insert(*address, signatures);
+        Ok(())
+    }
+
+    /// See [AnchorLens::deserialize_transaction]. The IDLs of every program the
+    /// transaction invoked are fetched concurrently before decoding.
+    pub async fn deserialize_transaction(&self, tx: HistoricalTransaction) -> Result<Value, LensError> {
+        let program_ids = self.needing_idls(tx.program_ids());
+        let idls = self.fetch_idls(&program_ids).await;
+        self.offline_lens(&program_ids, idls, InMemorySource::new())
+            .deserialize_transaction(tx)
+    }
+
+    /// See [AnchorLens::deserialize_transaction_with_idl_history]. The IDL histories
+    /// of the programs are reconstructed concurrently.
+    pub async fn deserialize_transaction_with_idl_history(
+        &self,
+        tx: HistoricalTransaction,
+    ) -> Result<Value, LensError> {
+        if tx.slot.is_some() {
+            let program_ids = self.needing_idls(tx.program_ids());
+            let unregistered = program_ids
+                .iter()
+                .filter(|program_id| !self.idl_registry.is_registered(program_id));
+            for history in join_all(unregistered.map(|program_id| self.fetch_idl_history(program_id))).await {
+                history?;
+            }
+        }
+        self.deserialize_transaction(tx).await
+    }
+
+    /// See [AnchorLens::deserialize_message]. The IDLs of the message's programs,
+    /// and any address lookup tables it uses, are fetched concurrently before decoding.
+    pub async fn deserialize_message(&self, message: &VersionedMessage) -> Result<Value, LensError> {
+        // Programs can't be loaded from lookup tables, so they're all static keys.
+        let mut program_ids = vec![];
+        for ix in message.instructions() {
+            if let Some(program_id) = message.static_account_keys().get(ix.program_id_index as usize) {
+                if !program_ids.contains(program_id) {
+                    program_ids.push(*program_id);
+                }
+            }
+        }
+        let program_ids = self.needing_idls(program_ids);
+        let table_keys = match message {
+            VersionedMessage::Legacy(_) => vec![],
+            VersionedMessage::V0(message) => message
+                .address_table_lookups
+                .iter()
+                .map(|lookup| lookup.account_key)
+                .collect(),
+        };
+        let (idls, tables) = futures::join!(
+            self.fetch_idls(&program_ids),
+            join_all(table_keys.iter().map(|key| self.get_optional_account(key)))
+        );
+        let mut source = InMemorySource::new();
+        for (key, table) in table_keys.into_iter().zip(tables) {
+            if let Some(table) = table? {
+                source.add_account(key, table);
+            }
+        }
+        self.offline_lens(&program_ids, idls, source)
+            .deserialize_message(message)
+    }
+
+    /// Leaves out the programs decoded without an IDL, by a registered decoder
+    /// or by hand, so their IDL accounts aren't fetched for nothing.
+    fn needing_idls(&self, program_ids: Vec<Pubkey>) -> Vec<Pubkey> {
+        program_ids
+            .into_iter()
+            .filter(|program_id| {
+                native_program_name(program_id).is_none()
+                    && !self.program_decoders.contains_key(program_id)
+            })
+            .collect()
+    }
+
+    /// A synchronous lens over `source`, with this lens's settings and caches.
+    fn lens_over<S: AccountSource>(&self, source: S) -> AnchorLens<S> {
+        AnchorLens {
+            client: source,
+            idl_cache: self.idl_cache.clone(),
+            cache_idls: self.cache_idls,
+            // Registered IDLs are behind an [Arc], so this doesn't copy them, and
+            // keeps the versions used to decode transactions from their slot.
+            idl_registry: self.idl_registry.clone(),
+            disk_cache: self.disk_cache.clone(),
+            strictness: self.strictness,
+            decode_idl_writes: self.decode_idl_writes,
+            program_decoders: self.program_decoders.clone(),
+        }
+    }
+
+    /// A synchronous lens that decodes with already-fetched IDLs and accounts,
+    /// and never touches the network.
+    /// The IDL histories of `program_ids` already reconstructed are used too.
+    fn offline_lens(
+        &self,
+        program_ids: &[Pubkey],
+        idls: HashMap<Pubkey, Arc<IdlWithDiscriminators>>,
+        source: InMemorySource,
+    ) -> AnchorLens<InMemorySource> {
+        let mut lens = self.lens_over(source);
+        lens.idl_cache = Arc::new(IdlCache::new());
+        lens.cache_idls = true;
+        // Programs whose IDL wasn't fetched have no account in `source`,
+        // which mustn't evict them from the disk cache.
+        lens.disk_cache = None;
+        for (program_id, idl) in idls {
+            lens.idl_cache.insert(program_id, idl);
+        }
+        for program_id in program_ids {
+            if let Some(history) = self.idl_cache.history(program_id) {
+                lens.idl_cache.insert_history(*program_id, history);
+            }
+        }
+        lens
+    }
+}
+
+/// Accounts fetched ahead of a synchronous IDL lookup. An account that wasn't fetched
+/// is an error rather than missing, and so is one whose fetch failed, once.
+#[derive(Default)]
+struct PrefetchedAccounts(RefCell<HashMap<Pubkey, FetchedAccount>>);
+
+/// An account and the slot it was read at, or why it couldn't be read.
+type FetchedAccount = Result<(Option<Account>, Option<u64>)>;
+
+impl AccountSource for PrefetchedAccounts {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.get_account_with_slot(pubkey)?.0)
+    }
+
+    fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, Option<u64>)> {
+        let mut accounts = self.0.borrow_mut();
+        match accounts.get(pubkey) {
+            Some(Ok(fetched)) => Ok(fetched.clone()),
+            Some(Err(_)) => accounts.remove(pubkey).unwrap(),
+            None => Err(anyhow!("Account {} was not fetched", pubkey)),
+        }
+    }
+}
+
+/// Transactions fetched ahead of reconstructing an IDL history. Asking for the
+/// signatures of an address that wasn't fetched records it in `unfetched` and fails,
+/// so that they can be fetched before replaying again.
+#[derive(Default)]
+struct PrefetchedHistory {
+    signatures: HashMap<Pubkey, Vec<(Signature, u64)>>,
+    transactions: HashMap<Signature, HistoricalTransaction>,
+    unfetched: Cell<Option<Pubkey>>,
+}
+
+impl SignatureSource for PrefetchedHistory {
+    fn get_signatures_for_address(&self, address: &Pubkey) -> Result<Vec<(Signature, u64)>> {
+        match self.signatures.get(address) {
+            Some(signatures) => Ok(signatures.clone()),
+            None => {
+                self.unfetched.set(Some(*address));
+                Err(anyhow!("Signatures for {} were not fetched", address))
+            }
+        }
+    }
+}
+
+impl TransactionSource for PrefetchedHistory {
+    fn get_transaction(&self, signature: &Signature) -> Result<Option<HistoricalTransaction>> {
+        Ok(self.transactions.get(signature).cloned())
+    }
+}
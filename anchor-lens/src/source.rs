@@ -0,0 +1,265 @@
+//! Where accounts and transactions come from.
+//!
+//! Everything the lens reads goes through [AccountSource] and [TransactionSource],
+//! so it can decode from an RPC node, or from data already at hand, such as
+//! a test fixture, a snapshot, or a local validator like BanksClient or LiteSVM.
+
+use crate::deserialize::instruction::full_account_keys;
+use crate::deserialize::HistoricalTransaction;
+use anyhow::Result;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use serde_json::{json, Value};
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Provides account data, including program IDL accounts.
+pub trait AccountSource {
+    /// Returns `None` if the account doesn't exist.
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>>;
+
+    /// Like [AccountSource::get_account], along with the slot the account was read at,
+    /// if the source knows it.
+    fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, Option<u64>)> {
+        Ok((self.get_account(pubkey)?, None))
+    }
+
+    /// Like [AccountSource::get_account_with_slot], for program and IDL accounts looked
+    /// up to find an IDL. Sources with a commitment read these at the lowest one,
+    /// so that an IDL is found as soon as it's published.
+    fn get_idl_account_with_slot(
+        &self,
+        pubkey: &Pubkey,
+    ) -> Result<(Option<Account>, Option<u64>)> {
+        self.get_account_with_slot(pubkey)
+    }
+
+    /// The signature of the newest transaction that referenced an account, to tell
+    /// whether it may have changed without reading it. Returns `None` if the source
+    /// can't tell, which is the default.
+    fn get_latest_signature(&self, _pubkey: &Pubkey) -> Result<Option<Signature>> {
+        Ok(None)
+    }
+}
+
+/// Provides historical transactions, along with the metadata the runtime recorded for them.
+pub trait TransactionSource {
+    /// Returns `None` if the transaction isn't known.
+    fn get_transaction(&self, signature: &Signature) -> Result<Option<HistoricalTransaction>>;
+}
+
+/// Lists the transactions that referenced an account, to reconstruct its history.
+pub trait SignatureSource {
+    /// The signatures and slots of the successful transactions
+    /// that referenced `address`, oldest first.
+    fn get_signatures_for_address(&self, address: &Pubkey) -> Result<Vec<(Signature, u64)>>;
+}
+
+impl AccountSource for RpcClient {
+    /// Fetches the account at the client's configured commitment.
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .get_account_with_commitment(pubkey, self.commitment())?
+            .value)
+    }
+
+    fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, Option<u64>)> {
+        let response = self.get_account_with_commitment(pubkey, self.commitment())?;
+        Ok((response.value, Some(response.context.slot)))
+    }
+
+    /// Fetches the account at [CommitmentConfig::processed].
+    fn get_idl_account_with_slot(
+        &self,
+        pubkey: &Pubkey,
+    ) -> Result<(Option<Account>, Option<u64>)> {
+        let response = self.get_account_with_commitment(pubkey, CommitmentConfig::processed())?;
+        Ok((response.value, Some(response.context.slot)))
+    }
+
+    /// Requests a single signature, the newest, failed transactions included.
+    fn get_latest_signature(&self, pubkey: &Pubkey) -> Result<Option<Signature>> {
+        let page = self.get_signatures_for_address_with_config(
+            pubkey,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(1),
+                ..signatures_page_config(None)
+            },
+        )?;
+        Ok(page
+            .first()
+            .map(|status| Signature::from_str(&status.signature))
+            .transpose()?)
+    }
+}
+
+impl TransactionSource for RpcClient {
+    /// Fetches the transaction with its metadata, accepting v0 transactions.
+    fn get_transaction(&self, signature: &Signature) -> Result<Option<HistoricalTransaction>> {
+        let tx: Option<EncodedConfirmedTransactionWithStatusMeta> =
+            self.send(RpcRequest::GetTransaction, get_transaction_params(signature))?;
+        tx.map(HistoricalTransaction::from_encoded).transpose()
+    }
+}
+
+/// The parameters of a `getTransaction` request: in a binary encoding, which
+/// [HistoricalTransaction::from_encoded] needs, and accepting v0 transactions.
+///
+/// The RPC returns `null` for a transaction it doesn't know, which the client's
+/// `get_transaction` fails on, so the transaction is requested as an [Option] instead.
+pub(crate) fn get_transaction_params(signature: &Signature) -> Value {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: None,
+        max_supported_transaction_version: Some(0),
+    };
+    json!([signature.to_string(), config])
+}
+
+impl SignatureSource for RpcClient {
+    /// Pages through the whole history of the address, which the RPC returns newest first.
+    fn get_signatures_for_address(&self, address: &Pubkey) -> Result<Vec<(Signature, u64)>> {
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page =
+                self.get_signatures_for_address_with_config(address, signatures_page_config(before))?;
+            before = match push_signatures_page(&mut signatures, page)? {
+                Some(oldest) => Some(oldest),
+                None => break,
+            };
+        }
+        signatures.reverse();
+        Ok(signatures)
+    }
+}
+
+/// How the page of signatures before `before` is requested from an RPC node.
+pub(crate) fn signatures_page_config(
+    before: Option<Signature>,
+) -> GetConfirmedSignaturesForAddress2Config {
+    GetConfirmedSignaturesForAddress2Config {
+        before,
+        until: None,
+        limit: None,
+        commitment: None,
+    }
+}
+
+/// Adds the successful transactions in a page of signatures to `signatures`, newest first.
+/// Returns the oldest signature in the page, to request the next page before,
+/// or `None` if the page is empty.
+pub(crate) fn push_signatures_page(
+    signatures: &mut Vec<(Signature, u64)>,
+    page: Vec<RpcConfirmedTransactionStatusWithSignature>,
+) -> Result<Option<Signature>> {
+    let oldest = match page.last() {
+        Some(oldest) => Signature::from_str(&oldest.signature)?,
+        None => return Ok(None),
+    };
+    for status in page {
+        if status.err.is_none() {
+            signatures.push((Signature::from_str(&status.signature)?, status.slot));
+        }
+    }
+    Ok(Some(oldest))
+}
+
+impl<T: AccountSource + ?Sized> AccountSource for &T {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        (**self).get_account(pubkey)
+    }
+
+    fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Option<Account>, Option<u64>)> {
+        (**self).get_account_with_slot(pubkey)
+    }
+
+    fn get_idl_account_with_slot(
+        &self,
+        pubkey: &Pubkey,
+    ) -> Result<(Option<Account>, Option<u64>)> {
+        (**self).get_idl_account_with_slot(pubkey)
+    }
+
+    fn get_latest_signature(&self, pubkey: &Pubkey) -> Result<Option<Signature>> {
+        (**self).get_latest_signature(pubkey)
+    }
+}
+
+impl<T: TransactionSource + ?Sized> TransactionSource for &T {
+    fn get_transaction(&self, signature: &Signature) -> Result<Option<HistoricalTransaction>> {
+        (**self).get_transaction(signature)
+    }
+}
+
+impl<T: SignatureSource + ?Sized> SignatureSource for &T {
+    fn get_signatures_for_address(&self, address: &Pubkey) -> Result<Vec<(Signature, u64)>> {
+        (**self).get_signatures_for_address(address)
+    }
+}
+
+/// Accounts and transactions held in memory, for decoding offline.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySource {
+    pub accounts: HashMap<Pubkey, Account>,
+    pub transactions: HashMap<Signature, HistoricalTransaction>,
+}
+
+impl InMemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces an account.
+    pub fn add_account(&mut self, pubkey: Pubkey, account: Account) {
+        self.accounts.insert(pubkey, account);
+    }
+
+    /// Adds or replaces a transaction.
+    pub fn add_transaction(&mut self, signature: Signature, transaction: HistoricalTransaction) {
+        self.transactions.insert(signature, transaction);
+    }
+}
+
+impl AccountSource for InMemorySource {
+    fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.accounts.get(pubkey).cloned())
+    }
+
+    fn get_latest_signature(&self, pubkey: &Pubkey) -> Result<Option<Signature>> {
+        Ok(self
+            .get_signatures_for_address(pubkey)?
+            .last()
+            .map(|(signature, _)| *signature))
+    }
+}
+
+impl TransactionSource for InMemorySource {
+    fn get_transaction(&self, signature: &Signature) -> Result<Option<HistoricalTransaction>> {
+        Ok(self.transactions.get(signature).cloned())
+    }
+}
+
+impl SignatureSource for InMemorySource {
+    /// Transactions without a slot are taken to be the oldest.
+    fn get_signatures_for_address(&self, address: &Pubkey) -> Result<Vec<(Signature, u64)>> {
+        let mut signatures = self
+            .transactions
+            .iter()
+            .filter(|(_, tx)| {
+                tx.err.is_none()
+                    && full_account_keys(&tx.message, &tx.loaded_addresses).contains(address)
+            })
+            .map(|(signature, tx)| (*signature, tx.slot.unwrap_or(0)))
+            .collect::<Vec<_>>();
+        signatures.sort_by_key(|(signature, slot)| (*slot, *signature));
+        Ok(signatures)
+    }
+}
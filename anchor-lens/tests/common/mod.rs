@@ -0,0 +1,117 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use anchor_lang::idl::IdlAccount;
+use borsh::BorshSerialize;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+use solana_anchor_lens::source::{AccountSource, InMemorySource};
+use solana_program::hash::hash;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::signature::Signature;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub fn account(owner: Pubkey, data: Vec<u8>, executable: bool) -> Account {
+    Account {
+        lamports: 1_000_000,
+        data,
+        owner,
+        executable,
+        rent_epoch: 0,
+    }
+}
+
+pub fn discriminator(preimage: &str) -> Vec<u8> {
+    hash(preimage.as_bytes()).to_bytes()[..8].to_vec()
+}
+
+/// The IDL of a counter program, whose `increment` instruction takes an `arg`.
+pub fn counter_idl(name: &str, arg: &str) -> Value {
+    json!({
+        "version": "0.1.0",
+        "name": name,
+        "instructions": [
+            {
+                "name": "increment",
+                "accounts": [
+                    { "name": "counter", "isMut": true, "isSigner": false },
+                    { "name": "authority", "isMut": false, "isSigner": true }
+                ],
+                "args": [{ "name": arg, "type": "u64" }]
+            }
+        ],
+        "accounts": [
+            {
+                "name": "Counter",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "count", "type": "u64" },
+                        { "name": "authority", "type": "publicKey" }
+                    ]
+                }
+            }
+        ]
+    })
+}
+
+/// IDL JSON compressed the way the Anchor CLI stores it on chain.
+pub fn compress_idl(idl: &Value) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(idl.to_string().as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// An IDL account, as written by `anchor idl init`: a discriminator, the authority,
+/// and the zlib-compressed IDL JSON.
+pub fn idl_account(program_id: Pubkey, idl: &Value) -> Account {
+    let data = [
+        discriminator("account:IdlAccount"),
+        Pubkey::new_unique().to_bytes().to_vec(),
+        compress_idl(idl).try_to_vec().unwrap(),
+    ]
+    .concat();
+    account(program_id, data, false)
+}
+
+/// A program account, and its IDL account at the canonical address.
+pub fn add_program(source: &mut InMemorySource, program_id: Pubkey, idl: &Value) {
+    source.add_account(program_id, account(Pubkey::new_unique(), vec![], true));
+    source.add_account(
+        IdlAccount::address(&program_id),
+        idl_account(program_id, idl),
+    );
+}
+
+/// Counts the accounts read, to tell when the IDL cache is used.
+pub struct CountingSource {
+    pub inner: InMemorySource,
+    pub reads: AtomicUsize,
+}
+
+impl CountingSource {
+    pub fn new(inner: InMemorySource) -> Self {
+        Self {
+            inner,
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::SeqCst)
+    }
+}
+
+impl AccountSource for CountingSource {
+    fn get_account(&self, pubkey: &Pubkey) -> anyhow::Result<Option<Account>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.get_account(pubkey)
+    }
+
+    fn get_latest_signature(&self, pubkey: &Pubkey) -> anyhow::Result<Option<Signature>> {
+        self.inner.get_latest_signature(pubkey)
+    }
+}
@@ -0,0 +1,170 @@
+//! Keeping fetched IDLs on disk between runs.
+
+mod common;
+
+use anchor_lang::idl::IdlAccount;
+use anyhow::anyhow;
+use common::{add_program, counter_idl, idl_account, CountingSource};
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::fetch_idl::DiskIdlCache;
+use solana_anchor_lens::source::{AccountSource, InMemorySource};
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::signature::Signature;
+use std::fs;
+use std::path::PathBuf;
+
+fn cache_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "anchor-lens-idl-cache-{}-{}",
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// A lens as a fresh CLI run would set one up.
+fn lens<S: AccountSource>(source: S, dir: &PathBuf) -> AnchorLens<S> {
+    let mut lens = AnchorLens::new_with_idl_caching(source);
+    lens.disk_cache = Some(DiskIdlCache::new(dir, "localnet"));
+    lens
+}
+
+/// An RPC node we can't reach.
+struct Offline;
+
+impl AccountSource for Offline {
+    fn get_account(&self, _pubkey: &Pubkey) -> anyhow::Result<Option<Account>> {
+        Err(anyhow!("connection refused"))
+    }
+}
+
+#[test]
+fn cached_idls_are_revalidated() {
+    let dir = cache_dir("revalidated");
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+
+    let first = lens(CountingSource::new(source.clone()), &dir);
+    assert_eq!(first.fetch_idl(&program_id).unwrap().name, "counter");
+    assert_eq!(first.client.reads(), 2);
+    assert!(DiskIdlCache::new(&dir, "localnet")
+        .path(&program_id)
+        .is_file());
+
+    // Only the IDL account is read to check the cached IDL is current.
+    let second = lens(CountingSource::new(source.clone()), &dir);
+    let idl = second.fetch_idl(&program_id).unwrap();
+    assert_eq!(idl.name, "counter");
+    assert_eq!(idl.address, Some(program_id));
+    assert_eq!(second.client.reads(), 1);
+
+    // An upgraded IDL replaces the cached one.
+    source.add_account(
+        IdlAccount::address(&program_id),
+        idl_account(program_id, &counter_idl("counter_v2", "amount")),
+    );
+    let third = lens(source.clone(), &dir);
+    assert_eq!(third.fetch_idl(&program_id).unwrap().name, "counter_v2");
+    let fourth = lens(Offline, &dir);
+    assert_eq!(fourth.fetch_idl(&program_id).unwrap().name, "counter_v2");
+
+    // Other clusters don't share it.
+    let mut devnet = AnchorLens::new(Offline);
+    devnet.disk_cache = Some(DiskIdlCache::new(&dir, "devnet"));
+    assert!(devnet.fetch_idl(&program_id).is_err());
+}
+
+/// Records a transaction that referenced the IDL account, like an IDL write.
+fn touch_idl_account(source: &mut InMemorySource, program_id: Pubkey, slot: u64) {
+    let ix = Instruction::new_with_bytes(
+        program_id,
+        &[],
+        vec![AccountMeta::new(IdlAccount::address(&program_id), false)],
+    );
+    source.add_transaction(
+        Signature::new_unique(),
+        HistoricalTransaction {
+            slot: Some(slot),
+            ..HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+                &[ix],
+                Some(&Pubkey::new_unique()),
+            )))
+        },
+    );
+}
+
+#[test]
+fn untouched_idl_accounts_are_not_read() {
+    let dir = cache_dir("untouched");
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    touch_idl_account(&mut source, program_id, 10);
+    lens(&source, &dir).fetch_idl(&program_id).unwrap();
+
+    // The IDL account is read once more, to record the newest transaction along with it.
+    let second = lens(CountingSource::new(source.clone()), &dir);
+    assert_eq!(second.fetch_idl(&program_id).unwrap().name, "counter");
+    assert_eq!(second.client.reads(), 1);
+    let third = lens(CountingSource::new(source.clone()), &dir);
+    assert_eq!(third.fetch_idl(&program_id).unwrap().name, "counter");
+    assert_eq!(third.client.reads(), 0);
+
+    // A newer transaction means the IDL may have changed.
+    source.add_account(
+        IdlAccount::address(&program_id),
+        idl_account(program_id, &counter_idl("counter_v2", "amount")),
+    );
+    touch_idl_account(&mut source, program_id, 20);
+    let fourth = lens(CountingSource::new(source), &dir);
+    assert_eq!(fourth.fetch_idl(&program_id).unwrap().name, "counter_v2");
+    assert_eq!(fourth.client.reads(), 1);
+}
+
+#[test]
+fn cached_idls_can_be_trusted_without_rpc_calls() {
+    let dir = cache_dir("trusted");
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    lens(&source, &dir).fetch_idl(&program_id).unwrap();
+
+    let mut lens = lens(CountingSource::new(source), &dir);
+    lens.disk_cache.as_mut().unwrap().revalidate = false;
+    assert_eq!(lens.fetch_idl(&program_id).unwrap().name, "counter");
+    assert_eq!(lens.client.reads(), 0);
+}
+
+#[test]
+fn closed_idl_accounts_are_evicted() {
+    let dir = cache_dir("closed");
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    lens(&source, &dir).fetch_idl(&program_id).unwrap();
+
+    source.accounts.remove(&IdlAccount::address(&program_id));
+    let err = lens(&source, &dir).fetch_idl(&program_id).unwrap_err();
+    assert!(
+        matches!(err, LensError::IdlAccountNotFound { program_id: id } if id == program_id),
+        "{}",
+        err
+    );
+    assert!(!DiskIdlCache::new(&dir, "localnet")
+        .path(&program_id)
+        .is_file());
+}
+
+#[test]
+fn cache_keys_for_urls() {
+    let cache = DiskIdlCache::for_url("/tmp", "https://api.devnet.solana.com");
+    assert_eq!(cache.cluster, "devnet");
+    let cache = DiskIdlCache::for_url("/tmp", "https://rpc.example.com:8899");
+    assert_eq!(cache.cluster, "https___rpc_example_com_8899");
+}
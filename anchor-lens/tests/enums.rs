@@ -0,0 +1,154 @@
+//! Enums are decoded by their Borsh variant index, and must consume exactly
+//! the bytes of the selected variant.
+
+use anchor_syn::idl::IdlTypeDefinition;
+use borsh::BorshSerialize;
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::context::{DecodeContext, DecodeWarning, Strictness};
+use solana_anchor_lens::deserialize::idl_type_deserializer::TypeDefinitionDeserializer;
+use solana_anchor_lens::error::LensError;
+
+#[derive(BorshSerialize)]
+enum Mode {
+    Off,
+    Fixed { rate: u64 },
+    Pair(u8, u16),
+}
+
+#[derive(BorshSerialize)]
+struct Config {
+    mode: Mode,
+    bump: u8,
+}
+
+fn type_defs() -> Vec<IdlTypeDefinition> {
+    serde_json::from_value(json!([
+        {
+            "name": "Mode",
+            "type": {
+                "kind": "enum",
+                "variants": [
+                    { "name": "Off" },
+                    { "name": "Fixed", "fields": [{ "name": "rate", "type": "u64" }] },
+                    { "name": "Pair", "fields": ["u8", "u16"] }
+                ]
+            }
+        },
+        {
+            "name": "Config",
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    { "name": "mode", "type": { "defined": "Mode" } },
+                    { "name": "bump", "type": "u8" }
+                ]
+            }
+        }
+    ]))
+    .unwrap()
+}
+
+fn deserialize_config(data: &[u8]) -> anyhow::Result<(Value, usize)> {
+    let type_defs = type_defs();
+    let mut cursor = data;
+    let value = TypeDefinitionDeserializer {
+        idl_type_defs: type_defs.clone(),
+        curr_type: type_defs[1].clone(),
+    }
+    .deserialize(&mut cursor)?;
+    Ok((value, cursor.len()))
+}
+
+#[test]
+fn each_variant_kind_is_selected_by_its_index() {
+    let cases = [
+        (Mode::Off, json!({ "name": "Off", "fields": null })),
+        (
+            Mode::Fixed { rate: 7 },
+            json!({ "name": "Fixed", "fields": { "rate": 7 } }),
+        ),
+        (
+            Mode::Pair(1, 0x0203),
+            json!({ "name": "Pair", "fields": [1, 0x0203] }),
+        ),
+    ];
+    for (mode, expected) in cases {
+        let data = Config { mode, bump: 0xfe }.try_to_vec().unwrap();
+        let (value, remaining) = deserialize_config(&data).unwrap();
+        // The field after the enum is read from the right offset.
+        assert_eq!(value, json!({ "mode": expected, "bump": 0xfe }));
+        assert_eq!(remaining, 0);
+    }
+}
+
+#[test]
+fn unknown_variant_index_is_an_error() {
+    let err = deserialize_config(&[3, 0]).unwrap_err();
+    match err.downcast_ref::<LensError>() {
+        Some(LensError::InvalidEnumVariant {
+            enum_name,
+            variant_index,
+            location,
+        }) => {
+            assert_eq!(enum_name, "Mode");
+            assert_eq!(*variant_index, 3);
+            assert_eq!(location.path, "Config.mode");
+            assert_eq!(location.offset, 0);
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn tuples_stop_at_the_first_failing_field() {
+    let type_defs: Vec<IdlTypeDefinition> = serde_json::from_value(json!([
+        {
+            "name": "Point",
+            "type": {
+                "kind": "struct",
+                "fields": [{ "name": "x", "type": "u8" }, { "name": "y", "type": "u16" }]
+            }
+        },
+        {
+            "name": "Shape",
+            "type": {
+                "kind": "enum",
+                "variants": [{ "name": "Dot", "fields": [{ "defined": "Point" }, "u8"] }]
+            }
+        }
+    ]))
+    .unwrap();
+    // The point's `x` is read before its `y` runs out of data.
+    let data = [0, 1, 2];
+    let decode = |strictness| {
+        let mut ctx = DecodeContext::new(strictness, None, &data, "Shape");
+        let mut cursor = &data[..];
+        let value = TypeDefinitionDeserializer {
+            idl_type_defs: type_defs.clone(),
+            curr_type: type_defs[1].clone(),
+        }
+        .deserialize_in(&mut cursor, &mut ctx);
+        (value, cursor.len(), ctx.warnings)
+    };
+
+    // The cursor is put back to the start of the point, rather than left inside it.
+    let (value, remaining, warnings) = decode(Strictness::Lenient);
+    assert_eq!(value.unwrap(), json!({ "name": "Dot", "fields": [] }));
+    assert_eq!(remaining, 2);
+    assert!(warnings.is_empty());
+
+    let (value, remaining, warnings) = decode(Strictness::Warn);
+    assert_eq!(value.unwrap(), json!({ "name": "Dot", "fields": [] }));
+    assert_eq!(remaining, 2);
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning {
+            path: "Shape.Dot[0].y".to_string(),
+            offset: 2,
+            message: "Unexpected end of data".to_string(),
+        }]
+    );
+
+    let (value, _, _) = decode(Strictness::Strict);
+    assert!(value.is_err());
+}
@@ -0,0 +1,221 @@
+//! Decoding events, whether logged with `emit!` or self-invoked with `emit_cpi!`.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{add_program, discriminator};
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::event::{extract_logged_events, EVENT_IX_TAG_LE};
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::instruction::{AccountMeta, CompiledInstruction, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::ed25519_program;
+
+fn event_idl() -> Value {
+    json!({
+        "version": "0.1.0",
+        "name": "counter",
+        "instructions": [
+            {
+                "name": "increment",
+                "accounts": [
+                    { "name": "counter", "isMut": true, "isSigner": false },
+                    { "name": "authority", "isMut": false, "isSigner": true }
+                ],
+                "args": [{ "name": "by", "type": "u64" }]
+            }
+        ],
+        "events": [
+            {
+                "name": "Incremented",
+                "fields": [
+                    { "name": "count", "type": "u64", "index": false },
+                    { "name": "authority", "type": "publicKey", "index": false }
+                ]
+            }
+        ]
+    })
+}
+
+fn incremented(count: u64, authority: &Pubkey) -> Vec<u8> {
+    [
+        discriminator("event:Incremented"),
+        count.try_to_vec().unwrap(),
+        authority.to_bytes().to_vec(),
+    ]
+    .concat()
+}
+
+#[test]
+fn logged_events_are_located_by_the_invocation_stack() {
+    let [outer, inner, failing, second] = [(); 4].map(|_| Pubkey::new_unique());
+    let logs = [
+        // Data logged before any invocation has no instruction to go with.
+        "Program data: AQ==".to_string(),
+        format!("Program {} invoke [1]", outer),
+        "Program log: Instruction: Increment".to_string(),
+        "Program data: Ag==".to_string(),
+        format!("Program {} invoke [2]", inner),
+        "Program data: Aw==".to_string(),
+        format!("Program {} success", inner),
+        format!("Program {} invoke [2]", failing),
+        format!("Program {} failed: custom program error: 0x1", failing),
+        // Back in the outer program, after its second inner instruction.
+        "Program data: BA==".to_string(),
+        format!("Program {} consumed 1000 of 200000 compute units", outer),
+        format!("Program {} success", outer),
+        format!("Program {} invoke [1]", second),
+        // Several fields logged with `sol_log_data` make up one event.
+        "Program data: BQ== Bg==".to_string(),
+        // Lines that aren't base64 are skipped.
+        "Program data: not-base64!".to_string(),
+        format!("Program {} success", second),
+    ];
+    let events = extract_logged_events(&logs, &[outer, second])
+        .into_iter()
+        .map(|event| {
+            (
+                event.program_id,
+                event.instruction_index,
+                event.inner_instruction_index,
+                event.data,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            (outer, 0, None, vec![2]),
+            (inner, 0, Some(0), vec![3]),
+            (outer, 0, None, vec![4]),
+            (second, 1, None, vec![5, 6]),
+        ]
+    );
+}
+
+#[test]
+fn decodes_logged_and_cpi_events() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &event_idl());
+    let lens = AnchorLens::new_with_idl_caching(source);
+
+    let increment = Instruction::new_with_bytes(
+        program_id,
+        &[
+            discriminator("global:increment"),
+            1u64.try_to_vec().unwrap(),
+        ]
+        .concat(),
+        vec![
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+    );
+    let mut tx = HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+        &[increment],
+        Some(&authority),
+    )));
+    let program_id_index = tx
+        .message
+        .static_account_keys()
+        .iter()
+        .position(|key| *key == program_id)
+        .unwrap() as u8;
+    // `emit_cpi!` invokes the program itself with the event as instruction data.
+    tx.inner_instructions.insert(
+        0,
+        vec![CompiledInstruction {
+            program_id_index,
+            accounts: vec![],
+            data: [EVENT_IX_TAG_LE.to_vec(), incremented(2, &authority)].concat(),
+        }],
+    );
+    tx.log_messages = vec![
+        format!("Program {} invoke [1]", program_id),
+        format!(
+            "Program data: {}",
+            base64::encode(incremented(1, &authority))
+        ),
+        format!(
+            "Program data: {}",
+            base64::encode(discriminator("event:Unknown"))
+        ),
+        format!("Program {} invoke [2]", program_id),
+        format!("Program {} success", program_id),
+        format!("Program {} success", program_id),
+    ];
+
+    let json = lens.deserialize_transaction(tx).unwrap();
+    let ix = &json["instructions"][0];
+    assert_eq!(ix["instruction"]["name"], "increment");
+    assert_eq!(
+        ix["events"],
+        json!([
+            {
+                "name": "Incremented",
+                "data": { "count": 1, "authority": authority.to_string() },
+            },
+            { "unknown_event": base64::encode(discriminator("event:Unknown")) },
+        ])
+    );
+    assert_eq!(
+        ix["inner_instructions"][0],
+        json!({
+            "program_id": program_id.to_string(),
+            "program_name": "counter",
+            "event": {
+                "name": "Incremented",
+                "data": { "count": 2, "authority": authority.to_string() },
+            },
+        })
+    );
+}
+
+#[test]
+fn precompile_instructions_log_no_invocation() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &event_idl());
+    let lens = AnchorLens::new_with_idl_caching(source);
+
+    // Signatures are checked by the Ed25519 program ahead of the instruction using them.
+    let verify = Instruction::new_with_bytes(ed25519_program::id(), &[0; 16], vec![]);
+    let increment = Instruction::new_with_bytes(
+        program_id,
+        &[
+            discriminator("global:increment"),
+            1u64.try_to_vec().unwrap(),
+        ]
+        .concat(),
+        vec![
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+    );
+    let mut tx = HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+        &[verify, increment],
+        Some(&authority),
+    )));
+    tx.log_messages = vec![
+        format!("Program {} invoke [1]", program_id),
+        format!(
+            "Program data: {}",
+            base64::encode(incremented(1, &authority))
+        ),
+        format!("Program {} success", program_id),
+    ];
+
+    let json = lens.deserialize_transaction(tx).unwrap();
+    assert!(json["instructions"][0].get("events").is_none());
+    assert_eq!(json["instructions"][1]["instruction"]["name"], "increment");
+    assert_eq!(
+        json["instructions"][1]["events"][0]["data"]["count"],
+        json!(1)
+    );
+}
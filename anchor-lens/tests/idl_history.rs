@@ -0,0 +1,276 @@
+//! Decoding old transactions with the IDL that was live when they landed.
+
+mod common;
+
+use anchor_lang::idl::{IdlAccount, IdlInstruction, IDL_IX_TAG};
+use borsh::BorshSerialize;
+use common::{add_program, compress_idl, counter_idl, discriminator};
+use serde_json::json;
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::error::LensError;
+use solana_anchor_lens::fetch_idl::{
+    fetch_idl_history, IdlRegistry, IdlWithDiscriminators, VersionedIdl,
+};
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+use solana_sdk::signature::Signature;
+
+fn idl_ix(program_id: Pubkey, ix: IdlInstruction, accounts: &[Pubkey]) -> Instruction {
+    let data = [IDL_IX_TAG.to_le_bytes().to_vec(), ix.try_to_vec().unwrap()].concat();
+    let accounts = accounts
+        .iter()
+        .map(|pubkey| AccountMeta::new(*pubkey, false))
+        .collect();
+    Instruction::new_with_bytes(program_id, &data, accounts)
+}
+
+fn add_transaction(source: &mut InMemorySource, ixs: &[Instruction], payer: Pubkey, slot: u64) {
+    source.add_transaction(
+        Signature::new_unique(),
+        HistoricalTransaction {
+            slot: Some(slot),
+            ..HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(ixs, Some(&payer))))
+        },
+    );
+}
+
+struct Fixture {
+    source: InMemorySource,
+    program_id: Pubkey,
+    authority: Pubkey,
+}
+
+/// A program whose `increment` argument was renamed from `amount` to `delta`:
+/// the first IDL was written into the IDL account over slots 10 and 11, and
+/// the second was written into a buffer at slot 20, then set at slot 30.
+fn fixture() -> Fixture {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let buffer = Pubkey::new_unique();
+    let idl_addr = IdlAccount::address(&program_id);
+    let v1 = compress_idl(&counter_idl("counter", "amount"));
+    let v2 = compress_idl(&counter_idl("counter", "delta"));
+    let (v1_start, v1_end) = v1.split_at(v1.len() / 2);
+
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "delta"));
+    let create = IdlInstruction::Create { data_len: 1000 };
+    let create_accounts = [
+        authority,
+        idl_addr,
+        authority,
+        system_program::id(),
+        program_id,
+    ];
+    let write = |data: &[u8]| IdlInstruction::Write {
+        data: data.to_vec(),
+    };
+    add_transaction(
+        &mut source,
+        &[
+            idl_ix(program_id, create, &create_accounts),
+            idl_ix(program_id, write(v1_start), &[idl_addr, authority]),
+        ],
+        authority,
+        10,
+    );
+    add_transaction(
+        &mut source,
+        &[idl_ix(program_id, write(v1_end), &[idl_addr, authority])],
+        authority,
+        11,
+    );
+    add_transaction(
+        &mut source,
+        &[
+            idl_ix(
+                program_id,
+                IdlInstruction::CreateBuffer,
+                &[buffer, authority],
+            ),
+            idl_ix(program_id, write(&v2), &[buffer, authority]),
+        ],
+        authority,
+        20,
+    );
+    add_transaction(
+        &mut source,
+        &[idl_ix(
+            program_id,
+            IdlInstruction::SetBuffer,
+            &[buffer, idl_addr, authority],
+        )],
+        authority,
+        30,
+    );
+    Fixture {
+        source,
+        program_id,
+        authority,
+    }
+}
+
+fn increment(program_id: Pubkey, authority: Pubkey, slot: u64) -> HistoricalTransaction {
+    let data = [
+        discriminator("global:increment"),
+        5u64.try_to_vec().unwrap(),
+    ]
+    .concat();
+    let ix = Instruction::new_with_bytes(
+        program_id,
+        &data,
+        vec![
+            AccountMeta::new(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+    );
+    HistoricalTransaction {
+        slot: Some(slot),
+        ..HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+            &[ix],
+            Some(&authority),
+        )))
+    }
+}
+
+fn increment_arg(idl: &IdlWithDiscriminators) -> &str {
+    &idl.instructions[0].args[0].name
+}
+
+#[test]
+fn replays_init_and_upgrade() {
+    let Fixture {
+        source, program_id, ..
+    } = fixture();
+    let history = fetch_idl_history(&source, &program_id).unwrap();
+    let slots = history
+        .versions
+        .iter()
+        .map(|version| version.slot)
+        .collect::<Vec<_>>();
+    // The partial write at slot 10, and the buffer at slot 20, aren't versions.
+    assert_eq!(slots, vec![11, 30]);
+    assert!(history.at(10).is_none());
+    assert_eq!(increment_arg(history.at(11).unwrap()), "amount");
+    assert_eq!(increment_arg(history.at(29).unwrap()), "amount");
+    assert_eq!(increment_arg(history.at(30).unwrap()), "delta");
+    assert_eq!(history.at(30).unwrap().address, Some(program_id));
+}
+
+#[test]
+fn old_transactions_use_the_idl_live_at_their_slot() {
+    let Fixture {
+        source,
+        program_id,
+        authority,
+    } = fixture();
+    let lens = AnchorLens::new_with_idl_caching(source);
+    let decoded_args =
+        |json: serde_json::Value| json["instructions"][0]["instruction"]["data"].clone();
+
+    let old = increment(program_id, authority, 15);
+    // Without the history, today's IDL is used.
+    let json = lens.deserialize_transaction(old.clone()).unwrap();
+    assert_eq!(decoded_args(json), json!({ "delta": 5 }));
+    let json = lens
+        .deserialize_transaction_with_idl_history(old.clone())
+        .unwrap();
+    assert_eq!(decoded_args(json), json!({ "amount": 5 }));
+    // Once fetched, the history is cached and used automatically.
+    let json = lens.deserialize_transaction(old).unwrap();
+    assert_eq!(decoded_args(json), json!({ "amount": 5 }));
+
+    let new = increment(program_id, authority, 40);
+    let json = lens.deserialize_transaction_with_idl_history(new).unwrap();
+    assert_eq!(decoded_args(json), json!({ "delta": 5 }));
+}
+
+#[test]
+fn registry_versions() {
+    let program_id = Pubkey::new_unique();
+    let idl = |arg: &str| {
+        let idl = VersionedIdl::from_value(counter_idl("counter", arg)).unwrap();
+        IdlWithDiscriminators::from_versioned(idl).unwrap()
+    };
+    let mut registry = IdlRegistry::new();
+    registry.insert_version(program_id, 100, idl("amount"));
+    registry.insert_version(program_id, 200, idl("delta"));
+    assert!(registry.get_at(&program_id, 99).is_none());
+    assert_eq!(
+        increment_arg(registry.get_at(&program_id, 150).unwrap()),
+        "amount"
+    );
+    assert_eq!(
+        increment_arg(registry.get_at(&program_id, 200).unwrap()),
+        "delta"
+    );
+
+    // Before the first version, the unversioned IDL is used.
+    registry.insert(program_id, idl("current"));
+    assert_eq!(
+        increment_arg(registry.get_at(&program_id, 99).unwrap()),
+        "current"
+    );
+}
+
+#[test]
+fn no_idl_was_live_before_the_first_version() {
+    let Fixture {
+        source,
+        program_id,
+        authority,
+    } = fixture();
+    let lens = AnchorLens::new_with_idl_caching(source);
+    lens.fetch_idl_history(&program_id).unwrap();
+    let err = lens.fetch_idl_at(&program_id, Some(5)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            LensError::IdlNotLive { program_id: id, slot: 5, first_slot: 11 } if id == program_id
+        ),
+        "{}",
+        err
+    );
+    let json = lens
+        .deserialize_transaction(increment(program_id, authority, 5))
+        .unwrap();
+    let ix = &json["instructions"][0];
+    assert!(ix.get("unknown_ix").is_some());
+    assert_eq!(ix["decode_error"], json!(err.to_string()));
+}
+
+#[test]
+fn registered_versions_replace_the_history() {
+    let Fixture {
+        source,
+        program_id,
+        authority,
+    } = fixture();
+    let mut lens = AnchorLens::new_with_idl_caching(source);
+    let idl = VersionedIdl::from_value(counter_idl("counter", "registered")).unwrap();
+    lens.idl_registry.insert_version(
+        program_id,
+        100,
+        IdlWithDiscriminators::from_versioned(idl).unwrap(),
+    );
+    let json = lens
+        .deserialize_transaction_with_idl_history(increment(program_id, authority, 150))
+        .unwrap();
+    assert_eq!(
+        json["instructions"][0]["instruction"]["data"],
+        json!({ "registered": 5 })
+    );
+    assert!(lens.idl_cache.history(&program_id).is_none());
+    // The registered versions stand in for the history, even before the first of them.
+    let json = lens
+        .deserialize_transaction_with_idl_history(increment(program_id, authority, 15))
+        .unwrap();
+    assert!(json["instructions"][0]["decode_error"]
+        .as_str()
+        .unwrap()
+        .starts_with("No IDL was live"));
+}
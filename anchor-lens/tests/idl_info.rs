@@ -0,0 +1,78 @@
+//! Auditing the IDL accounts programs publish.
+
+mod common;
+
+use anchor_lang::idl::IdlAccount;
+use borsh::BorshSerialize;
+use common::{account, compress_idl, counter_idl, discriminator};
+use solana_anchor_lens::fetch_idl::fetch_idl_with_metadata;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn reports_idl_account_metadata() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let idl_addr = IdlAccount::address(&program_id);
+    let idl = counter_idl("counter", "amount");
+    let compressed = compress_idl(&idl);
+    // IDL accounts are allocated with room to spare for upgrades.
+    let mut data = [
+        discriminator("account:IdlAccount"),
+        authority.to_bytes().to_vec(),
+        compressed.try_to_vec().unwrap(),
+    ]
+    .concat();
+    data.resize(data.len() + 100, 0);
+
+    let mut source = InMemorySource::new();
+    source.add_account(program_id, account(Pubkey::new_unique(), vec![], true));
+    source.add_account(idl_addr, account(program_id, data.clone(), false));
+    let lens = AnchorLens::new(&source);
+
+    let fetched = lens.fetch_idl_with_metadata(&program_id).unwrap();
+    assert_eq!(fetched.idl.name, "counter");
+    assert_eq!(fetched.idl.address, Some(program_id));
+    assert_eq!(fetched.program_id, program_id);
+    assert_eq!(fetched.idl_account, idl_addr);
+    assert!(!fetched.is_buffer);
+    assert_eq!(fetched.authority, authority);
+    assert_eq!(fetched.data_len, data.len());
+    assert_eq!(fetched.compressed_len, compressed.len());
+    assert_eq!(fetched.decompressed_len, idl.to_string().len());
+    assert_eq!(fetched.free_space(), 100);
+    // An in-memory source doesn't know the slot.
+    assert_eq!(fetched.slot, None);
+
+    let info = serde_json::to_value(fetched.info()).unwrap();
+    assert_eq!(info["authority"], authority.to_string());
+    assert_eq!(info["program_id"], program_id.to_string());
+
+    // The IDL account can be given directly, and the program is its owner.
+    let fetched = fetch_idl_with_metadata(&source, &idl_addr).unwrap();
+    assert_eq!(fetched.authority, authority);
+    assert_eq!(fetched.program_id, program_id);
+    assert_eq!(fetched.idl.address, Some(program_id));
+    assert!(!fetched.is_buffer);
+
+    let missing = Pubkey::new_unique();
+    let err = lens.fetch_idl_with_metadata(&missing).unwrap_err();
+    assert!(
+        matches!(err, LensError::IdlAccountNotFound { program_id } if program_id == missing),
+        "{}",
+        err
+    );
+
+    // A buffer staged for an upgrade can be given directly too.
+    let buffer = Pubkey::new_unique();
+    source.add_account(buffer, account(program_id, data, false));
+    let fetched = fetch_idl_with_metadata(&source, &buffer).unwrap();
+    assert_eq!(fetched.program_id, program_id);
+    assert_eq!(fetched.idl_account, buffer);
+    assert!(fetched.is_buffer);
+    assert_eq!(
+        serde_json::to_value(fetched.info()).unwrap()["is_buffer"],
+        true
+    );
+}
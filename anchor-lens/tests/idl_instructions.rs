@@ -0,0 +1,176 @@
+//! Decoding Anchor's built-in IDL management instructions.
+
+mod common;
+
+use anchor_lang::idl::{IdlAccount, IdlInstruction, IDL_IX_TAG};
+use borsh::BorshSerialize;
+use common::{add_program, compress_idl, counter_idl};
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+
+fn idl_ix(program_id: Pubkey, ix: IdlInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    let data = [IDL_IX_TAG.to_le_bytes().to_vec(), ix.try_to_vec().unwrap()].concat();
+    Instruction::new_with_bytes(program_id, &data, accounts)
+}
+
+fn transaction(ixs: &[Instruction], payer: Pubkey) -> HistoricalTransaction {
+    HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(ixs, Some(&payer))))
+}
+
+fn account_names(ix: &Value) -> Vec<&str> {
+    ix["idl_instruction"]["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| account["name"].as_str().unwrap())
+        .collect()
+}
+
+/// `anchor idl init`: creates the IDL account, then writes the compressed IDL into it.
+fn idl_init(program_id: Pubkey, authority: Pubkey, idl: &Value) -> HistoricalTransaction {
+    let idl_addr = IdlAccount::address(&program_id);
+    let compressed = compress_idl(idl);
+    let (start, end) = compressed.split_at(compressed.len() / 2);
+    let write = |data: &[u8]| {
+        idl_ix(
+            program_id,
+            IdlInstruction::Write {
+                data: data.to_vec(),
+            },
+            vec![
+                AccountMeta::new(idl_addr, false),
+                AccountMeta::new_readonly(authority, true),
+            ],
+        )
+    };
+    let create = idl_ix(
+        program_id,
+        IdlInstruction::Create { data_len: 1000 },
+        vec![
+            AccountMeta::new(authority, true),
+            AccountMeta::new(idl_addr, false),
+            AccountMeta::new_readonly(Pubkey::new_unique(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(program_id, false),
+        ],
+    );
+    transaction(&[create, write(start), write(end)], authority)
+}
+
+#[test]
+fn decodes_idl_instructions() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    let lens = AnchorLens::new_with_idl_caching(source);
+
+    let json = lens
+        .deserialize_transaction(idl_init(
+            program_id,
+            authority,
+            &counter_idl("counter", "amount"),
+        ))
+        .unwrap();
+    let instructions = json["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["idl_instruction"]["name"], "create");
+    assert_eq!(
+        instructions[0]["idl_instruction"]["data"],
+        json!({ "data_len": 1000 })
+    );
+    assert_eq!(
+        account_names(&instructions[0]),
+        vec!["from", "to", "base", "system_program", "program"]
+    );
+    assert_eq!(instructions[1]["idl_instruction"]["name"], "write");
+    assert_eq!(account_names(&instructions[1]), vec!["idl", "authority"]);
+    // IDLs are only reassembled on request.
+    assert!(instructions
+        .iter()
+        .all(|ix| ix.get("written_idl").is_none()));
+
+    let new_authority = Pubkey::new_unique();
+    let set_authority = idl_ix(
+        program_id,
+        IdlInstruction::SetAuthority { new_authority },
+        vec![
+            AccountMeta::new(IdlAccount::address(&program_id), false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+    );
+    let json = lens
+        .deserialize_transaction(transaction(&[set_authority], authority))
+        .unwrap();
+    let ix = &json["instructions"][0]["idl_instruction"];
+    assert_eq!(ix["name"], "set_authority");
+    assert_eq!(
+        ix["data"],
+        json!({ "new_authority": new_authority.to_string() })
+    );
+}
+
+#[test]
+fn reassembles_written_idls() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut lens = AnchorLens::new(InMemorySource::new());
+    lens.decode_idl_writes = true;
+
+    let idl = counter_idl("counter", "amount");
+    let json = lens
+        .deserialize_transaction(idl_init(program_id, authority, &idl))
+        .unwrap();
+    let instructions = json["instructions"].as_array().unwrap();
+    // The IDL is attached to the write that completed it.
+    assert!(instructions[1].get("written_idl").is_none());
+    assert_eq!(instructions[2]["written_idl"], idl);
+}
+
+#[test]
+fn decodes_close_and_resize() {
+    // Added in Anchor 0.29, after the `IdlInstruction` this crate builds against.
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let idl_addr = IdlAccount::address(&program_id);
+    let raw_ix = |data: &[u8], accounts| {
+        let data = [&IDL_IX_TAG.to_le_bytes()[..], data].concat();
+        Instruction::new_with_bytes(program_id, &data, accounts)
+    };
+    let close = raw_ix(
+        &[5],
+        vec![
+            AccountMeta::new(idl_addr, false),
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new(authority, false),
+        ],
+    );
+    let resize = raw_ix(
+        &[&[6u8][..], &2000u64.to_le_bytes()].concat(),
+        vec![
+            AccountMeta::new(idl_addr, false),
+            AccountMeta::new(authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    let lens = AnchorLens::new(InMemorySource::new());
+    let json = lens
+        .deserialize_transaction(transaction(&[close, resize], authority))
+        .unwrap();
+    let instructions = json["instructions"].as_array().unwrap();
+    assert_eq!(instructions[0]["idl_instruction"]["name"], "close");
+    assert_eq!(
+        account_names(&instructions[0]),
+        vec!["idl", "authority", "sol_destination"]
+    );
+    assert_eq!(instructions[1]["idl_instruction"]["name"], "resize");
+    assert_eq!(
+        instructions[1]["idl_instruction"]["data"],
+        json!({ "data_len": 2000 })
+    );
+}
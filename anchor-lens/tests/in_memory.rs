@@ -0,0 +1,193 @@
+//! The whole pipeline, from IDL account to decoded account and transaction,
+//! running against an in-memory source instead of an RPC node.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{account, add_program, counter_idl, discriminator, CountingSource};
+use serde_json::json;
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(BorshSerialize)]
+struct Counter {
+    count: u64,
+    authority: Pubkey,
+}
+
+struct Fixture {
+    source: InMemorySource,
+    program_id: Pubkey,
+    counter: Pubkey,
+    authority: Pubkey,
+}
+
+fn fixture() -> Fixture {
+    let program_id = Pubkey::new_unique();
+    let counter = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    let counter_data = [
+        discriminator("account:Counter"),
+        Counter {
+            count: 41,
+            authority,
+        }
+        .try_to_vec()
+        .unwrap(),
+    ]
+    .concat();
+    source.add_account(counter, account(program_id, counter_data, false));
+    Fixture {
+        source,
+        program_id,
+        counter,
+        authority,
+    }
+}
+
+#[test]
+fn decodes_an_account() {
+    let Fixture {
+        source,
+        counter,
+        authority,
+        ..
+    } = fixture();
+    let lens = AnchorLens::new(source);
+    let account = lens.fetch_and_deserialize_account(&counter, None).unwrap();
+    assert_eq!(account.program_name, "counter");
+    assert_eq!(account.type_name, "Counter");
+    assert_eq!(
+        account.data,
+        json!({ "count": 41, "authority": authority.to_string() })
+    );
+}
+
+#[test]
+fn decodes_a_transaction() {
+    let Fixture {
+        mut source,
+        program_id,
+        counter,
+        authority,
+    } = fixture();
+    let data = [
+        discriminator("global:increment"),
+        5u64.try_to_vec().unwrap(),
+    ]
+    .concat();
+    let ix = Instruction::new_with_bytes(
+        program_id,
+        &data,
+        vec![
+            AccountMeta::new(counter, false),
+            AccountMeta::new_readonly(authority, true),
+        ],
+    );
+    let signature = Signature::new_unique();
+    source.add_transaction(
+        signature,
+        HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+            &[ix],
+            Some(&authority),
+        ))),
+    );
+
+    let lens = AnchorLens::new_with_idl_caching(source);
+    let tx = lens.get_versioned_transaction(&signature).unwrap();
+    assert_eq!(tx.program_ids(), vec![program_id]);
+    let json = lens.deserialize_transaction(tx).unwrap();
+    let instruction = &json["instructions"][0]["instruction"];
+    assert_eq!(instruction["name"], "increment");
+    assert_eq!(instruction["data"], json!({ "amount": 5 }));
+    assert_eq!(instruction["accounts"][0]["pubkey"], counter.to_string());
+    assert_eq!(instruction["accounts"][0]["is_mut"], "true");
+    // The fee payer is writable, though the IDL only needs it to sign.
+    assert_eq!(
+        instruction["accounts"][1]["is_mut"],
+        "unnecessary_privilege_escalation"
+    );
+    assert_eq!(json["error"], json!(null));
+}
+
+#[test]
+fn missing_accounts_and_idls() {
+    let Fixture { source, .. } = fixture();
+    let lens = AnchorLens::new(source);
+
+    let missing = Pubkey::new_unique();
+    let err = lens.get_account(&missing).unwrap_err();
+    assert!(
+        matches!(err, LensError::AccountNotFound { pubkey } if pubkey == missing),
+        "{}",
+        err
+    );
+
+    let err = lens.fetch_idl(&missing).unwrap_err();
+    assert!(
+        matches!(err, LensError::IdlAccountNotFound { program_id } if program_id == missing),
+        "{}",
+        err
+    );
+
+    let signature = Signature::new_unique();
+    let err = lens.get_versioned_transaction(&signature).unwrap_err();
+    assert!(
+        matches!(err, LensError::TransactionNotFound { signature: sig } if sig == signature),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn idl_cache_is_shared_between_threads() {
+    let Fixture {
+        source, program_id, ..
+    } = fixture();
+    let lens = Arc::new(AnchorLens::new_with_idl_caching(CountingSource::new(
+        source,
+    )));
+    let first = lens.fetch_idl(&program_id).unwrap();
+    // The program account, then its IDL account.
+    assert_eq!(lens.client.reads(), 2);
+
+    let handles = (0..4)
+        .map(|_| {
+            let lens = lens.clone();
+            thread::spawn(move || lens.fetch_idl(&program_id).unwrap())
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        assert!(Arc::ptr_eq(&handle.join().unwrap(), &first));
+    }
+    assert_eq!(lens.client.reads(), 2);
+}
+
+#[test]
+fn programs_without_an_idl_are_cached() {
+    let Fixture { source, .. } = fixture();
+    let lens = AnchorLens::new_with_idl_caching(CountingSource::new(source));
+    let missing = Pubkey::new_unique();
+    for _ in 0..3 {
+        let err = lens.fetch_idl(&missing).unwrap_err();
+        assert!(
+            matches!(err, LensError::IdlAccountNotFound { program_id } if program_id == missing),
+            "{}",
+            err
+        );
+    }
+    assert_eq!(lens.client.reads(), 1);
+
+    lens.idl_cache.remove(&missing);
+    assert!(lens.fetch_idl(&missing).is_err());
+    assert_eq!(lens.client.reads(), 2);
+}
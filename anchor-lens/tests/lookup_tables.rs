@@ -0,0 +1,119 @@
+//! Decoding v0 transactions, whose accounts can be loaded from address lookup tables.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{account, add_program, counter_idl, discriminator};
+use solana_anchor_lens::deserialize::native::address_lookup_table;
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_program::hash::Hash;
+use solana_program::instruction::CompiledInstruction;
+use solana_program::message::v0::{self, LoadedAddresses, MessageAddressTableLookup};
+use solana_program::message::{MessageHeader, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+
+/// A lookup table holding `addresses`, with room for its 56 byte header.
+fn lookup_table(addresses: &[Pubkey]) -> Vec<u8> {
+    let mut data = [
+        1u32.to_le_bytes().to_vec(),
+        u64::MAX.to_le_bytes().to_vec(),
+        0u64.to_le_bytes().to_vec(),
+        vec![0, 0],
+    ]
+    .concat();
+    data.resize(56, 0);
+    for address in addresses {
+        data.extend(address.to_bytes());
+    }
+    data
+}
+
+/// An `increment` whose counter account, at index 2, comes from `table`.
+fn increment(program_id: Pubkey, authority: Pubkey, table: Pubkey) -> VersionedMessage {
+    VersionedMessage::V0(v0::Message {
+        header: MessageHeader {
+            num_required_signatures: 1,
+            num_readonly_signed_accounts: 0,
+            num_readonly_unsigned_accounts: 1,
+        },
+        account_keys: vec![authority, program_id],
+        recent_blockhash: Hash::default(),
+        instructions: vec![CompiledInstruction {
+            program_id_index: 1,
+            accounts: vec![2, 0],
+            data: [
+                discriminator("global:increment"),
+                5u64.try_to_vec().unwrap(),
+            ]
+            .concat(),
+        }],
+        address_table_lookups: vec![MessageAddressTableLookup {
+            account_key: table,
+            writable_indexes: vec![1],
+            readonly_indexes: vec![],
+        }],
+    })
+}
+
+#[test]
+fn accounts_come_from_the_loaded_addresses() {
+    let program_id = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let counter = Pubkey::new_unique();
+    let table = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    source.add_account(
+        table,
+        account(
+            address_lookup_table::ID,
+            lookup_table(&[Pubkey::new_unique(), counter]),
+            false,
+        ),
+    );
+    let lens = AnchorLens::new_with_idl_caching(source);
+    let message = increment(program_id, authority, table);
+
+    // Historical transactions come with the addresses the runtime loaded.
+    let mut tx = HistoricalTransaction::new(message.clone());
+    tx.loaded_addresses = LoadedAddresses {
+        writable: vec![counter],
+        readonly: vec![],
+    };
+    assert_eq!(tx.program_ids(), vec![program_id]);
+    let json = lens.deserialize_transaction(tx).unwrap();
+    let accounts = &json["instructions"][0]["instruction"]["accounts"];
+    assert_eq!(accounts[0]["name"], "counter");
+    assert_eq!(accounts[0]["pubkey"], counter.to_string());
+    // Looked up accounts are writable when listed under `writable_indexes`.
+    assert_eq!(accounts[0]["is_mut"], "true");
+    assert_eq!(accounts[1]["pubkey"], authority.to_string());
+
+    // Messages that haven't run have their lookup tables fetched instead.
+    let json = lens.deserialize_message(&message).unwrap();
+    assert_eq!(
+        json["instructions"][0]["instruction"]["accounts"],
+        *accounts
+    );
+}
+
+#[test]
+fn out_of_range_lookups_are_errors() {
+    let program_id = Pubkey::new_unique();
+    let table = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "amount"));
+    source.add_account(
+        table,
+        account(address_lookup_table::ID, lookup_table(&[]), false),
+    );
+    let lens = AnchorLens::new_with_idl_caching(source);
+    let err = lens
+        .deserialize_message(&increment(program_id, Pubkey::new_unique(), table))
+        .unwrap_err();
+    assert!(err.to_string().contains("out of bounds"), "{}", err);
+    // Failures without a variant of their own keep their message.
+    assert!(matches!(err, LensError::Source(_)), "{:?}", err);
+}
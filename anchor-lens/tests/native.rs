@@ -0,0 +1,591 @@
+//! Decoding the native and SPL programs, which don't publish an IDL.
+
+mod common;
+
+use common::CountingSource;
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::native::deser_native_account;
+use solana_anchor_lens::deserialize::native::{
+    address_lookup_table, associated_token, bpf_loader_upgradeable, compute_budget, stake, token,
+    vote,
+};
+use solana_anchor_lens::deserialize::{lookup_table_addresses, HistoricalTransaction, Strictness};
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::hash::Hash;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_instruction, system_program};
+
+fn decode(ixs: &[Instruction], payer: Pubkey) -> Vec<Value> {
+    decode_transaction(ixs, payer, None)["instructions"]
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+fn decode_transaction(
+    ixs: &[Instruction],
+    payer: Pubkey,
+    compute_units_consumed: Option<u64>,
+) -> Value {
+    let mut tx =
+        HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(ixs, Some(&payer))));
+    tx.compute_units_consumed = compute_units_consumed;
+    let lens = AnchorLens::new(CountingSource::new(InMemorySource::new()));
+    let json = lens.deserialize_transaction(tx).unwrap();
+    // Native programs are decoded without looking for an IDL.
+    assert_eq!(lens.client.reads(), 0);
+    json
+}
+
+fn accounts(ix: &Value) -> Vec<(&str, &str, &str)> {
+    ix["instruction"]["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| {
+            (
+                account["name"].as_str().unwrap(),
+                account["is_signer"].as_str().unwrap(),
+                account["is_mut"].as_str().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn token_ix(program_id: Pubkey, data: Vec<u8>, accounts: Vec<AccountMeta>) -> Instruction {
+    Instruction::new_with_bytes(program_id, &data, accounts)
+}
+
+#[test]
+fn system_instructions() {
+    let payer = Pubkey::new_unique();
+    let new_account = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let ixs = decode(
+        &[
+            system_instruction::transfer(&payer, &Pubkey::new_unique(), 42),
+            system_instruction::create_account(&payer, &new_account, 1000, 165, &owner),
+            system_instruction::create_account_with_seed(
+                &payer,
+                &Pubkey::new_unique(),
+                &payer,
+                "vault",
+                1000,
+                64,
+                &owner,
+            ),
+        ],
+        payer,
+    );
+    assert_eq!(ixs[0]["program_name"], "system_program");
+    assert_eq!(ixs[0]["instruction"]["name"], "transfer");
+    assert_eq!(ixs[0]["instruction"]["data"], json!({ "lamports": 42 }));
+    assert_eq!(
+        accounts(&ixs[0]),
+        vec![("from", "true", "true"), ("to", "false", "true")]
+    );
+    assert_eq!(ixs[1]["instruction"]["name"], "create_account");
+    assert_eq!(
+        ixs[1]["instruction"]["data"],
+        json!({ "lamports": 1000, "space": 165, "owner": owner.to_string() })
+    );
+    assert_eq!(ixs[2]["instruction"]["name"], "create_account_with_seed");
+    assert_eq!(ixs[2]["instruction"]["data"]["seed"], "vault");
+}
+
+#[test]
+fn token_instructions() {
+    let owner = Pubkey::new_unique();
+    let source = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let transfer_checked = [vec![12], 500u64.to_le_bytes().to_vec(), vec![6]].concat();
+    let ixs = decode(
+        &[
+            token_ix(
+                token::TOKEN_PROGRAM_ID,
+                transfer_checked.clone(),
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(owner, true),
+                ],
+            ),
+            // Token-2022 shares the instruction set.
+            token_ix(
+                token::TOKEN_2022_PROGRAM_ID,
+                transfer_checked,
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(owner, true),
+                ],
+            ),
+            token_ix(
+                token::TOKEN_PROGRAM_ID,
+                [vec![6, 2, 1], owner.to_bytes().to_vec()].concat(),
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new_readonly(owner, true),
+                ],
+            ),
+        ],
+        Pubkey::new_unique(),
+    );
+    assert_eq!(ixs[0]["program_name"], "spl_token");
+    assert_eq!(ixs[0]["instruction"]["name"], "transfer_checked");
+    assert_eq!(
+        ixs[0]["instruction"]["data"],
+        json!({ "amount": 500, "decimals": 6 })
+    );
+    assert_eq!(
+        accounts(&ixs[0]),
+        vec![
+            ("source", "false", "true"),
+            ("mint", "false", "false"),
+            ("destination", "false", "true"),
+            ("authority", "true", "false"),
+        ]
+    );
+    assert_eq!(ixs[1]["program_name"], "spl_token_2022");
+    assert_eq!(ixs[1]["instruction"], ixs[0]["instruction"]);
+    assert_eq!(ixs[2]["instruction"]["name"], "set_authority");
+    assert_eq!(
+        ixs[2]["instruction"]["data"],
+        json!({ "authority_type": "account_owner", "new_authority": owner.to_string() })
+    );
+}
+
+#[test]
+fn multisig_authorities() {
+    let payer = Pubkey::new_unique();
+    let multisig = Pubkey::new_unique();
+    let signers = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let ixs = decode(
+        &[token_ix(
+            token::TOKEN_PROGRAM_ID,
+            [vec![3], 7u64.to_le_bytes().to_vec()].concat(),
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(multisig, false),
+                AccountMeta::new_readonly(signers[0], true),
+                AccountMeta::new_readonly(signers[1], true),
+            ],
+        )],
+        payer,
+    );
+    assert_eq!(
+        accounts(&ixs[0]),
+        vec![
+            ("source", "false", "true"),
+            ("destination", "false", "true"),
+            ("authority", "false", "false"),
+            ("signer_0", "true", "false"),
+            ("signer_1", "true", "false"),
+        ]
+    );
+}
+
+#[test]
+fn associated_token_accounts() {
+    let payer = Pubkey::new_unique();
+    let create = |data: &[u8]| {
+        Instruction::new_with_bytes(
+            associated_token::ID,
+            data,
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(payer, false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(token::TOKEN_PROGRAM_ID, false),
+            ],
+        )
+    };
+    let ixs = decode(&[create(&[]), create(&[1])], payer);
+    assert_eq!(ixs[0]["program_name"], "spl_associated_token_account");
+    assert_eq!(ixs[0]["instruction"]["name"], "create");
+    assert_eq!(ixs[1]["instruction"]["name"], "create_idempotent");
+    assert_eq!(
+        accounts(&ixs[1])[..2],
+        [
+            ("funding_account", "true", "true"),
+            ("associated_token_account", "false", "true"),
+        ]
+    );
+}
+
+#[test]
+fn malformed_native_instructions() {
+    let payer = Pubkey::new_unique();
+    let ixs = decode(
+        &[
+            // Truncated transfer amount.
+            token_ix(token::TOKEN_PROGRAM_ID, vec![3, 1, 2], vec![]),
+            // Token-2022 only instruction, sent to the original token program.
+            token_ix(token::TOKEN_PROGRAM_ID, vec![32], vec![]),
+        ],
+        payer,
+    );
+    assert_eq!(ixs[0]["program_name"], "spl_token");
+    assert!(ixs[0]["decode_error"]
+        .as_str()
+        .unwrap()
+        .contains("transfer.amount"));
+    assert_eq!(ixs[1]["unknown_discriminator"], "instruction 1");
+}
+
+#[test]
+fn compute_budget_and_priority_fee() {
+    let payer = Pubkey::new_unique();
+    let budget_ix = |data: Vec<u8>| Instruction::new_with_bytes(compute_budget::ID, &data, vec![]);
+    let transfer = system_instruction::transfer(&payer, &Pubkey::new_unique(), 42);
+    let json = decode_transaction(
+        &[
+            budget_ix([vec![2], 300_000u32.to_le_bytes().to_vec()].concat()),
+            budget_ix([vec![3], 2_500u64.to_le_bytes().to_vec()].concat()),
+            budget_ix([vec![1], (64 * 1024u32).to_le_bytes().to_vec()].concat()),
+            budget_ix([vec![4], 100_000u32.to_le_bytes().to_vec()].concat()),
+            transfer.clone(),
+        ],
+        payer,
+        Some(450),
+    );
+    let ixs = json["instructions"].as_array().unwrap();
+    assert_eq!(ixs[0]["program_name"], "compute_budget");
+    assert_eq!(ixs[0]["instruction"]["name"], "set_compute_unit_limit");
+    assert_eq!(ixs[0]["instruction"]["data"], json!({ "units": 300_000 }));
+    assert_eq!(ixs[1]["instruction"]["name"], "set_compute_unit_price");
+    assert_eq!(
+        ixs[1]["instruction"]["data"],
+        json!({ "micro_lamports": 2_500 })
+    );
+    assert_eq!(ixs[2]["instruction"]["name"], "request_heap_frame");
+    assert_eq!(
+        ixs[3]["instruction"]["name"],
+        "set_loaded_accounts_data_size_limit"
+    );
+    assert_eq!(
+        json["compute_budget"],
+        json!({
+            "compute_unit_limit": 300_000,
+            "effective_compute_unit_limit": 300_000,
+            "compute_unit_price": 2_500,
+            "heap_frame_bytes": 65_536,
+            "loaded_accounts_data_size_limit": 100_000,
+            // 2500 micro-lamports for each of 300k units.
+            "priority_fee": 750,
+            "compute_units_consumed": 450,
+        })
+    );
+
+    // Without a limit, each other instruction gets the default, and the fee rounds up.
+    let json = decode_transaction(
+        &[
+            budget_ix([vec![3], 1u64.to_le_bytes().to_vec()].concat()),
+            transfer.clone(),
+            transfer.clone(),
+        ],
+        payer,
+        None,
+    );
+    assert_eq!(json["compute_budget"]["compute_unit_limit"], Value::Null);
+    assert_eq!(
+        json["compute_budget"]["effective_compute_unit_limit"],
+        400_000
+    );
+    assert_eq!(json["compute_budget"]["priority_fee"], 1);
+    assert_eq!(
+        json["compute_budget"]["compute_units_consumed"],
+        Value::Null
+    );
+
+    // The deprecated instruction sets the limit, and pays its fee in lamports.
+    let json = decode_transaction(
+        &[
+            budget_ix(
+                [
+                    vec![0],
+                    50_000u32.to_le_bytes().to_vec(),
+                    7u32.to_le_bytes().to_vec(),
+                ]
+                .concat(),
+            ),
+            transfer,
+        ],
+        payer,
+        None,
+    );
+    let ixs = json["instructions"].as_array().unwrap();
+    assert_eq!(ixs[0]["instruction"]["name"], "request_units_deprecated");
+    assert_eq!(
+        ixs[0]["instruction"]["data"],
+        json!({ "units": 50_000, "additional_fee": 7 })
+    );
+    assert_eq!(json["compute_budget"]["compute_unit_limit"], 50_000);
+    assert_eq!(json["compute_budget"]["priority_fee"], 7);
+}
+
+#[test]
+fn loader_and_stake_instructions() {
+    let payer = Pubkey::new_unique();
+    let program = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let stake_account = Pubkey::new_unique();
+    let vote_account = Pubkey::new_unique();
+    let ixs = decode(
+        &[
+            solana_program::bpf_loader_upgradeable::upgrade(
+                &program,
+                &Pubkey::new_unique(),
+                &authority,
+                &payer,
+            ),
+            solana_program::bpf_loader_upgradeable::set_upgrade_authority(
+                &program, &authority, None,
+            ),
+            solana_program::stake::instruction::delegate_stake(
+                &stake_account,
+                &authority,
+                &vote_account,
+            ),
+            solana_program::stake::instruction::withdraw(
+                &stake_account,
+                &authority,
+                &payer,
+                5000,
+                None,
+            ),
+        ],
+        payer,
+    );
+    assert_eq!(ixs[0]["program_name"], "bpf_loader_upgradeable");
+    assert_eq!(ixs[0]["instruction"]["name"], "upgrade");
+    assert_eq!(accounts(&ixs[0])[6], ("upgrade_authority", "true", "false"));
+    assert_eq!(ixs[1]["instruction"]["name"], "set_authority");
+    // Making the program immutable leaves out the new authority.
+    assert_eq!(accounts(&ixs[1]).len(), 2);
+    assert_eq!(ixs[2]["program_name"], "stake_program");
+    assert_eq!(ixs[2]["instruction"]["name"], "delegate_stake");
+    assert_eq!(accounts(&ixs[2])[1], ("vote", "false", "false"));
+    assert_eq!(ixs[3]["instruction"]["name"], "withdraw");
+    assert_eq!(ixs[3]["instruction"]["data"], json!({ "lamports": 5000 }));
+    assert_eq!(accounts(&ixs[3]).len(), 5);
+}
+
+#[test]
+fn vote_and_lookup_table_instructions() {
+    let payer = Pubkey::new_unique();
+    let vote_authority = Pubkey::new_unique();
+    let hash = Hash::new_unique();
+    let block_id = Hash::new_unique();
+    // A tower sync rooted at slot 100, with lockouts 2 and 3 slots after it.
+    let tower_sync = [
+        14u32.to_le_bytes().to_vec(),
+        100u64.to_le_bytes().to_vec(),
+        vec![2, 2, 31, 1, 1],
+        hash.to_bytes().to_vec(),
+        vec![1],
+        1_700_000_000i64.to_le_bytes().to_vec(),
+        block_id.to_bytes().to_vec(),
+    ]
+    .concat();
+    let new_address = Pubkey::new_unique();
+    let extend = [
+        2u32.to_le_bytes().to_vec(),
+        1u64.to_le_bytes().to_vec(),
+        new_address.to_bytes().to_vec(),
+    ]
+    .concat();
+    let lookup_table = Pubkey::new_unique();
+    let ixs = decode(
+        &[
+            Instruction::new_with_bytes(
+                vote::ID,
+                &tower_sync,
+                vec![
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                    AccountMeta::new_readonly(vote_authority, true),
+                ],
+            ),
+            Instruction::new_with_bytes(
+                address_lookup_table::ID,
+                &extend,
+                vec![
+                    AccountMeta::new(lookup_table, false),
+                    AccountMeta::new_readonly(vote_authority, true),
+                ],
+            ),
+        ],
+        payer,
+    );
+    assert_eq!(ixs[0]["program_name"], "vote_program");
+    assert_eq!(ixs[0]["instruction"]["name"], "tower_sync");
+    assert_eq!(
+        ixs[0]["instruction"]["data"],
+        json!({
+            "lockouts": [
+                { "slot": 102, "confirmation_count": 31 },
+                { "slot": 103, "confirmation_count": 1 },
+            ],
+            "root": 100,
+            "hash": hash.to_string(),
+            "timestamp": 1_700_000_000i64,
+            "block_id": block_id.to_string(),
+        })
+    );
+    assert_eq!(ixs[1]["program_name"], "address_lookup_table");
+    assert_eq!(ixs[1]["instruction"]["name"], "extend_lookup_table");
+    assert_eq!(
+        ixs[1]["instruction"]["data"],
+        json!({ "new_addresses": [new_address.to_string()] })
+    );
+    // Extending a table that has room doesn't need a payer.
+    assert_eq!(accounts(&ixs[1]).len(), 2);
+}
+
+#[test]
+fn native_account_states() {
+    let authority = Pubkey::new_unique();
+    let program_data = [
+        3u32.to_le_bytes().to_vec(),
+        42u64.to_le_bytes().to_vec(),
+        vec![1],
+        authority.to_bytes().to_vec(),
+        vec![0x7f, b'E', b'L', b'F'],
+    ]
+    .concat();
+    let (type_name, value) = deser_native_account(&bpf_loader_upgradeable::ID, &program_data)
+        .unwrap()
+        .unwrap();
+    assert_eq!(type_name, "ProgramData");
+    assert_eq!(
+        value,
+        json!({ "slot": 42, "upgrade_authority_address": authority.to_string(), "data_len": 4 })
+    );
+
+    let staker = Pubkey::new_unique();
+    let voter = Pubkey::new_unique();
+    let stake_state = [
+        2u32.to_le_bytes().to_vec(),
+        2_282_880u64.to_le_bytes().to_vec(),
+        staker.to_bytes().to_vec(),
+        staker.to_bytes().to_vec(),
+        0i64.to_le_bytes().to_vec(),
+        0u64.to_le_bytes().to_vec(),
+        Pubkey::default().to_bytes().to_vec(),
+        voter.to_bytes().to_vec(),
+        1_000_000u64.to_le_bytes().to_vec(),
+        10u64.to_le_bytes().to_vec(),
+        u64::MAX.to_le_bytes().to_vec(),
+        0.25f64.to_le_bytes().to_vec(),
+        7u64.to_le_bytes().to_vec(),
+        vec![0],
+    ]
+    .concat();
+    let (type_name, value) = deser_native_account(&stake::ID, &stake_state)
+        .unwrap()
+        .unwrap();
+    assert_eq!(type_name, "Stake");
+    assert_eq!(
+        value["meta"]["authorized"]["withdrawer"],
+        staker.to_string()
+    );
+    assert_eq!(
+        value["stake"]["delegation"],
+        json!({
+            "voter_pubkey": voter.to_string(),
+            "stake": 1_000_000,
+            "activation_epoch": 10,
+            "deactivation_epoch": u64::MAX,
+            "warmup_cooldown_rate": 0.25,
+        })
+    );
+
+    let addresses = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let lookup_table = [
+        1u32.to_le_bytes().to_vec(),
+        u64::MAX.to_le_bytes().to_vec(),
+        300u64.to_le_bytes().to_vec(),
+        vec![0],
+        vec![1],
+        authority.to_bytes().to_vec(),
+        vec![0, 0],
+        addresses[0].to_bytes().to_vec(),
+        addresses[1].to_bytes().to_vec(),
+    ]
+    .concat();
+    let (type_name, value) = deser_native_account(&address_lookup_table::ID, &lookup_table)
+        .unwrap()
+        .unwrap();
+    assert_eq!(type_name, "LookupTable");
+    assert_eq!(
+        value,
+        json!({
+            "meta": {
+                "deactivation_slot": null,
+                "last_extended_slot": 300,
+                "last_extended_slot_start_index": 0,
+                "authority": authority.to_string(),
+            },
+            "addresses": [addresses[0].to_string(), addresses[1].to_string()],
+        })
+    );
+    // Address lookups are resolved with the same parser.
+    assert_eq!(lookup_table_addresses(&lookup_table).unwrap(), addresses);
+}
+
+#[test]
+fn native_trailing_bytes_follow_strictness() {
+    let payer = Pubkey::new_unique();
+    let mut transfer = system_instruction::transfer(&payer, &Pubkey::new_unique(), 42);
+    transfer.data.extend([1, 2]);
+    let tx = HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(
+        &[transfer],
+        Some(&payer),
+    )));
+    let mut lens = AnchorLens::new(InMemorySource::new());
+    let json = lens.deserialize_transaction(tx.clone()).unwrap();
+    assert_eq!(json["instructions"][0]["instruction"]["name"], "transfer");
+    assert!(json["instructions"][0]["instruction"]
+        .get("warnings")
+        .is_none());
+
+    lens.strictness = Strictness::Warn;
+    let json = lens.deserialize_transaction(tx.clone()).unwrap();
+    assert_eq!(
+        json["instructions"][0]["instruction"]["warnings"],
+        json!([{ "path": "transfer", "offset": 12, "message": "2 trailing bytes" }])
+    );
+
+    lens.strictness = Strictness::Strict;
+    let json = lens.deserialize_transaction(tx).unwrap();
+    let ix = &json["instructions"][0];
+    assert_eq!(ix["program_name"], "system_program");
+    assert!(ix["decode_error"]
+        .as_str()
+        .unwrap()
+        .contains("2 trailing bytes"));
+}
+
+#[test]
+fn unknown_native_instructions_keep_the_program_name() {
+    let payer = Pubkey::new_unique();
+    let ixs = decode(
+        &[Instruction::new_with_bytes(
+            compute_budget::ID,
+            &[200],
+            vec![],
+        )],
+        payer,
+    );
+    assert_eq!(ixs[0]["program_name"], "compute_budget");
+    assert_eq!(ixs[0]["unknown_discriminator"], "instruction 0");
+}
@@ -0,0 +1,414 @@
+//! Decoding native Borsh programs through Shank and Codama IDLs.
+
+mod common;
+
+use borsh::BorshSerialize;
+use common::{account, add_program};
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::instruction::deser_ix_data_from_idl;
+use solana_anchor_lens::deserialize::{deserialize_account_from_idl, HistoricalTransaction};
+use solana_anchor_lens::fetch_idl::discriminators::IdlWithDiscriminators;
+use solana_anchor_lens::fetch_idl::versioned::VersionedIdl;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+const PROGRAM_ID: &str = "Counter111111111111111111111111111111111111";
+
+fn number(format: &str) -> Value {
+    json!({ "kind": "numberTypeNode", "format": format, "endian": "le" })
+}
+
+fn u32_prefixed(ty: Value) -> Value {
+    json!({ "kind": "sizePrefixTypeNode", "type": ty, "prefix": number("u32") })
+}
+
+fn codama_idl() -> Value {
+    json!({
+        "kind": "rootNode",
+        "standard": "codama",
+        "version": "1.0.0",
+        "program": {
+            "kind": "programNode",
+            "name": "counter",
+            "publicKey": PROGRAM_ID,
+            "version": "0.1.0",
+            "accounts": [{
+                "kind": "accountNode",
+                "name": "counter",
+                "data": {
+                    "kind": "structTypeNode",
+                    "fields": [
+                        {
+                            "kind": "structFieldNode",
+                            "name": "key",
+                            "type": { "kind": "definedTypeLinkNode", "name": "key" },
+                            "defaultValue": {
+                                "kind": "enumValueNode",
+                                "enum": { "kind": "definedTypeLinkNode", "name": "key" },
+                                "variant": "counter",
+                            },
+                        },
+                        { "kind": "structFieldNode", "name": "authority", "type": { "kind": "publicKeyTypeNode" } },
+                        {
+                            "kind": "structFieldNode",
+                            "name": "count",
+                            "type": { "kind": "definedTypeLinkNode", "name": "amount" },
+                        },
+                        {
+                            "kind": "structFieldNode",
+                            "name": "last",
+                            "type": {
+                                "kind": "tupleTypeNode",
+                                "items": [number("u8"), { "kind": "booleanTypeNode", "size": number("u8") }],
+                            },
+                        },
+                    ],
+                },
+                "discriminators": [{ "kind": "fieldDiscriminatorNode", "name": "key", "offset": 0 }],
+            }],
+            "instructions": [
+                {
+                    "kind": "instructionNode",
+                    "name": "createCounter",
+                    "accounts": [
+                        { "kind": "instructionAccountNode", "name": "counter", "isWritable": true, "isSigner": true },
+                        { "kind": "instructionAccountNode", "name": "payer", "isWritable": true, "isSigner": true },
+                    ],
+                    "arguments": [
+                        {
+                            "kind": "instructionArgumentNode",
+                            "name": "discriminator",
+                            "type": number("u8"),
+                            "defaultValue": { "kind": "numberValueNode", "number": 0 },
+                            "defaultValueStrategy": "omitted",
+                        },
+                        {
+                            "kind": "instructionArgumentNode",
+                            "name": "initial",
+                            "type": { "kind": "definedTypeLinkNode", "name": "amount" },
+                        },
+                        {
+                            "kind": "instructionArgumentNode",
+                            "name": "label",
+                            "type": u32_prefixed(json!({ "kind": "stringTypeNode", "encoding": "utf8" })),
+                        },
+                        {
+                            "kind": "instructionArgumentNode",
+                            "name": "limits",
+                            "type": {
+                                "kind": "mapTypeNode",
+                                "key": u32_prefixed(json!({ "kind": "stringTypeNode", "encoding": "utf8" })),
+                                "value": number("u16"),
+                                "count": { "kind": "prefixedCountNode", "prefix": number("u32") },
+                            },
+                        },
+                    ],
+                    "discriminators": [{ "kind": "fieldDiscriminatorNode", "name": "discriminator", "offset": 0 }],
+                },
+                {
+                    "kind": "instructionNode",
+                    "name": "increment",
+                    "accounts": [
+                        { "kind": "instructionAccountNode", "name": "counter", "isWritable": true, "isSigner": false },
+                        { "kind": "instructionAccountNode", "name": "authority", "isWritable": false, "isSigner": true },
+                    ],
+                    "arguments": [{
+                        "kind": "instructionArgumentNode",
+                        "name": "by",
+                        "type": { "kind": "optionTypeNode", "item": number("u32"), "prefix": number("u8") },
+                    }],
+                    "discriminators": [{
+                        "kind": "constantDiscriminatorNode",
+                        "offset": 0,
+                        "constant": {
+                            "kind": "constantValueNode",
+                            "type": { "kind": "bytesTypeNode" },
+                            "value": { "kind": "bytesValueNode", "encoding": "base16", "data": "0a0b" },
+                        },
+                    }],
+                },
+            ],
+            "definedTypes": [
+                {
+                    "kind": "definedTypeNode",
+                    "name": "key",
+                    "type": {
+                        "kind": "enumTypeNode",
+                        "variants": [
+                            { "kind": "enumEmptyVariantTypeNode", "name": "uninitialized" },
+                            { "kind": "enumEmptyVariantTypeNode", "name": "counter" },
+                        ],
+                        "size": number("u8"),
+                    },
+                },
+                { "kind": "definedTypeNode", "name": "amount", "type": number("u64") },
+            ],
+            "errors": [{ "kind": "errorNode", "name": "overflow", "code": 0, "message": "Counter overflowed" }],
+            "pdas": [],
+        },
+        "additionalPrograms": [],
+    })
+}
+
+fn shank_idl() -> Value {
+    json!({
+        "version": "0.1.0",
+        "name": "vault",
+        "instructions": [
+            {
+                "name": "Deposit",
+                "accounts": [
+                    { "name": "vault", "isMut": true, "isSigner": false },
+                    { "name": "owner", "isMut": false, "isSigner": true, "desc": "The vault's owner" },
+                ],
+                "args": [{ "name": "amount", "type": "u64" }],
+                "discriminant": { "type": "u8", "value": 0 },
+            },
+            {
+                "name": "Configure",
+                "accounts": [{ "name": "vault", "isMut": true, "isSigner": false }],
+                "args": [
+                    { "name": "range", "type": { "tuple": ["u8", "u16"] } },
+                    { "name": "fees", "type": { "hashMap": ["string", "u8"] } },
+                    { "name": "members", "type": { "option": { "bTreeSet": "publicKey" } } },
+                ],
+                "discriminant": { "type": "u8", "value": 1 },
+            },
+        ],
+        "accounts": [
+            {
+                "name": "Vault",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "key", "type": { "defined": "Key" } },
+                        { "name": "owner", "type": "publicKey" },
+                        { "name": "balance", "type": "u64" },
+                    ],
+                },
+            },
+            {
+                "name": "Position",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "key", "type": { "defined": "Key" } },
+                        { "name": "shares", "type": "u64" },
+                    ],
+                },
+            },
+            {
+                "name": "Receipt",
+                "type": {
+                    "kind": "struct",
+                    "fields": [{ "name": "amount", "type": "u64" }],
+                },
+            },
+        ],
+        "types": [{
+            "name": "Key",
+            "type": {
+                "kind": "enum",
+                "variants": [
+                    { "name": "Uninitialized" },
+                    { "name": "VaultV1" },
+                    { "name": "PositionV1" },
+                    { "name": "PositionV2" },
+                ],
+            },
+        }],
+        "metadata": { "origin": "shank", "address": PROGRAM_ID },
+    })
+}
+
+fn load(idl: Value) -> IdlWithDiscriminators {
+    IdlWithDiscriminators::from_versioned(VersionedIdl::from_value(idl).unwrap()).unwrap()
+}
+
+#[test]
+fn codama_instructions_and_accounts() {
+    let idl = load(codama_idl());
+    assert_eq!(idl.address, Some(PROGRAM_ID.parse().unwrap()));
+    assert_eq!(idl.errors.as_ref().unwrap()[0].name, "overflow");
+
+    let mut limits = BTreeMap::new();
+    limits.insert("daily".to_string(), 30u16);
+    let data = [
+        vec![0],
+        (7u64, "tally".to_string(), limits).try_to_vec().unwrap(),
+    ]
+    .concat();
+    let (ix, args) = deser_ix_data_from_idl(&idl, data).unwrap();
+    assert_eq!(ix.name, "createCounter");
+    assert_eq!(
+        args,
+        json!({
+            "initial": 7,
+            "label": "tally",
+            "limits": [{ "key": "daily", "value": 30 }],
+        })
+    );
+
+    let data = [vec![0x0a, 0x0b], Some(3u32).try_to_vec().unwrap()].concat();
+    let (ix, args) = deser_ix_data_from_idl(&idl, data).unwrap();
+    assert_eq!(ix.name, "increment");
+    assert_eq!(args, json!({ "by": 3 }));
+
+    // Anchor's hashed discriminators mean nothing to a native program.
+    let anchor_ix = [common::discriminator("global:increment"), vec![0]].concat();
+    assert!(deser_ix_data_from_idl(&idl, anchor_ix).is_err());
+
+    let authority = Pubkey::new_unique();
+    let data = [vec![1], (authority, 42u64, 9u8, true).try_to_vec().unwrap()].concat();
+    let program_id = idl.address.unwrap();
+    let (type_name, value) =
+        deserialize_account_from_idl(&idl, &account(program_id, data, false)).unwrap();
+    assert_eq!(type_name, "counter");
+    assert_eq!(value["authority"], json!(authority.to_string()));
+    assert_eq!(value["count"], json!(42));
+    assert_eq!(value["last"], json!({ "0": 9, "1": true }));
+}
+
+#[test]
+fn shank_instructions_and_accounts() {
+    let idl = load(shank_idl());
+    assert_eq!(idl.address, Some(PROGRAM_ID.parse().unwrap()));
+
+    let (ix, args) =
+        deser_ix_data_from_idl(&idl, [vec![0], 50u64.to_le_bytes().to_vec()].concat()).unwrap();
+    assert_eq!(ix.name, "Deposit");
+    assert_eq!(args, json!({ "amount": 50 }));
+
+    let member = Pubkey::new_unique();
+    let mut fees = HashMap::new();
+    fees.insert("swap".to_string(), 5u8);
+    let data = [
+        vec![1],
+        ((2u8, 300u16), fees, Some(vec![member]))
+            .try_to_vec()
+            .unwrap(),
+    ]
+    .concat();
+    let (ix, args) = deser_ix_data_from_idl(&idl, data).unwrap();
+    assert_eq!(ix.name, "Configure");
+    assert_eq!(
+        args,
+        json!({
+            "range": { "0": 2, "1": 300 },
+            "fees": [{ "key": "swap", "value": 5 }],
+            "members": [member.to_string()],
+        })
+    );
+
+    // Accounts are told apart by their leading `key`, the latest version of
+    // the account's variant if it's versioned. Others are decoded by name.
+    let program_id = idl.address.unwrap();
+    let owner = Pubkey::new_unique();
+    let (vault, position, receipt) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let mut source = InMemorySource::new();
+    let vault_data = [vec![1], (owner, 1_000u64).try_to_vec().unwrap()].concat();
+    source.add_account(vault, account(program_id, vault_data, false));
+    let position_data = [vec![3], 25u64.to_le_bytes().to_vec()].concat();
+    source.add_account(position, account(program_id, position_data, false));
+    source.add_account(
+        receipt,
+        account(program_id, 7u64.to_le_bytes().to_vec(), false),
+    );
+    let mut lens = AnchorLens::new(source);
+    lens.idl_registry.insert(program_id, idl);
+
+    let decoded = lens.fetch_and_deserialize_account(&vault, None).unwrap();
+    assert_eq!(
+        (decoded.program_name.as_str(), decoded.type_name.as_str()),
+        ("vault", "Vault")
+    );
+    assert_eq!(
+        decoded.data,
+        json!({ "owner": owner.to_string(), "balance": 1_000 })
+    );
+    let decoded = lens.fetch_and_deserialize_account(&position, None).unwrap();
+    assert_eq!(decoded.type_name, "Position");
+    assert_eq!(decoded.data, json!({ "shares": 25 }));
+
+    assert!(lens.fetch_and_deserialize_account(&receipt, None).is_err());
+    let decoded = lens
+        .fetch_and_deserialize_account_as(&receipt, "Receipt", None)
+        .unwrap();
+    assert_eq!(decoded.data, json!({ "amount": 7 }));
+    // Accounts with a discriminator are only decoded as such if their data starts with it.
+    assert!(lens
+        .fetch_and_deserialize_account_as(&receipt, "Vault", None)
+        .is_err());
+    let decoded = lens
+        .fetch_and_deserialize_account_as(&vault, "Vault", None)
+        .unwrap();
+    assert_eq!(decoded.data["balance"], json!(1_000));
+    let err = lens
+        .fetch_and_deserialize_account_as(&receipt, "Missing", None)
+        .err()
+        .unwrap();
+    match err {
+        LensError::UnknownType {
+            program_id: id,
+            type_name,
+        } => {
+            assert_eq!(id, Some(program_id));
+            assert_eq!(type_name, "Missing");
+        }
+        err => panic!("expected an unknown type, got {}", err),
+    }
+}
+
+#[test]
+fn rejected_idls_report_why() {
+    let mut idl = codama_idl();
+    idl["program"]["definedTypes"][0]["type"]["size"] = number("u16");
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &idl);
+    let err = AnchorLens::new(source).fetch_idl(&program_id).unwrap_err();
+    match err {
+        LensError::InvalidIdl { reason, .. } => {
+            assert!(reason.contains("Enum key isn't indexed by a u8"), "{}", reason)
+        }
+        err => panic!("expected an invalid IDL, got {}", err),
+    }
+}
+
+#[test]
+fn native_idl_files_decode_transactions() {
+    let dir = std::env::temp_dir().join(format!("anchor-lens-native-idls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("vault.json");
+    fs::write(&path, serde_json::to_vec(&shank_idl()).unwrap()).unwrap();
+    let mut lens = AnchorLens::new(InMemorySource::new());
+    let program_id = lens.idl_registry.load_file(&path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let vault = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let ix = Instruction::new_with_bytes(
+        program_id,
+        &[vec![0], 50u64.to_le_bytes().to_vec()].concat(),
+        vec![
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(owner, true),
+        ],
+    );
+    let tx =
+        HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(&[ix], Some(&owner))));
+    let value = lens.deserialize_transaction(tx).unwrap();
+    let ix = &value["instructions"][0];
+    assert_eq!(ix["program_name"], json!("vault"));
+    assert_eq!(ix["instruction"]["name"], json!("Deposit"));
+    assert_eq!(ix["instruction"]["data"], json!({ "amount": 50 }));
+}
@@ -0,0 +1,162 @@
+//! Regression tests for `Option<T>` decoding. A `None` is a single 0 byte in Borsh,
+//! so decoding one must not consume the bytes of the fields that follow it.
+
+use anchor_syn::idl::{IdlType, IdlTypeDefinition};
+use borsh::BorshSerialize;
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::field::deserialize_idl_type;
+use solana_anchor_lens::deserialize::idl_type_deserializer::TypeDefinitionDeserializer;
+
+#[derive(BorshSerialize)]
+struct Fees {
+    numerator: u64,
+    denominator: u64,
+}
+
+#[derive(BorshSerialize)]
+struct Pool {
+    fee_rate: Option<u16>,
+    fees: Option<Fees>,
+    nested: Option<Option<u8>>,
+    authority_bump: u8,
+    total: u64,
+}
+
+fn type_defs() -> Vec<IdlTypeDefinition> {
+    serde_json::from_value(json!([
+        {
+            "name": "Fees",
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    { "name": "numerator", "type": "u64" },
+                    { "name": "denominator", "type": "u64" }
+                ]
+            }
+        },
+        {
+            "name": "Pool",
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    { "name": "feeRate", "type": { "option": "u16" } },
+                    { "name": "fees", "type": { "option": { "defined": "Fees" } } },
+                    { "name": "nested", "type": { "option": { "option": "u8" } } },
+                    { "name": "authorityBump", "type": "u8" },
+                    { "name": "total", "type": "u64" }
+                ]
+            }
+        }
+    ]))
+    .unwrap()
+}
+
+fn deserialize_pool(pool: &Pool) -> (Value, usize) {
+    let data = pool.try_to_vec().unwrap();
+    let type_defs = type_defs();
+    let mut cursor = data.as_slice();
+    let value = TypeDefinitionDeserializer {
+        idl_type_defs: type_defs.clone(),
+        curr_type: type_defs[1].clone(),
+    }
+    .deserialize(&mut cursor)
+    .unwrap();
+    (value, cursor.len())
+}
+
+#[test]
+fn none_primitive_consumes_only_the_tag() {
+    let data = [
+        None::<u64>.try_to_vec().unwrap(),
+        0xbeefu16.try_to_vec().unwrap(),
+    ]
+    .concat();
+    let mut cursor = data.as_slice();
+    let option = IdlType::Option(Box::new(IdlType::U64));
+    assert_eq!(
+        deserialize_idl_type(&option, &vec![], &mut cursor).unwrap(),
+        Value::Null
+    );
+    assert_eq!(
+        deserialize_idl_type(&IdlType::U16, &vec![], &mut cursor).unwrap(),
+        json!(0xbeef)
+    );
+    assert!(cursor.is_empty());
+}
+
+#[test]
+fn some_primitive() {
+    let data = Some(42u64).try_to_vec().unwrap();
+    let option = IdlType::Option(Box::new(IdlType::U64));
+    assert_eq!(
+        deserialize_idl_type(&option, &vec![], &mut data.as_slice()).unwrap(),
+        json!(42)
+    );
+}
+
+#[test]
+fn all_none_fields_keep_following_fields_aligned() {
+    let (value, remaining) = deserialize_pool(&Pool {
+        fee_rate: None,
+        fees: None,
+        nested: None,
+        authority_bump: 254,
+        total: 1_000_000,
+    });
+    assert_eq!(
+        value,
+        json!({
+            "feeRate": null,
+            "fees": null,
+            "nested": null,
+            "authorityBump": 254,
+            "total": 1_000_000
+        })
+    );
+    assert_eq!(remaining, 0);
+}
+
+#[test]
+fn some_defined_type() {
+    let (value, remaining) = deserialize_pool(&Pool {
+        fee_rate: Some(30),
+        fees: Some(Fees {
+            numerator: 1,
+            denominator: 10_000,
+        }),
+        nested: Some(Some(7)),
+        authority_bump: 255,
+        total: 5,
+    });
+    assert_eq!(
+        value,
+        json!({
+            "feeRate": 30,
+            "fees": { "numerator": 1, "denominator": 10_000 },
+            "nested": 7,
+            "authorityBump": 255,
+            "total": 5
+        })
+    );
+    assert_eq!(remaining, 0);
+}
+
+#[test]
+fn nested_some_none() {
+    // `Some(None)` has no distinct JSON representation, but it must still
+    // consume exactly its two tag bytes.
+    let (value, remaining) = deserialize_pool(&Pool {
+        fee_rate: None,
+        fees: Some(Fees {
+            numerator: 3,
+            denominator: 4,
+        }),
+        nested: Some(None),
+        authority_bump: 1,
+        total: u64::MAX,
+    });
+    assert_eq!(value["nested"], Value::Null);
+    assert_eq!(value["authorityBump"], json!(1));
+    assert_eq!(value["total"], json!(u64::MAX));
+    assert_eq!(remaining, 0);
+}
@@ -0,0 +1,197 @@
+//! Decoding programs with hand-written decoders registered on the lens.
+
+mod common;
+
+use anyhow::anyhow;
+use common::{account, add_program, counter_idl, discriminator};
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::decoder::{NativeAccount, ProgramDecoder};
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+
+/// A counter program that predates Anchor: instructions are a `u8` tag followed by
+/// their arguments, and counters are a `7` tag followed by the count.
+struct LegacyCounter;
+
+impl ProgramDecoder for LegacyCounter {
+    fn program_name(&self) -> &str {
+        "legacy_counter"
+    }
+
+    fn decode_instruction(
+        &self,
+        data: &[u8],
+        _num_accounts: usize,
+    ) -> Option<anyhow::Result<(String, Value)>> {
+        match data.split_first()? {
+            (0, start) => Some(
+                <[u8; 8]>::try_from(start)
+                    .map(|start| {
+                        (
+                            "initialize".to_string(),
+                            json!({ "start": u64::from_le_bytes(start) }),
+                        )
+                    })
+                    .map_err(|_| anyhow!("initialize takes a u64")),
+            ),
+            (1, []) => Some(Ok(("reset".to_string(), json!({})))),
+            _ => None,
+        }
+    }
+
+    fn name_accounts(
+        &self,
+        _instruction: &str,
+        _data: &Value,
+        _num_accounts: usize,
+    ) -> Vec<NativeAccount> {
+        vec![
+            NativeAccount {
+                name: "counter".to_string(),
+                writable: true,
+                signer: false,
+            },
+            NativeAccount {
+                name: "authority".to_string(),
+                writable: false,
+                signer: true,
+            },
+        ]
+    }
+
+    fn decode_account(&self, data: &[u8]) -> Option<anyhow::Result<(String, Value)>> {
+        match data {
+            [7, count @ ..] => Some(
+                <[u8; 8]>::try_from(count)
+                    .map(|count| {
+                        (
+                            "Counter".to_string(),
+                            json!({ "count": u64::from_le_bytes(count) }),
+                        )
+                    })
+                    .map_err(|_| anyhow!("Counter is not 9 bytes")),
+            ),
+            _ => None,
+        }
+    }
+}
+
+fn transaction(ixs: &[Instruction], payer: Pubkey) -> HistoricalTransaction {
+    HistoricalTransaction::new(VersionedMessage::Legacy(Message::new(ixs, Some(&payer))))
+}
+
+#[test]
+fn registered_decoder_comes_before_the_idl() {
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "by"));
+    let mut lens = AnchorLens::new(source);
+    lens.register_decoder(program_id, LegacyCounter);
+
+    let counter = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let metas = vec![
+        AccountMeta::new(counter, false),
+        AccountMeta::new_readonly(authority, true),
+    ];
+    let ix = |data: Vec<u8>| Instruction::new_with_bytes(program_id, &data, metas.clone());
+    let tx = transaction(
+        &[
+            ix([vec![0], 5u64.to_le_bytes().to_vec()].concat()),
+            ix(vec![0, 1]),
+            // Instructions the decoder doesn't know are decoded with the IDL.
+            ix([
+                discriminator("global:increment"),
+                2u64.to_le_bytes().to_vec(),
+            ]
+            .concat()),
+        ],
+        Pubkey::new_unique(),
+    );
+    let value = lens.deserialize_transaction(tx).unwrap();
+    let ixs = value["instructions"].as_array().unwrap();
+
+    assert_eq!(ixs[0]["program_name"], json!("legacy_counter"));
+    assert_eq!(ixs[0]["instruction"]["name"], json!("initialize"));
+    assert_eq!(ixs[0]["instruction"]["data"], json!({ "start": 5 }));
+    assert_eq!(
+        ixs[0]["instruction"]["accounts"],
+        json!([
+            { "name": "counter", "pubkey": counter.to_string(), "is_signer": "false", "is_mut": "true" },
+            { "name": "authority", "pubkey": authority.to_string(), "is_signer": "true", "is_mut": "false" },
+        ])
+    );
+
+    assert_eq!(ixs[1]["program_name"], json!("legacy_counter"));
+    assert_eq!(ixs[1]["decode_error"], json!("initialize takes a u64"));
+
+    assert_eq!(ixs[2]["program_name"], json!("counter"));
+    assert_eq!(ixs[2]["instruction"]["name"], json!("increment"));
+    assert_eq!(ixs[2]["instruction"]["data"], json!({ "by": 2 }));
+}
+
+#[test]
+fn registered_decoder_replaces_native_decoder() {
+    let mut lens = AnchorLens::new(InMemorySource::new());
+    lens.register_decoder(system_program::id(), LegacyCounter);
+    let payer = Pubkey::new_unique();
+    let ix = Instruction::new_with_bytes(
+        system_program::id(),
+        &[1],
+        vec![AccountMeta::new(payer, true)],
+    );
+    let value = lens
+        .deserialize_transaction(transaction(&[ix], payer))
+        .unwrap();
+    assert_eq!(
+        value["instructions"][0]["program_name"],
+        json!("legacy_counter")
+    );
+    assert_eq!(
+        value["instructions"][0]["instruction"]["name"],
+        json!("reset")
+    );
+}
+
+#[test]
+fn registered_decoder_decodes_accounts() {
+    let program_id = Pubkey::new_unique();
+    let counter = Pubkey::new_unique();
+    let anchor_counter = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "by"));
+    source.add_account(
+        counter,
+        account(
+            program_id,
+            [vec![7], 9u64.to_le_bytes().to_vec()].concat(),
+            false,
+        ),
+    );
+    let anchor_data = [
+        discriminator("account:Counter"),
+        3u64.to_le_bytes().to_vec(),
+        Pubkey::new_unique().to_bytes().to_vec(),
+    ]
+    .concat();
+    source.add_account(anchor_counter, account(program_id, anchor_data, false));
+    let mut lens = AnchorLens::new(source);
+    lens.register_decoder(program_id, LegacyCounter);
+
+    let decoded = lens.fetch_and_deserialize_account(&counter, None).unwrap();
+    assert_eq!(decoded.program_name, "legacy_counter");
+    assert_eq!(decoded.type_name, "Counter");
+    assert_eq!(decoded.data, json!({ "count": 9 }));
+
+    // Accounts the decoder doesn't know are decoded with the IDL.
+    let decoded = lens
+        .fetch_and_deserialize_account(&anchor_counter, None)
+        .unwrap();
+    assert_eq!(decoded.program_name, "counter");
+    assert_eq!(decoded.data["count"], json!(3));
+}
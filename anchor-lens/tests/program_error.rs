@@ -0,0 +1,104 @@
+//! Naming custom program errors, and where the failing program is found.
+
+mod common;
+
+use common::{add_program, counter_idl};
+use serde_json::json;
+use solana_anchor_lens::deserialize::program_error::{
+    failed_program_from_logs, lookup_error_code, transaction_error_json, ErrorSource,
+};
+use solana_anchor_lens::deserialize::HistoricalTransaction;
+use solana_anchor_lens::fetch_idl::discriminators::IdlWithDiscriminators;
+use solana_anchor_lens::fetch_idl::versioned::VersionedIdl;
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::instruction::{Instruction, InstructionError};
+use solana_program::message::{Message, VersionedMessage};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+fn idl(mut idl: serde_json::Value) -> IdlWithDiscriminators {
+    idl["errors"] = json!([{ "code": 6000, "name": "Overflow", "msg": "Counter overflowed" }]);
+    IdlWithDiscriminators::from_versioned(VersionedIdl::from_value(idl).unwrap()).unwrap()
+}
+
+#[test]
+fn idl_errors_then_anchor_errors() {
+    let anchor = idl(counter_idl("counter", "by"));
+    assert_eq!(
+        lookup_error_code(Some(&anchor), 6000),
+        Some((
+            "Overflow".to_string(),
+            Some("Counter overflowed".to_string()),
+            ErrorSource::Idl
+        ))
+    );
+    let (name, _, source) = lookup_error_code(Some(&anchor), 2006).unwrap();
+    assert_eq!(
+        (name.as_str(), source),
+        ("ConstraintSeeds", ErrorSource::Anchor)
+    );
+    assert_eq!(lookup_error_code(Some(&anchor), 6001), None);
+}
+
+#[test]
+fn anchor_errors_need_an_anchor_idl() {
+    // Without an IDL, or with a native program's, Anchor's codes mean nothing.
+    assert_eq!(lookup_error_code(None, 2006), None);
+    let mut shank_idl = counter_idl("vault", "amount");
+    shank_idl["metadata"] = json!({ "origin": "shank" });
+    let shank = idl(shank_idl);
+    assert!(!shank.is_anchor());
+    assert_eq!(lookup_error_code(Some(&shank), 2006), None);
+    assert_eq!(lookup_error_code(Some(&shank), 6000).unwrap().0, "Overflow");
+
+    let program_id = Pubkey::new_unique();
+    let err = TransactionError::InstructionError(1, InstructionError::Custom(2006));
+    let json = transaction_error_json(&err, Some(&program_id), None);
+    assert_eq!(json["instruction_index"], json!(1));
+    assert_eq!(json["program_id"], json!(program_id.to_string()));
+    assert_eq!(json["code"], json!(2006));
+    assert!(json.get("name").is_none());
+}
+
+#[test]
+fn failing_program_is_the_innermost() {
+    let outer = Pubkey::new_unique();
+    let inner = Pubkey::new_unique();
+    let logs = [
+        format!("Program {} invoke [1]", outer),
+        format!("Program {} invoke [2]", inner),
+        format!("Program {} failed: custom program error: 0x1770", inner),
+        format!("Program {} failed: custom program error: 0x1770", outer),
+    ];
+    assert_eq!(failed_program_from_logs(&logs), Some(inner));
+    assert_eq!(failed_program_from_logs(&logs[..2]), None);
+}
+
+#[test]
+fn transactions_and_messages_share_a_shape() {
+    let program_id = Pubkey::new_unique();
+    let mut source = InMemorySource::new();
+    add_program(&mut source, program_id, &counter_idl("counter", "by"));
+    let lens = AnchorLens::new(source);
+    let payer = Pubkey::new_unique();
+    let message = VersionedMessage::Legacy(Message::new(
+        &[Instruction::new_with_bytes(program_id, &[], vec![])],
+        Some(&payer),
+    ));
+    let mut tx = HistoricalTransaction::new(message.clone());
+    tx.log_messages = vec![format!(
+        "Program {} failed: custom program error: 0x1770",
+        program_id
+    )];
+    tx.err = Some(TransactionError::InstructionError(
+        0,
+        InstructionError::Custom(6000),
+    ));
+    let tx_json = lens.deserialize_transaction(tx).unwrap();
+    assert_eq!(tx_json["error"]["code"], json!(6000));
+    let message_json = lens.deserialize_message(&message).unwrap();
+    assert_eq!(message_json["instructions"], tx_json["instructions"]);
+    assert_eq!(message_json["compute_budget"], tx_json["compute_budget"]);
+    assert!(message_json.get("error").is_none());
+}
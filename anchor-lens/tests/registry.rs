@@ -0,0 +1,120 @@
+//! Loading IDLs from files, and using them in place of on-chain IDLs.
+
+use serde_json::{json, Value};
+use solana_anchor_lens::fetch_idl::registry::SkippedIdl;
+use solana_anchor_lens::fetch_idl::IdlRegistry;
+use solana_anchor_lens::AnchorLens;
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const COUNTER_ID: &str = "Counter111111111111111111111111111111111111";
+
+fn legacy_idl(name: &str, address: Option<&str>) -> Value {
+    let mut idl = json!({
+        "version": "0.1.0",
+        "name": name,
+        "instructions": [
+            { "name": "increment", "accounts": [], "args": [] }
+        ]
+    });
+    if let Some(address) = address {
+        idl["metadata"] = json!({ "address": address });
+    }
+    idl
+}
+
+/// A fresh directory holding the given IDL files, like an Anchor `target/idl`.
+fn idl_dir(test: &str, idls: &[(&str, Value)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("anchor-lens-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, idl) in idls {
+        fs::write(dir.join(file), serde_json::to_vec(idl).unwrap()).unwrap();
+    }
+    fs::write(dir.join("README.md"), "not an IDL").unwrap();
+    dir
+}
+
+#[test]
+fn load_dir_uses_recorded_and_explicit_program_ids() {
+    let explicit_id = Pubkey::new_unique();
+    let dir = idl_dir(
+        "load-dir",
+        &[
+            ("counter.json", legacy_idl("counter", Some(COUNTER_ID))),
+            ("unpublished.json", legacy_idl("unpublished", None)),
+        ],
+    );
+    let mut registry = IdlRegistry::new();
+    let program_ids = HashMap::from([("unpublished".to_string(), explicit_id)]);
+    let loaded = registry.load_dir(&dir, &program_ids).unwrap();
+
+    let counter_id = Pubkey::from_str(COUNTER_ID).unwrap();
+    assert_eq!(loaded, vec![counter_id, explicit_id]);
+    assert_eq!(registry.get(&counter_id).unwrap().name, "counter");
+    assert_eq!(registry.get(&explicit_id).unwrap().name, "unpublished");
+    assert_eq!(
+        registry.get(&explicit_id).unwrap().address,
+        Some(explicit_id)
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn load_dir_requires_a_program_id() {
+    let dir = idl_dir(
+        "missing-id",
+        &[("unpublished.json", legacy_idl("unpublished", None))],
+    );
+    let err = IdlRegistry::new()
+        .load_dir(&dir, &HashMap::new())
+        .unwrap_err();
+    assert!(err.to_string().contains("does not record its program ID"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn load_dir_can_skip_idls_without_a_program_id() {
+    let dir = idl_dir(
+        "skip-missing-id",
+        &[
+            ("counter.json", legacy_idl("counter", Some(COUNTER_ID))),
+            ("unpublished.json", legacy_idl("unpublished", None)),
+        ],
+    );
+    let mut registry = IdlRegistry::new();
+    let (loaded, skipped) = registry
+        .load_dir_skipping_unknown(&dir, &HashMap::new())
+        .unwrap();
+    assert_eq!(loaded, vec![Pubkey::from_str(COUNTER_ID).unwrap()]);
+    assert_eq!(
+        skipped,
+        vec![SkippedIdl {
+            path: dir.join("unpublished.json"),
+            name: "unpublished".to_string(),
+        }]
+    );
+    assert_eq!(registry.len(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn registry_is_consulted_before_rpc() {
+    let dir = idl_dir(
+        "lens",
+        &[("counter.json", legacy_idl("counter", Some(COUNTER_ID)))],
+    );
+    let mut lens = AnchorLens::new(RpcClient::new("http://127.0.0.1:1"));
+    let program_id = lens
+        .idl_registry
+        .load_file(dir.join("counter.json"))
+        .unwrap();
+    let idl = lens.fetch_idl(&program_id).unwrap();
+    assert_eq!(idl.name, "counter");
+    assert_eq!(idl.instructions[0].name, "increment");
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,30 @@
+//! The RPC client as a source, against a mock RPC node.
+
+use serde_json::Value;
+use solana_anchor_lens::source::TransactionSource;
+use solana_anchor_lens::{AnchorLens, LensError};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+
+/// A node that answers `getTransaction` with `null`, as for a transaction it doesn't know.
+fn unknown_transaction_node() -> RpcClient {
+    let mocks = HashMap::from([(RpcRequest::GetTransaction, Value::Null)]);
+    RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+}
+
+#[test]
+fn unknown_transactions_are_not_found() {
+    let signature = Signature::new_unique();
+    let tx = TransactionSource::get_transaction(&unknown_transaction_node(), &signature);
+    assert!(tx.unwrap().is_none());
+    let err = AnchorLens::new(unknown_transaction_node())
+        .get_versioned_transaction(&signature)
+        .unwrap_err();
+    assert!(
+        matches!(err, LensError::TransactionNotFound { signature: sig } if sig == signature),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,182 @@
+//! Checks that decoding locates where account data diverges from its IDL,
+//! and that lenient decoding keeps ignoring leftover bytes.
+
+use anchor_syn::idl::Idl;
+use borsh::BorshSerialize;
+use serde_json::json;
+use solana_anchor_lens::deserialize::context::DecodeWarning;
+use solana_anchor_lens::deserialize::{
+    deserialize_account_from_idl, deserialize_account_from_idl_with_strictness, Strictness,
+};
+use solana_anchor_lens::fetch_idl::discriminators::IdlWithDiscriminators;
+use solana_anchor_lens::LensError;
+use solana_program::hash::hash;
+use solana_sdk::account::Account;
+
+#[derive(BorshSerialize)]
+struct Fees {
+    numerator: u64,
+    denominator: u64,
+}
+
+#[derive(BorshSerialize)]
+struct Pool {
+    authority_bump: u8,
+    fees: Vec<Fees>,
+}
+
+fn idl() -> IdlWithDiscriminators {
+    let idl: Idl = serde_json::from_value(json!({
+        "version": "0.1.0",
+        "name": "pools",
+        "instructions": [],
+        "accounts": [
+            {
+                "name": "Pool",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "authorityBump", "type": "u8" },
+                        { "name": "fees", "type": { "vec": { "defined": "Fees" } } }
+                    ]
+                }
+            }
+        ],
+        "types": [
+            {
+                "name": "Fees",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "numerator", "type": "u64" },
+                        { "name": "denominator", "type": "u64" }
+                    ]
+                }
+            }
+        ]
+    }))
+    .unwrap();
+    IdlWithDiscriminators::new(idl)
+}
+
+fn account(pool: &Pool, trailing: &[u8]) -> Account {
+    let data = [
+        &hash(b"account:Pool").to_bytes()[..8],
+        &pool.try_to_vec().unwrap(),
+        trailing,
+    ]
+    .concat();
+    Account {
+        lamports: 1,
+        data,
+        owner: Default::default(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn pool() -> Pool {
+    Pool {
+        authority_bump: 254,
+        fees: (0..3)
+            .map(|i| Fees {
+                numerator: i,
+                denominator: 10_000,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn exact_data_has_no_warnings() {
+    let (type_name, value, warnings) = deserialize_account_from_idl_with_strictness(
+        &idl(),
+        &account(&pool(), &[]),
+        Strictness::Strict,
+    )
+    .unwrap();
+    assert_eq!(type_name, "Pool");
+    assert_eq!(
+        value["fees"][2],
+        json!({ "numerator": 2, "denominator": 10_000 })
+    );
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn trailing_bytes() {
+    let account = account(&pool(), &[0; 5]);
+    // 8 discriminator bytes, 1 bump byte, a 4 byte length and 3 fees of 16 bytes.
+    let end = 8 + 1 + 4 + 3 * 16;
+
+    assert!(deserialize_account_from_idl(&idl(), &account).is_ok());
+
+    let (_, _, warnings) =
+        deserialize_account_from_idl_with_strictness(&idl(), &account, Strictness::Warn).unwrap();
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning {
+            path: "Pool".to_string(),
+            offset: end,
+            message: "5 trailing bytes".to_string(),
+        }]
+    );
+
+    let err = deserialize_account_from_idl_with_strictness(&idl(), &account, Strictness::Strict)
+        .unwrap_err();
+    match &err {
+        LensError::TrailingBytes { len, location } => {
+            assert_eq!(*len, 5);
+            assert_eq!(location.offset, end);
+        }
+        _ => panic!("expected trailing bytes, got {}", err),
+    }
+}
+
+#[test]
+fn short_data_reports_the_field_path() {
+    let mut account = account(&pool(), &[]);
+    // Cut the last fee's denominator short.
+    account.data.truncate(account.data.len() - 4);
+    for strictness in [Strictness::Lenient, Strictness::Warn, Strictness::Strict] {
+        let err =
+            deserialize_account_from_idl_with_strictness(&idl(), &account, strictness).unwrap_err();
+        let location = match &err {
+            LensError::BufferUnderflow { location } => location,
+            _ => panic!("expected a buffer underflow, got {}", err),
+        };
+        assert_eq!(location.program_id, Some(account.owner));
+        assert_eq!(location.type_name, "Pool");
+        assert_eq!(location.path, "Pool.fees[2].denominator");
+        assert_eq!(location.offset, 8 + 1 + 4 + 2 * 16 + 8);
+    }
+}
+
+#[test]
+fn unknown_discriminator() {
+    let mut account = account(&pool(), &[]);
+    account.data[0] ^= 0xff;
+    let err = deserialize_account_from_idl(&idl(), &account).unwrap_err();
+    match &err {
+        LensError::UnknownDiscriminator { discriminator, .. } => {
+            assert_eq!(discriminator, &account.data[..8]);
+        }
+        _ => panic!("expected an unknown discriminator, got {}", err),
+    }
+}
+
+#[test]
+fn type_not_defined() {
+    let mut bare = (*idl()).clone();
+    bare.types.clear();
+    let idl = IdlWithDiscriminators::new(bare);
+    let err = deserialize_account_from_idl(&idl, &account(&pool(), &[])).unwrap_err();
+    match &err {
+        LensError::TypeNotDefined { defined, location } => {
+            assert_eq!(defined, "Fees");
+            assert_eq!(location.path, "Pool.fees[0]");
+            assert_eq!(location.offset, 8 + 1 + 4);
+        }
+        _ => panic!("expected an undefined type, got {}", err),
+    }
+}
@@ -0,0 +1,224 @@
+//! Decoding token mints and accounts, including Token-2022 extensions.
+
+mod common;
+
+use common::{account, CountingSource};
+use serde_json::json;
+use solana_anchor_lens::deserialize::deserialized_native_account_json;
+use solana_anchor_lens::deserialize::native::token::{
+    ACCOUNT_LEN, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+use solana_anchor_lens::source::InMemorySource;
+use solana_anchor_lens::AnchorLens;
+use solana_program::pubkey::Pubkey;
+
+fn mint_data(mint_authority: &Pubkey, supply: u64) -> Vec<u8> {
+    [
+        1u32.to_le_bytes().to_vec(),
+        mint_authority.to_bytes().to_vec(),
+        supply.to_le_bytes().to_vec(),
+        vec![6, 1],
+        0u32.to_le_bytes().to_vec(),
+        vec![0; 32],
+    ]
+    .concat()
+}
+
+fn tlv(extension_type: u16, value: &[u8]) -> Vec<u8> {
+    [
+        extension_type.to_le_bytes().to_vec(),
+        (value.len() as u16).to_le_bytes().to_vec(),
+        value.to_vec(),
+    ]
+    .concat()
+}
+
+#[test]
+fn legacy_mint_and_account() {
+    let mint = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let token_account = [
+        mint.to_bytes().to_vec(),
+        owner.to_bytes().to_vec(),
+        250u64.to_le_bytes().to_vec(),
+        vec![0; 36],
+        vec![1],
+        vec![0; 12],
+        0u64.to_le_bytes().to_vec(),
+        vec![0; 36],
+    ]
+    .concat();
+    assert_eq!(token_account.len(), ACCOUNT_LEN);
+
+    let json = deserialized_native_account_json(
+        &mint,
+        account(TOKEN_PROGRAM_ID, mint_data(&authority, 1000), false),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(json["program_name"], "spl_token");
+    assert_eq!(json["account_type"], "Mint");
+    assert_eq!(
+        json["deserialized"],
+        json!({
+            "mint_authority": authority.to_string(),
+            "supply": 1000,
+            "decimals": 6,
+            "is_initialized": true,
+            "freeze_authority": null,
+        })
+    );
+
+    let json = deserialized_native_account_json(
+        &Pubkey::new_unique(),
+        account(TOKEN_PROGRAM_ID, token_account, false),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(json["account_type"], "Account");
+    assert_eq!(json["deserialized"]["owner"], owner.to_string());
+    assert_eq!(json["deserialized"]["amount"], 250);
+    assert_eq!(json["deserialized"]["state"], "initialized");
+    assert!(json["deserialized"].get("extensions").is_none());
+
+    // Other programs' accounts are left to the IDL.
+    assert!(deserialized_native_account_json(
+        &mint,
+        account(Pubkey::new_unique(), mint_data(&authority, 1000), false),
+    )
+    .is_none());
+}
+
+#[test]
+fn token_2022_mint_with_extensions() {
+    let mint = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let transfer_fee = |epoch: u64, basis_points: u16| {
+        [
+            epoch.to_le_bytes().to_vec(),
+            5000u64.to_le_bytes().to_vec(),
+            basis_points.to_le_bytes().to_vec(),
+        ]
+        .concat()
+    };
+    let transfer_fee_config = [
+        authority.to_bytes().to_vec(),
+        vec![0; 32],
+        12u64.to_le_bytes().to_vec(),
+        transfer_fee(1, 50),
+        transfer_fee(2, 100),
+    ]
+    .concat();
+    let mut data = mint_data(&authority, 1000);
+    data.resize(ACCOUNT_LEN, 0);
+    data.push(1);
+    data.extend(tlv(1, &transfer_fee_config));
+    data.extend(tlv(3, &authority.to_bytes()));
+    data.extend(tlv(999, &[1, 2, 3]));
+    // Room left for an extension that isn't initialized.
+    data.extend([0; 8]);
+
+    let mut source = InMemorySource::new();
+    source.add_account(mint, account(TOKEN_2022_PROGRAM_ID, data, false));
+    let lens = AnchorLens::new(CountingSource::new(source));
+    let decoded = lens.fetch_and_deserialize_account(&mint, None).unwrap();
+    // Only the mint itself is read, no IDL is looked for.
+    assert_eq!(lens.client.reads(), 1);
+    assert_eq!(decoded.program_name, "spl_token_2022");
+    assert_eq!(decoded.type_name, "Mint");
+    assert_eq!(decoded.data["supply"], 1000);
+    assert_eq!(
+        decoded.data["extensions"],
+        json!({
+            "transfer_fee_config": {
+                "transfer_fee_config_authority": authority.to_string(),
+                "withdraw_withheld_authority": null,
+                "withheld_amount": 12,
+                "older_transfer_fee": {
+                    "epoch": 1,
+                    "maximum_fee": 5000,
+                    "transfer_fee_basis_points": 50,
+                },
+                "newer_transfer_fee": {
+                    "epoch": 2,
+                    "maximum_fee": 5000,
+                    "transfer_fee_basis_points": 100,
+                },
+            },
+            "mint_close_authority": { "close_authority": authority.to_string() },
+        })
+    );
+    assert_eq!(
+        decoded.data["unknown_extensions"],
+        json!([{ "extension_type": 999, "data": base64::encode([1, 2, 3]) }])
+    );
+}
+
+#[test]
+fn truncated_extension() {
+    let mint = Pubkey::new_unique();
+    let mut data = mint_data(&Pubkey::new_unique(), 1);
+    data.resize(ACCOUNT_LEN, 0);
+    data.push(1);
+    // A close authority extension that claims more bytes than the account holds.
+    data.extend(tlv(3, &[0; 32]));
+    data.truncate(data.len() - 4);
+    let err = deserialized_native_account_json(&mint, account(TOKEN_2022_PROGRAM_ID, data, false))
+        .unwrap()
+        .unwrap_err();
+    assert!(err.to_string().contains("extension"), "{}", err);
+}
+
+#[test]
+fn token_groups_and_undecodable_extensions() {
+    let mint = Pubkey::new_unique();
+    let group = Pubkey::new_unique();
+    let token_group = [
+        vec![0; 32],
+        mint.to_bytes().to_vec(),
+        3u64.to_le_bytes().to_vec(),
+        10u64.to_le_bytes().to_vec(),
+    ]
+    .concat();
+    let member = [
+        mint.to_bytes().to_vec(),
+        group.to_bytes().to_vec(),
+        3u64.to_le_bytes().to_vec(),
+    ]
+    .concat();
+    let mut data = mint_data(&Pubkey::new_unique(), 1);
+    data.resize(ACCOUNT_LEN, 0);
+    data.push(1);
+    data.extend(tlv(21, &token_group));
+    data.extend(tlv(23, &member));
+    // A close authority with bytes left over, as a newer version might have added.
+    data.extend(tlv(3, &[[7; 32], [1; 32]].concat()));
+    // A transfer hook too short to decode.
+    data.extend(tlv(14, &[1; 40]));
+
+    let json = deserialized_native_account_json(&mint, account(TOKEN_2022_PROGRAM_ID, data, false))
+        .unwrap()
+        .unwrap();
+    let extensions = &json["deserialized"]["extensions"];
+    assert_eq!(extensions["token_group"]["size"], 3);
+    assert_eq!(extensions["token_group"]["max_size"], 10);
+    assert_eq!(
+        extensions["token_group_member"],
+        json!({ "mint": mint.to_string(), "group": group.to_string(), "member_number": 3 })
+    );
+    // Known extensions that don't decode are kept as raw bytes.
+    let unknown = json["deserialized"]["unknown_extensions"]
+        .as_array()
+        .unwrap();
+    assert_eq!(unknown.len(), 2);
+    assert_eq!(unknown[0]["name"], "mint_close_authority");
+    assert_eq!(unknown[0]["error"], "32 bytes left over");
+    assert_eq!(
+        unknown[0]["data"],
+        json!(base64::encode([[7; 32], [1; 32]].concat()))
+    );
+    assert_eq!(unknown[1]["name"], "transfer_hook");
+    assert!(unknown[1]["error"].as_str().is_some());
+    assert!(extensions.get("transfer_hook").is_none());
+}
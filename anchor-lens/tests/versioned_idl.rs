@@ -0,0 +1,195 @@
+//! Converting Anchor 0.30+ IDLs, whose generics, aliases and zero-copy types
+//! have no direct equivalent in the legacy format.
+
+mod common;
+
+use anchor_syn::idl::{IdlType, IdlTypeDefinitionTy};
+use common::account;
+use serde_json::{json, Value};
+use solana_anchor_lens::deserialize::deserialize_account_from_idl;
+use solana_anchor_lens::fetch_idl::{IdlWithDiscriminators, VersionedIdl};
+use solana_anchor_lens::LensError;
+use solana_program::pubkey::Pubkey;
+
+/// An IDL with a `Vault` account of `fields`, which can refer to `types`.
+fn spec_idl(types: Value, fields: Value) -> Value {
+    let mut all_types =
+        vec![json!({ "name": "Vault", "type": { "kind": "struct", "fields": fields } })];
+    all_types.extend(types.as_array().unwrap().clone());
+    json!({
+        "address": Pubkey::new_unique().to_string(),
+        "metadata": { "name": "vault", "version": "0.1.0", "spec": "0.1.0" },
+        "instructions": [],
+        "accounts": [{ "name": "Vault", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+        "types": all_types
+    })
+}
+
+fn convert(idl: Value) -> anyhow::Result<IdlWithDiscriminators> {
+    IdlWithDiscriminators::from_versioned(VersionedIdl::from_value(idl)?)
+}
+
+fn field_types(idl: &IdlWithDiscriminators, name: &str) -> Vec<IdlType> {
+    let type_def = idl.types.iter().find(|ty| ty.name == name).unwrap();
+    match &type_def.ty {
+        IdlTypeDefinitionTy::Struct { fields } => {
+            fields.iter().map(|field| field.ty.clone()).collect()
+        }
+        IdlTypeDefinitionTy::Enum { .. } => panic!("{} is an enum", name),
+    }
+}
+
+#[test]
+fn generics_are_instantiated_and_aliases_inlined() {
+    let types = json!([
+        {
+            "name": "Slots",
+            "generics": [
+                { "kind": "type", "name": "T" },
+                { "kind": "const", "name": "N", "type": "usize" }
+            ],
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    { "name": "head", "type": { "generic": "T" } },
+                    { "name": "slots", "type": { "array": [{ "generic": "T" }, { "generic": "N" }] } }
+                ]
+            }
+        },
+        { "name": "Amount", "type": { "kind": "type", "alias": "u64" } }
+    ]);
+    let fields = json!([
+        {
+            "name": "slots",
+            "type": {
+                "defined": {
+                    "name": "Slots",
+                    "generics": [
+                        { "kind": "type", "type": "u16" },
+                        { "kind": "const", "value": "2" }
+                    ]
+                }
+            }
+        },
+        { "name": "amount", "type": { "defined": { "name": "Amount" } } },
+        { "name": "big", "type": "u256" },
+        { "name": "signed_big", "type": "i256" }
+    ]);
+    let idl = convert(spec_idl(types, fields)).unwrap();
+    assert_eq!(
+        field_types(&idl, "Vault"),
+        vec![
+            IdlType::Defined("Slots<u16, 2>".to_string()),
+            IdlType::U64,
+            IdlType::Array(Box::new(IdlType::U8), 32),
+            IdlType::Array(Box::new(IdlType::U8), 32),
+        ]
+    );
+    assert_eq!(
+        field_types(&idl, "Slots<u16, 2>"),
+        vec![IdlType::U16, IdlType::Array(Box::new(IdlType::U16), 2)]
+    );
+    // Only the instantiation is listed, aliases and generic definitions aren't.
+    let mut names = idl
+        .types
+        .iter()
+        .map(|ty| ty.name.as_str())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["Slots<u16, 2>", "Vault"]);
+
+    let data = [
+        vec![1, 2, 3, 4, 5, 6, 7, 8],
+        [1u16, 2, 3].iter().flat_map(|n| n.to_le_bytes()).collect(),
+        9u64.to_le_bytes().to_vec(),
+        vec![0xff; 32],
+        vec![0; 32],
+    ]
+    .concat();
+    let (name, value) =
+        deserialize_account_from_idl(&idl, &account(Pubkey::new_unique(), data, false)).unwrap();
+    assert_eq!(name, "Vault");
+    assert_eq!(value["slots"], json!({ "head": 1, "slots": [2, 3] }));
+    assert_eq!(value["amount"], 9);
+    assert_eq!(value["big"], json!(vec![0xff; 32]));
+}
+
+#[test]
+fn zero_copy_types_with_padding_are_left_out() {
+    let zero_copy = |serialization: &str, repr: Value, fields: Value| {
+        json!([{
+            "name": "Stats",
+            "serialization": serialization,
+            "repr": repr,
+            "type": { "kind": "struct", "fields": fields }
+        }])
+    };
+    let vault_fields = json!([{ "name": "stats", "type": { "defined": { "name": "Stats" } } }]);
+    let flag_then_amount = json!([
+        { "name": "flag", "type": "u8" },
+        { "name": "amount", "type": "u64" }
+    ]);
+    let has_stats = |idl: &IdlWithDiscriminators| idl.types.iter().any(|ty| ty.name == "Stats");
+
+    // `repr(C)` pads the flag to align the amount.
+    let idl = convert(spec_idl(
+        zero_copy(
+            "bytemuckunsafe",
+            json!({ "kind": "c" }),
+            flag_then_amount.clone(),
+        ),
+        vault_fields.clone(),
+    ))
+    .unwrap();
+    assert!(!has_stats(&idl));
+    // The rest of the IDL is kept, but what uses the padded type can't be decoded.
+    let data = [vec![1, 2, 3, 4, 5, 6, 7, 8], vec![0; 16]].concat();
+    let err = deserialize_account_from_idl(&idl, &account(Pubkey::new_unique(), data, false))
+        .unwrap_err();
+    assert!(
+        matches!(&err, LensError::TypeNotDefined { defined, .. } if defined == "Stats"),
+        "{}",
+        err
+    );
+
+    // Trailing padding, to round the size up to the alignment, counts too.
+    let idl = convert(spec_idl(
+        zero_copy(
+            "bytemuckunsafe",
+            json!({ "kind": "c" }),
+            json!([
+                { "name": "amount", "type": "u64" },
+                { "name": "flag", "type": "u8" }
+            ]),
+        ),
+        vault_fields.clone(),
+    ))
+    .unwrap();
+    assert!(!has_stats(&idl));
+
+    // Packed and naturally aligned layouts read the same as Borsh.
+    let idl = convert(spec_idl(
+        zero_copy(
+            "bytemuckunsafe",
+            json!({ "kind": "c", "packed": true }),
+            flag_then_amount,
+        ),
+        vault_fields.clone(),
+    ))
+    .unwrap();
+    assert!(has_stats(&idl));
+    let idl = convert(spec_idl(
+        zero_copy(
+            "bytemuck",
+            json!({ "kind": "c" }),
+            json!([
+                { "name": "amount", "type": "u64" },
+                { "name": "owner", "type": "pubkey" },
+                { "name": "flags", "type": { "array": ["u8", 8] } }
+            ]),
+        ),
+        vault_fields,
+    ))
+    .unwrap();
+    assert!(has_stats(&idl));
+}
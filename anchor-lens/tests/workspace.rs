@@ -0,0 +1,132 @@
+//! Reading program IDs and IDLs from an Anchor workspace.
+
+use serde_json::json;
+use solana_anchor_lens::fetch_idl::workspace::{cluster_for_url, cluster_name};
+use solana_anchor_lens::fetch_idl::{AnchorWorkspace, IdlRegistry};
+use solana_program::pubkey::Pubkey;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const COUNTER_LOCALNET: &str = "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS";
+const COUNTER_DEVNET: &str = "BPFLoaderUpgradeab1e11111111111111111111111";
+const VAULT_DEVNET: &str = "SysvarRent111111111111111111111111111111111";
+
+fn idl(name: &str) -> serde_json::Value {
+    json!({
+        "version": "0.1.0",
+        "name": name,
+        "instructions": [{ "name": "initialize", "accounts": [], "args": [] }]
+    })
+}
+
+fn workspace(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("anchor-lens-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("target/idl")).unwrap();
+    fs::create_dir_all(root.join("programs/counter/src")).unwrap();
+    fs::create_dir_all(root.join("idls")).unwrap();
+    fs::write(
+        root.join("Anchor.toml"),
+        format!(
+            r#"
+[features]
+seeds = false
+
+[programs.localnet]
+counter = "{}"
+unbuilt = "{}"
+
+[programs.devnet]
+counter = "{}"
+vault = {{ address = "{}", idl = "idls/vault.json" }}
+
+[provider]
+cluster = "devnet"
+wallet = "~/.config/solana/id.json"
+"#,
+            COUNTER_LOCALNET, VAULT_DEVNET, COUNTER_DEVNET, VAULT_DEVNET
+        ),
+    )
+    .unwrap();
+    fs::write(
+        root.join("target/idl/counter.json"),
+        idl("counter").to_string(),
+    )
+    .unwrap();
+    fs::write(root.join("idls/vault.json"), idl("vault").to_string()).unwrap();
+    root
+}
+
+#[test]
+fn loads_the_selected_cluster() {
+    let root = workspace("workspace-cluster");
+    let workspace = AnchorWorkspace::find(root.join("programs/counter/src"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(workspace.root, root);
+    assert_eq!(workspace.provider_cluster.as_deref(), Some("devnet"));
+
+    let mut localnet = IdlRegistry::new();
+    let loaded = workspace.load_idls("localnet", &mut localnet).unwrap();
+    let counter = Pubkey::from_str(COUNTER_LOCALNET).unwrap();
+    // The unbuilt program has no IDL yet, so it's skipped.
+    assert_eq!(loaded, vec![counter]);
+    assert_eq!(localnet.get(&counter).unwrap().name, "counter");
+
+    let mut devnet = IdlRegistry::new();
+    workspace.load_idls("devnet", &mut devnet).unwrap();
+    assert_eq!(devnet.len(), 2);
+    let vault = Pubkey::from_str(VAULT_DEVNET).unwrap();
+    assert_eq!(devnet.get(&vault).unwrap().name, "vault");
+    assert!(devnet
+        .get(&Pubkey::from_str(COUNTER_DEVNET).unwrap())
+        .is_some());
+
+    // Another cluster's program IDs would be the wrong ones.
+    let mut mainnet = IdlRegistry::new();
+    let err = workspace.load_idls("mainnet", &mut mainnet).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("has no [programs.mainnet] section"));
+    assert!(mainnet.is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn cluster_names_are_normalized() {
+    // As generated by `anchor init`, which Anchor reads case-insensitively.
+    let root = workspace("workspace-case");
+    let manifest = fs::read_to_string(root.join("Anchor.toml"))
+        .unwrap()
+        .replace("[programs.localnet]", "[programs.Localnet]")
+        .replace(r#"cluster = "devnet""#, r#"cluster = "Localnet""#);
+    fs::write(root.join("Anchor.toml"), manifest).unwrap();
+    let workspace = AnchorWorkspace::load(&root).unwrap();
+    assert_eq!(workspace.provider_cluster.as_deref(), Some("localnet"));
+    let mut registry = IdlRegistry::new();
+    let loaded = workspace.load_idls("Localnet", &mut registry).unwrap();
+    assert_eq!(loaded, vec![Pubkey::from_str(COUNTER_LOCALNET).unwrap()]);
+
+    assert_eq!(cluster_name("m").as_deref(), Some("mainnet"));
+    assert_eq!(
+        cluster_name("https://api.devnet.solana.com").as_deref(),
+        Some("devnet")
+    );
+    assert_eq!(cluster_name("https://example.rpcpool.com"), None);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn cluster_names_for_urls() {
+    assert_eq!(
+        cluster_for_url("https://api.devnet.solana.com"),
+        Some("devnet")
+    );
+    assert_eq!(
+        cluster_for_url("https://api.mainnet-beta.solana.com"),
+        Some("mainnet")
+    );
+    assert_eq!(cluster_for_url("http://127.0.0.1:8899"), Some("localnet"));
+    assert_eq!(cluster_for_url("https://example.rpcpool.com"), None);
+}
@@ -1,14 +1,20 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use jungle_fi_cli_utils::clap::{UrlArg, pubkey_arg};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 use solana_anchor_lens::AnchorLens;
-use solana_anchor_lens::deserialize::deserialized_account_json;
+use solana_anchor_lens::fetch_idl::{AnchorWorkspace, DiskIdlCache, IdlRegistry};
+use solana_anchor_lens::fetch_idl::workspace::cluster_for_url;
+use solana_anchor_lens::deserialize::{
+    deserialized_account_json_with_strictness, deserialized_native_account_json, Strictness,
+};
 
 
 /// Account data cloning CLI.
@@ -17,6 +23,38 @@ pub struct Opts {
     /// RPC URL to target the Solana cluster
     #[clap(flatten)]
     pub url: UrlArg,
+    /// How closely data must match the IDL: lenient, warn, or strict.
+    #[clap(long, default_value = "lenient")]
+    pub strictness: Strictness,
+    /// IDL JSON file to use instead of the on-chain IDL, as `PATH` if the IDL
+    /// records its program ID, or `PROGRAM_ID=PATH`. Can be repeated.
+    /// Older versions can be given as `PROGRAM_ID@SLOT=PATH`, and are used to
+    /// decode transactions from the slot they were deployed in onwards.
+    #[clap(long)]
+    pub idl: Vec<String>,
+    /// Directory of IDL JSON files, such as an Anchor workspace's `target/idl`.
+    /// IDLs that don't record their program ID, and aren't given one with
+    /// `--program-id`, are skipped.
+    #[clap(long)]
+    pub idl_dir: Option<String>,
+    /// The program ID of an IDL in `--idl-dir`, as `NAME=PROGRAM_ID`, where `NAME`
+    /// is the IDL's program name or file name. Can be repeated.
+    #[clap(long)]
+    pub program_id: Vec<String>,
+    /// The `Anchor.toml` cluster to take program IDs from, when run inside
+    /// an Anchor workspace. Defaults to the cluster of the RPC URL.
+    #[clap(long)]
+    pub cluster: Option<String>,
+    /// Keep IDLs fetched from chain in `~/.cache/solana-anchor-lens/idls` between runs,
+    /// checking each is still current before it's used.
+    #[clap(long)]
+    pub idl_cache: bool,
+    /// Like `--idl-cache`, but in another directory.
+    #[clap(long)]
+    pub idl_cache_dir: Option<String>,
+    /// Use cached IDLs without checking they're current, making no RPC calls for them.
+    #[clap(long)]
+    pub trust_idl_cache: bool,
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -37,10 +75,33 @@ pub enum Command {
     Transaction {
         /// The transaction signature of the historical transaction.
         signature: String,
+        /// Decode with the IDLs that were live when the transaction landed, rebuilt
+        /// from the history of each program's IDL account. This can take many RPC calls.
+        #[clap(long)]
+        idl_history: bool,
+        /// Attach the IDL written by Anchor IDL `write` instructions, if the transaction
+        /// writes a whole one, decompressed.
+        #[clap(long)]
+        decode_idl_writes: bool,
         /// Optional output filepath.
         #[clap(short, long)]
         outfile: Option<String>,
     },
+    /// Inspect a program's on-chain IDL.
+    Idl {
+        #[clap(subcommand)]
+        command: IdlCommand,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum IdlCommand {
+    /// Show who controls a program's IDL account, and how large it is.
+    Info {
+        /// The program ID, or the address of its IDL account or an IDL buffer.
+        #[clap(parse(try_from_str=pubkey_arg))]
+        program_id: Pubkey,
+    },
 }
 
 pub fn entry(
@@ -49,10 +110,15 @@ pub fn entry(
 ) -> Result<()> {
     match &opts.command {
         Command::Account { address, outfile } => {
-            let lens = AnchorLens::new(client);
+            let lens = lens(opts, client)?;
             let account = lens.get_account(address)?;
-            let idl = lens.fetch_idl(&account.owner)?;
-            let json = deserialized_account_json(&idl, address, account)?;
+            let json = match deserialized_native_account_json(address, account.clone()) {
+                Some(json) => json?,
+                None => {
+                    let idl = lens.fetch_idl(&account.owner)?;
+                    deserialized_account_json_with_strictness(&idl, address, account, opts.strictness)?
+                }
+            };
             let json = serde_json::to_string_pretty(&json)?;
             if let Some(outfile) = outfile {
                 let mut file = File::create(outfile)?;
@@ -61,11 +127,16 @@ pub fn entry(
                 println!("{}", json);
             }
         }
-        Command::Transaction { signature, outfile } => {
+        Command::Transaction { signature, idl_history, decode_idl_writes, outfile } => {
             let signature = Signature::from_str(signature)?;
-            let lens = AnchorLens::new(client);
+            let mut lens = lens(opts, client)?;
+            lens.decode_idl_writes = *decode_idl_writes;
             let tx = lens.get_versioned_transaction(&signature)?;
-            let json = lens.deserialize_transaction(tx)?;
+            let json = if *idl_history {
+                lens.deserialize_transaction_with_idl_history(tx)?
+            } else {
+                lens.deserialize_transaction(tx)?
+            };
             let json = serde_json::to_string_pretty(&json)?;
             if let Some(outfile) = outfile {
                 let mut file = File::create(outfile)?;
@@ -74,6 +145,86 @@ pub fn entry(
                 println!("{}", json);
             }
         }
+        Command::Idl { command: IdlCommand::Info { program_id } } => {
+            let lens = lens(opts, client)?;
+            let idl = lens.fetch_idl_with_metadata(program_id)?;
+            println!("{}", serde_json::to_string_pretty(&idl.info())?);
+        }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Sets up the lens with the global options.
+fn lens(opts: &Opts, client: RpcClient) -> Result<AnchorLens> {
+    let mut lens = AnchorLens::new_with_idl_caching(client);
+    lens.strictness = opts.strictness;
+    lens.idl_registry = idl_registry(opts, &lens.client.url())?;
+    lens.disk_cache = disk_cache(opts, &lens.client.url())?;
+    Ok(lens)
+}
+
+/// The on-disk IDL cache, if `--idl-cache` or `--idl-cache-dir` was given.
+fn disk_cache(opts: &Opts, url: &str) -> Result<Option<DiskIdlCache>> {
+    let dir = match (&opts.idl_cache_dir, opts.idl_cache) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, true) => DiskIdlCache::default_dir()
+            .ok_or(anyhow!("Could not find a cache directory, pass --idl-cache-dir"))?,
+        (None, false) => return Ok(None),
+    };
+    let mut cache = match &opts.cluster {
+        Some(cluster) => DiskIdlCache::new(dir, cluster),
+        None => DiskIdlCache::for_url(dir, url),
+    };
+    cache.revalidate = !opts.trust_idl_cache;
+    Ok(Some(cache))
+}
+
+/// Loads the IDLs of the Anchor workspace we're run from, if any,
+/// followed by the IDL files passed with `--idl` and `--idl-dir`.
+fn idl_registry(opts: &Opts, url: &str) -> Result<IdlRegistry> {
+    let mut registry = IdlRegistry::new();
+    if let Some(workspace) = AnchorWorkspace::find(std::env::current_dir()?)? {
+        let cluster = opts
+            .cluster
+            .clone()
+            .or(cluster_for_url(url).map(String::from))
+            .or(workspace.provider_cluster.clone())
+            .unwrap_or("localnet".to_string());
+        // The workspace may not deploy to the cluster we're pointed at.
+        if let Err(err) = workspace.load_idls(&cluster, &mut registry) {
+            eprintln!("Warning: {}, not loading the workspace's IDLs", err);
+        }
+    }
+    if let Some(idl_dir) = &opts.idl_dir {
+        let mut program_ids = HashMap::new();
+        for mapping in &opts.program_id {
+            let (name, program_id) = mapping
+                .split_once('=')
+                .ok_or(anyhow!("Expected NAME=PROGRAM_ID, got {}", mapping))?;
+            program_ids.insert(name.to_string(), Pubkey::from_str(program_id)?);
+        }
+        let (_, skipped) = registry.load_dir_skipping_unknown(idl_dir, &program_ids)?;
+        for skipped in skipped {
+            eprintln!(
+                "Warning: skipping IDL {}, which does not record its program ID, \
+                pass --program-id {}=<PROGRAM_ID> to load it",
+                skipped.path.display(),
+                skipped.name
+            );
+        }
+    }
+    for idl in &opts.idl {
+        match idl.split_once('=') {
+            Some((program_id, path)) => match program_id.split_once('@') {
+                Some((program_id, slot)) => {
+                    registry.load_file_at(Pubkey::from_str(program_id)?, slot.parse()?, path)?
+                }
+                None => registry.load_file_for(Pubkey::from_str(program_id)?, path)?,
+            },
+            None => {
+                registry.load_file(idl)?;
+            }
+        }
+    }
+    Ok(registry)
+}